@@ -0,0 +1,269 @@
+use std::{
+    fs::File,
+    io::{Write, copy},
+    path::{Path, PathBuf},
+};
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+};
+
+use crate::collection::{Collection, CollectionWriter, collection_error};
+
+const COLLECTION_FILE: &str = "collection.anki2";
+// written by Anki 2.1.28+ next to placeholder `collection.anki2`
+const COLLECTION_21_FILE: &str = "collection.anki21";
+// zstd compressed collection of Anki 2.1.50+, it's written only if legacy format is disabled
+const COLLECTION_21B_FILE: &str = "collection.anki21b";
+const MEDIA_FILE: &str = "media";
+
+/// Anki package is read as a whole, it's unpacked into temporary directory first.
+pub struct FromApkgDeck {
+    deck: Option<String>,
+    cards: Vec<Card>,
+}
+
+impl FromApkgDeck {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        use tempfile::NamedTempFile;
+
+        if !path.as_ref().exists() {
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let mut archive = ZipArchive::new(File::open(path)?).map_err(collection_error)?;
+        let name = [COLLECTION_21_FILE, COLLECTION_FILE]
+            .into_iter()
+            .find(|name| archive.index_for_name(name).is_some())
+            .ok_or_else(|| {
+                if archive.index_for_name(COLLECTION_21B_FILE).is_some() {
+                    Error::Collection("package is in format of Anki 2.1.50+, export it with 'Support older Anki versions' option".to_string())
+                } else {
+                    Error::Collection("invalid package, collection is missing".to_string())
+                }
+            })?;
+
+        let mut collection_file = NamedTempFile::new()?;
+        copy(
+            &mut archive.by_name(name).map_err(collection_error)?,
+            &mut collection_file,
+        )?;
+        collection_file.flush()?;
+
+        let collection = Collection::open(collection_file.path())?;
+
+        Ok(Self {
+            deck: collection.main_deck()?,
+            cards: collection.cards(None)?,
+        })
+    }
+
+    /// Name of deck of the first note in package.
+    pub fn deck(&self) -> Option<&str> {
+        self.deck.as_deref()
+    }
+
+    pub async fn for_each<A>(&self, action: A) -> Result<()>
+    where
+        A: AsyncFn(Card) -> Result<()>,
+    {
+        for card in self.cards.iter().cloned() {
+            action(card).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ToApkgDeck {
+    deck: String,
+    path: PathBuf,
+    media: Vec<(String, PathBuf)>,
+}
+
+impl ToApkgDeck {
+    pub fn new(deck: &str) -> Self {
+        Self {
+            deck: deck.to_string(),
+            path: PathBuf::from(format!("{deck}.apkg")),
+            media: Vec::new(),
+        }
+    }
+
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
+    /// Packs media files, given by names which cards refer to and paths of local files.
+    pub fn with_media(mut self, mut media: Vec<(String, PathBuf)>) -> Self {
+        media.sort();
+        self.media = media;
+        self
+    }
+
+    /// Writes package with collection in legacy format, which can be imported by any Anki
+    /// version. Package file is created only after all cards were written to collection.
+    pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
+        use tempfile::TempDir;
+
+        if self.path.exists() {
+            return Err(Error::FileExists(self.path.clone()));
+        }
+
+        let dir = TempDir::new()?;
+        let collection_path = dir.path().join(COLLECTION_FILE);
+        let mut collection = CollectionWriter::create(&collection_path, &self.deck)?;
+
+        for (i, card) in cards.enumerate() {
+            collection
+                .add(card)
+                .map_err(|e| Error::invalid(format!("failed to write card #{}: {e}", i + 1)))?;
+        }
+        collection.finish()?;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(File::create(&self.path)?);
+
+        zip.start_file(COLLECTION_FILE, options)
+            .map_err(collection_error)?;
+        copy(&mut File::open(&collection_path)?, &mut zip)?;
+
+        // media files are packed with numbers as names, `media` maps numbers to real names
+        let mut names = serde_json::Map::new();
+
+        for (i, (name, path)) in self.media.iter().enumerate() {
+            zip.start_file(i.to_string(), options)
+                .map_err(collection_error)?;
+            copy(&mut File::open(path)?, &mut zip)?;
+            names.insert(i.to_string(), name.clone().into());
+        }
+
+        zip.start_file(MEDIA_FILE, options)
+            .map_err(collection_error)?;
+        serde_json::to_writer(&mut zip, &names)?;
+        zip.finish().map_err(collection_error)?;
+
+        self.path
+            .to_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    pub async fn test_apkg_deck() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let deck = dir.path().join("Capitals");
+        let deck = deck.to_str().expect("failed to convert path");
+        let cards = vec![
+            Card::basic("What is the capital of Ukraine?", "Kyiv")
+                .with_tags(vec!["geography".to_string()]),
+            Card::cloze("{{c1::Berlin}} is the capital of Germany", "city"),
+        ];
+
+        let path = ToApkgDeck::new(deck)
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write package");
+
+        assert!(path.ends_with(".apkg"));
+        assert!(
+            ToApkgDeck::new(deck)
+                .write(cards.clone().into_iter())
+                .await
+                .is_err()
+        );
+
+        let package = FromApkgDeck::new(&path).expect("failed to read package");
+        let read = Mutex::new(Vec::new());
+
+        package
+            .for_each(async |card| {
+                read.lock()
+                    .expect("failed to lock cards")
+                    .push(card.with_id(None));
+                Ok(())
+            })
+            .await
+            .expect("failed to read cards");
+
+        assert_eq!(package.deck(), Some(deck));
+        assert_eq!(read.into_inner().expect("failed to unlock cards"), cards);
+
+        let mut archive = ZipArchive::new(File::open(&path).expect("failed to open package"))
+            .expect("invalid zip");
+        assert!(archive.by_name(MEDIA_FILE).is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn test_apkg_deck_media() {
+        use std::io::Read;
+
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let image = dir.path().join("heart.png");
+        std::fs::write(&image, b"png").expect("failed to write image");
+
+        let deck = dir.path().join("Hearts");
+        let path = ToApkgDeck::new(deck.to_str().expect("failed to convert path"))
+            .with_media(vec![("heart.png".to_string(), image)])
+            .write(vec![Card::basic(r#"<img src="heart.png">"#, "heart")].into_iter())
+            .await
+            .expect("failed to write package");
+
+        let mut archive = ZipArchive::new(File::open(&path).expect("failed to open package"))
+            .expect("invalid zip");
+        let mut names = String::new();
+        archive
+            .by_name(MEDIA_FILE)
+            .expect("media map is missing")
+            .read_to_string(&mut names)
+            .expect("failed to read media map");
+
+        assert_eq!(names, r#"{"0":"heart.png"}"#);
+
+        let mut data = Vec::new();
+        archive
+            .by_name("0")
+            .expect("media file is missing")
+            .read_to_end(&mut data)
+            .expect("failed to read media file");
+
+        assert_eq!(data, b"png");
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_apkg_deck() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let path = dir.path().join("empty.apkg");
+        let mut zip = ZipWriter::new(File::create(&path).expect("failed to create file"));
+        zip.start_file(COLLECTION_21B_FILE, SimpleFileOptions::default())
+            .expect("failed to add file");
+        zip.finish().expect("failed to write zip");
+
+        let error = FromApkgDeck::new(&path)
+            .err()
+            .expect("new package format should fail");
+        assert!(error.to_string().contains("Anki 2.1.50+"));
+
+        let deck = dir.path().join("invalid");
+        let deck = deck.to_str().expect("failed to convert path");
+        assert!(
+            ToApkgDeck::new(deck)
+                .write(vec![Card::cloze("no deletions", "")].into_iter())
+                .await
+                .is_err()
+        );
+        assert!(!dir.path().join("invalid.apkg").exists());
+        assert!(FromApkgDeck::new(dir.path().join("missing.apkg")).is_err());
+    }
+}
@@ -0,0 +1,867 @@
+use rusqlite::{Connection, ErrorCode, OpenFlags, OptionalExtension, params};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anki_multitool_ds::{
+    card::{CLOZE_MODEL, Card, Fields, known_model_fields},
+    error::{Error, Result},
+};
+
+const FIELD_SEPARATOR: char = '\x1f';
+// separator of nested deck names in `decks` table of newer collections
+const DECK_SEPARATOR: char = '\x1f';
+const DEFAULT_DECK_ID: i64 = 1;
+const DEFAULT_CONF_ID: i64 = 1;
+const SCHEMA_VERSION: i64 = 11;
+const ANSWER_SEPARATOR: &str = "{{FrontSide}}\n\n<hr id=answer>\n\n";
+const GUID_ALPHABET: &[u8] =
+    b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&()*+,-./:;<=>?@[]^_`{|}~";
+const CSS: &str = ".card {\n    font-family: arial;\n    font-size: 20px;\n    text-align: center;\n    color: black;\n    background-color: white;\n}\n";
+const LATEX_PRE: &str = "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n";
+const LATEX_POST: &str = "\\end{document}";
+
+/// Tables of collection in legacy format (schema 11), which is understood by every Anki version.
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null,
+    time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+fn field_ref(name: &str) -> String {
+    format!("{{{{{name}}}}}")
+}
+
+/// Removes HTML tags, as Anki does for sort field and checksum of note.
+fn strip_html(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    stripped
+}
+
+/// First 32 bits of SHA-1 of sort field, it's used by Anki to find duplicates.
+fn checksum(sort_field: &str) -> i64 {
+    use sha1::{Digest, Sha1};
+
+    let hash = Sha1::digest(sort_field.as_bytes());
+
+    i64::from(u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]))
+}
+
+/// Stable id of note, so importing the same package again updates notes instead of duplicating.
+fn guid(model: &str, values: &[String]) -> String {
+    use sha1::{Digest, Sha1};
+
+    let hash = Sha1::digest(format!("{model}{FIELD_SEPARATOR}{}", values.join("\x1f")));
+    let mut n = u64::from_be_bytes(hash[..8].try_into().expect("hash is longer than 8 bytes"));
+    let mut guid = String::new();
+
+    while n > 0 {
+        guid.push(GUID_ALPHABET[(n % GUID_ALPHABET.len() as u64) as usize] as char);
+        n /= GUID_ALPHABET.len() as u64;
+    }
+
+    guid
+}
+
+fn cloze_numbers(text: &str) -> BTreeSet<u32> {
+    text.match_indices("{{c")
+        .filter_map(|(i, _)| {
+            let rest = &text[i + 3..];
+            let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+
+            rest[digits..]
+                .starts_with("::")
+                .then(|| rest[..digits].parse().ok())
+                .flatten()
+        })
+        .collect()
+}
+
+struct Template {
+    name: &'static str,
+    front: String,
+    back: String,
+    /// Card of template is generated only if this field isn't empty.
+    required: Option<usize>,
+}
+
+impl Template {
+    fn new(name: &'static str, front: String, back: String) -> Self {
+        Self {
+            name,
+            front,
+            back,
+            required: None,
+        }
+    }
+}
+
+/// Note type written to collection, stock note types get the same templates as in Anki.
+struct NoteType {
+    id: i64,
+    name: String,
+    cloze: bool,
+    fields: Vec<String>,
+    templates: Vec<Template>,
+}
+
+impl NoteType {
+    fn for_card(id: i64, card: &Card) -> Self {
+        let fields: Vec<String> = match known_model_fields(&card.model) {
+            Some(fields) => fields.iter().map(|f| f.to_string()).collect(),
+            None => card.fields.keys().cloned().collect(),
+        };
+        let forward = || {
+            Template::new(
+                "Card 1",
+                field_ref("Front"),
+                format!("{ANSWER_SEPARATOR}{}", field_ref("Back")),
+            )
+        };
+        let reverse = || {
+            Template::new(
+                "Card 2",
+                field_ref("Back"),
+                format!("{ANSWER_SEPARATOR}{}", field_ref("Front")),
+            )
+        };
+
+        let templates = match card.model.as_str() {
+            "Basic" => vec![forward()],
+            "Basic (and reversed card)" => vec![forward(), reverse()],
+            "Basic (optional reversed card)" => vec![
+                forward(),
+                Template {
+                    front: format!(
+                        "{{{{#Add Reverse}}}}{}{{{{/Add Reverse}}}}",
+                        field_ref("Back")
+                    ),
+                    required: Some(2),
+                    ..reverse()
+                },
+            ],
+            "Basic (type in the answer)" => vec![Template::new(
+                "Card 1",
+                "{{Front}}\n\n{{type:Back}}".to_string(),
+                "{{Front}}\n\n<hr id=answer>\n\n{{type:Back}}".to_string(),
+            )],
+            CLOZE_MODEL => vec![Template::new(
+                "Cloze",
+                "{{cloze:Text}}".to_string(),
+                "{{cloze:Text}}<br>\n{{Back Extra}}".to_string(),
+            )],
+            _ => vec![Template::new(
+                "Card 1",
+                field_ref(&fields[0]),
+                format!(
+                    "{ANSWER_SEPARATOR}{}",
+                    fields[1..]
+                        .iter()
+                        .map(|f| field_ref(f))
+                        .collect::<Vec<_>>()
+                        .join("<br>\n")
+                ),
+            )],
+        };
+
+        Self {
+            id,
+            name: card.model.clone(),
+            cloze: card.model == CLOZE_MODEL,
+            fields,
+            templates,
+        }
+    }
+
+    /// Ordinals of cards generated for note with given values of fields.
+    fn card_ords(&self, values: &[String]) -> Vec<u32> {
+        if self.cloze {
+            return cloze_numbers(&values[0])
+                .into_iter()
+                .filter(|&n| n > 0)
+                .map(|n| n - 1)
+                .collect();
+        }
+
+        self.templates
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.required.is_none_or(|i| !values[i].trim().is_empty()))
+            .map(|(ord, _)| ord as u32)
+            .collect()
+    }
+
+    fn to_json(&self, deck_id: i64, modified: i64) -> Value {
+        json!({
+            "id": self.id,
+            "name": self.name,
+            "type": if self.cloze { 1 } else { 0 },
+            "mod": modified,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": self.templates.iter().enumerate().map(|(ord, t)| json!({
+                "name": t.name,
+                "ord": ord,
+                "qfmt": t.front,
+                "afmt": t.back,
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+                "bfont": "",
+                "bsize": 0,
+            })).collect::<Vec<_>>(),
+            "flds": self.fields.iter().enumerate().map(|(ord, name)| json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })).collect::<Vec<_>>(),
+            "css": CSS,
+            "latexPre": LATEX_PRE,
+            "latexPost": LATEX_POST,
+            "latexsvg": false,
+            "req": self.templates.iter().enumerate().map(|(ord, t)| match t.required {
+                Some(field) => json!([ord, "all", [field]]),
+                None => json!([ord, "any", [0]]),
+            }).collect::<Vec<_>>(),
+            "tags": [],
+            "vers": [],
+        })
+    }
+}
+
+fn deck_json(id: i64, name: &str, modified: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": "",
+        "mod": modified,
+        "usn": -1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+        "dyn": 0,
+        "conf": DEFAULT_CONF_ID,
+        "extendNew": 10,
+        "extendRev": 50,
+    })
+}
+
+fn deck_conf_json() -> Value {
+    json!({
+        "id": DEFAULT_CONF_ID,
+        "name": "Default",
+        "mod": 0,
+        "usn": 0,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "delays": [1, 10],
+            "ints": [1, 4, 7],
+            "initialFactor": 2500,
+            "order": 1,
+            "perDay": 20,
+            "bury": false,
+        },
+        "rev": {
+            "perDay": 200,
+            "ease4": 1.3,
+            "ivlFct": 1,
+            "maxIvl": 36500,
+            "bury": false,
+            "hardFactor": 1.2,
+        },
+        "lapse": {
+            "delays": [10],
+            "mult": 0,
+            "minInt": 1,
+            "leechFails": 8,
+            "leechAction": 1,
+        },
+    })
+}
+
+/// Writes cards into a new collection with single deck.
+pub struct CollectionWriter {
+    conn: Connection,
+    deck: String,
+    deck_id: i64,
+    now: i64,
+    next_id: i64,
+    note_types: HashMap<String, NoteType>,
+    position: i64,
+}
+
+impl CollectionWriter {
+    pub fn create<P: AsRef<Path>>(path: P, deck: &str) -> Result<Self> {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(collection_error)?
+            .as_millis() as i64;
+        let conn = Connection::open(path).map_err(collection_error)?;
+
+        conn.execute_batch(SCHEMA).map_err(collection_error)?;
+        conn.execute_batch("BEGIN").map_err(collection_error)?;
+
+        Ok(Self {
+            conn,
+            deck: deck.to_string(),
+            deck_id: now_ms,
+            now: now_ms / 1000,
+            next_id: now_ms + 1,
+            note_types: HashMap::new(),
+            position: 0,
+        })
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn add(&mut self, card: Card) -> Result<()> {
+        if !self.note_types.contains_key(&card.model) {
+            let id = self.next_id();
+            self.note_types
+                .insert(card.model.clone(), NoteType::for_card(id, &card));
+        }
+
+        let note_id = self.next_id();
+        let note_type = &self.note_types[&card.model];
+
+        if let Some(field) = card
+            .fields
+            .keys()
+            .find(|field| !note_type.fields.contains(field))
+        {
+            return Err(Error::invalid(format!(
+                "field '{field}' doesn't belong to note type '{}'",
+                card.model
+            )));
+        }
+
+        let values: Vec<String> = note_type
+            .fields
+            .iter()
+            .map(|field| card.fields.get(field).cloned().unwrap_or_default())
+            .collect();
+        let ords = note_type.card_ords(&values);
+
+        if ords.is_empty() {
+            return Err(Error::invalid(format!(
+                "note '{}' produces no cards",
+                card.front()
+            )));
+        }
+
+        let sort_field = strip_html(&values[0]);
+        let tags = if card.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", card.tags.join(" "))
+        };
+
+        self.conn
+            .execute(
+                "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+                params![
+                    note_id,
+                    guid(&card.model, &values),
+                    note_type.id,
+                    self.now,
+                    tags,
+                    values.join(&FIELD_SEPARATOR.to_string()),
+                    sort_field,
+                    checksum(&sort_field),
+                ],
+            )
+            .map_err(collection_error)?;
+
+        for ord in ords {
+            let card_id = self.next_id();
+
+            self.conn.execute(
+                "INSERT INTO cards VALUES (?1, ?2, ?3, ?4, ?5, -1, 0, 0, ?6, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                params![card_id, note_id, self.deck_id, ord, self.now, self.position],
+            ).map_err(collection_error)?;
+        }
+
+        self.position += 1;
+
+        Ok(())
+    }
+
+    /// Writes note types and decks, collection is valid only after it's finished.
+    pub fn finish(self) -> Result<()> {
+        let models: serde_json::Map<String, Value> = self
+            .note_types
+            .values()
+            .map(|t| (t.id.to_string(), t.to_json(self.deck_id, self.now)))
+            .collect();
+        let decks = json!({
+            DEFAULT_DECK_ID.to_string(): deck_json(DEFAULT_DECK_ID, "Default", self.now),
+            self.deck_id.to_string(): deck_json(self.deck_id, &self.deck, self.now),
+        });
+        let conf = json!({
+            "nextPos": self.position,
+            "estTimes": true,
+            "activeDecks": [DEFAULT_DECK_ID],
+            "sortType": "noteFld",
+            "timeLim": 0,
+            "sortBackwards": false,
+            "addToCur": true,
+            "curDeck": DEFAULT_DECK_ID,
+            "newSpread": 0,
+            "dueCounts": true,
+            "curModel": null,
+            "collapseTime": 1200,
+        });
+
+        self.conn
+            .execute(
+                "INSERT INTO col VALUES (1, ?1, ?2, ?2, ?3, 0, 0, 0, ?4, ?5, ?6, ?7, '{}')",
+                params![
+                    self.now,
+                    self.now * 1000,
+                    SCHEMA_VERSION,
+                    conf.to_string(),
+                    Value::Object(models).to_string(),
+                    decks.to_string(),
+                    json!({ DEFAULT_CONF_ID.to_string(): deck_conf_json() }).to_string(),
+                ],
+            )
+            .map_err(collection_error)?;
+        self.conn
+            .execute_batch("COMMIT")
+            .map_err(collection_error)?;
+
+        self.conn.close().map_err(|(_, e)| collection_error(e))
+    }
+}
+
+/// Errors of SQLite and of data stored in collection.
+pub(crate) fn collection_error(e: impl Display) -> Error {
+    Error::Collection(e.to_string())
+}
+
+#[derive(Deserialize)]
+struct ModelRepr {
+    name: String,
+    flds: Vec<FieldRepr>,
+}
+
+#[derive(Deserialize)]
+struct FieldRepr {
+    name: String,
+    ord: u32,
+}
+
+#[derive(Deserialize)]
+struct DeckRepr {
+    name: String,
+}
+
+struct Model {
+    name: String,
+    fields: Vec<String>,
+}
+
+/// Read-only access to notes of Anki collection. Both legacy collections, which keep note types
+/// and decks as JSON in `col` table, and collections of Anki 2.1.28+ with separate tables are
+/// supported.
+pub struct Collection {
+    conn: Connection,
+}
+
+impl Collection {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let conn = Connection::open_with_flags(
+            &path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(collection_error)?;
+        // Anki keeps collection locked exclusively while profile is open, fail instead of waiting
+        conn.busy_timeout(Duration::ZERO)
+            .map_err(collection_error)?;
+        // collation of names in collections of Anki 2.1.28+
+        conn.create_collation("unicase", |a: &str, b: &str| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        })
+        .map_err(collection_error)?;
+
+        match conn.query_row("SELECT ver FROM col", [], |row| row.get::<_, i64>(0)) {
+            Ok(_) => Ok(Self { conn }),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if matches!(e.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) =>
+            {
+                Err(Error::Collection(format!(
+                    "collection {} is locked by Anki, close Anki or use AnkiConnect",
+                    path.as_ref().display()
+                )))
+            }
+            Err(e) => Err(Error::Collection(format!(
+                "invalid collection {}: {e}",
+                path.as_ref().display()
+            ))),
+        }
+    }
+
+    fn has_table(&self, table: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(collection_error)?
+            .is_some())
+    }
+
+    fn models(&self) -> Result<HashMap<i64, Model>> {
+        if self.has_table("notetypes")? {
+            let mut models: HashMap<i64, Model> = self
+                .conn
+                .prepare("SELECT id, name FROM notetypes")
+                .map_err(collection_error)?
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        Model {
+                            name: row.get(1)?,
+                            fields: Vec::new(),
+                        },
+                    ))
+                })
+                .map_err(collection_error)?
+                .collect::<rusqlite::Result<_>>()
+                .map_err(collection_error)?;
+
+            let mut fields = self
+                .conn
+                .prepare("SELECT ntid, name FROM fields ORDER BY ntid, ord")
+                .map_err(collection_error)?;
+
+            for field in fields
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))
+                .map_err(collection_error)?
+            {
+                let (id, name) = field.map_err(collection_error)?;
+
+                if let Some(model) = models.get_mut(&id) {
+                    model.fields.push(name);
+                }
+            }
+
+            return Ok(models);
+        }
+
+        let models: String = self
+            .conn
+            .query_row("SELECT models FROM col", [], |row| row.get(0))
+            .map_err(collection_error)?;
+        let models: HashMap<String, ModelRepr> = serde_json::from_str(&models)?;
+
+        models
+            .into_iter()
+            .map(|(id, mut model)| {
+                model.flds.sort_by_key(|f| f.ord);
+
+                Ok((
+                    id.parse().map_err(collection_error)?,
+                    Model {
+                        name: model.name,
+                        fields: model.flds.into_iter().map(|f| f.name).collect(),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    fn decks(&self) -> Result<HashMap<i64, String>> {
+        if self.has_table("decks")? {
+            return self
+                .conn
+                .prepare("SELECT id, name FROM decks")
+                .map_err(collection_error)?
+                .query_map([], |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get::<_, String>(1)?.replace(DECK_SEPARATOR, "::"),
+                    ))
+                })
+                .map_err(collection_error)?
+                .collect::<rusqlite::Result<_>>()
+                .map_err(collection_error);
+        }
+
+        let decks: String = self
+            .conn
+            .query_row("SELECT decks FROM col", [], |row| row.get(0))
+            .map_err(collection_error)?;
+        let decks: HashMap<String, DeckRepr> = serde_json::from_str(&decks)?;
+
+        decks
+            .into_iter()
+            .map(|(id, deck)| Ok((id.parse().map_err(collection_error)?, deck.name)))
+            .collect()
+    }
+
+    /// Names of all decks, sorted.
+    pub fn deck_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.decks()?.into_values().collect();
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// Notes with name of deck of their first card, ordered by creation.
+    fn notes(&self) -> Result<Vec<(String, Card)>> {
+        let models = self.models()?;
+        let decks = self.decks()?;
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT n.id, n.mid, n.tags, n.flds, c.did FROM notes n
+             JOIN cards c ON c.id = (SELECT id FROM cards WHERE nid = n.id ORDER BY ord LIMIT 1)
+             ORDER BY n.id",
+            )
+            .map_err(collection_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(collection_error)?;
+
+        rows.map(|row| {
+            let (id, model_id, tags, values, deck_id) = row.map_err(collection_error)?;
+            let model = models.get(&model_id).ok_or_else(|| {
+                Error::Collection(format!("note {id} has unknown note type {model_id}"))
+            })?;
+            let fields: Fields = model
+                .fields
+                .iter()
+                .cloned()
+                .zip(values.split(FIELD_SEPARATOR).map(str::to_string))
+                .collect();
+            let deck = decks.get(&deck_id).cloned().unwrap_or_default();
+            let card = Card::new(model.name.clone(), fields)
+                .with_tags(tags.split_whitespace().map(str::to_string).collect())
+                .with_id(u64::try_from(id).ok());
+
+            Ok((deck, card))
+        })
+        .collect()
+    }
+
+    /// Cards of all notes, or of notes in given deck and its subdecks.
+    pub fn cards(&self, deck: Option<&str>) -> Result<Vec<Card>> {
+        let subdeck_prefix = deck.map(|deck| format!("{deck}::"));
+
+        Ok(self
+            .notes()?
+            .into_iter()
+            .filter(|(name, _)| match (deck, &subdeck_prefix) {
+                (Some(deck), Some(prefix)) => name == deck || name.starts_with(prefix.as_str()),
+                _ => true,
+            })
+            .map(|(_, card)| card)
+            .collect())
+    }
+
+    /// Deck of the first note, packages exported from Anki usually contain single deck.
+    pub fn main_deck(&self) -> Result<Option<String>> {
+        Ok(self.notes()?.into_iter().next().map(|(deck, _)| deck))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collection() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let path = dir.path().join("collection.anki2");
+        let cards = vec![
+            Card::basic("<b>Q1</b>", "A1").with_tags(vec!["a".to_string(), "b".to_string()]),
+            Card::cloze("{{c1::Kyiv}} is the capital of {{c2::Ukraine}}", ""),
+            Card::new(
+                "Basic (optional reversed card)",
+                Fields::from([
+                    ("Front".to_string(), "Q3".to_string()),
+                    ("Back".to_string(), "A3".to_string()),
+                ]),
+            ),
+            Card::new(
+                "Vocabulary",
+                Fields::from([
+                    ("Word".to_string(), "серце".to_string()),
+                    ("Meaning".to_string(), "heart".to_string()),
+                ]),
+            ),
+        ];
+
+        let mut writer = CollectionWriter::create(&path, "Words::Ukrainian")
+            .expect("failed to create collection");
+        for card in cards.clone() {
+            writer.add(card).expect("failed to add card");
+        }
+        writer.finish().expect("failed to finish collection");
+
+        let collection = Collection::open(&path).expect("failed to open collection");
+        let card_count: i64 = collection
+            .conn
+            .query_row("SELECT COUNT(*) FROM cards", [], |row| row.get(0))
+            .expect("failed to count cards");
+        // two cloze deletions, reversed card is optional
+        assert_eq!(card_count, 5);
+
+        assert_eq!(
+            collection.deck_names().expect("failed to read decks"),
+            vec!["Default".to_string(), "Words::Ukrainian".to_string()]
+        );
+        assert_eq!(
+            collection.main_deck().expect("failed to read deck"),
+            Some("Words::Ukrainian".to_string())
+        );
+
+        let read: Vec<Card> = collection
+            .cards(Some("Words"))
+            .expect("failed to read cards")
+            .into_iter()
+            .map(|card| card.with_id(None))
+            .collect();
+        let mut expected = cards;
+        expected[1]
+            .fields
+            .insert("Back Extra".to_string(), String::new());
+        expected[2]
+            .fields
+            .insert("Add Reverse".to_string(), String::new());
+
+        assert_eq!(read, expected);
+        assert!(
+            collection
+                .cards(Some("Word"))
+                .expect("failed to read cards")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_failed_collection() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let mut writer = CollectionWriter::create(dir.path().join("collection.anki2"), "Deck")
+            .expect("failed to create collection");
+
+        assert!(
+            writer
+                .add(Card::new(
+                    "Basic",
+                    Fields::from([("Question".to_string(), "Q1".to_string())])
+                ))
+                .is_err()
+        );
+        assert!(writer.add(Card::cloze("no deletions", "")).is_err());
+        assert!(Collection::open(dir.path().join("missing.anki2")).is_err());
+    }
+
+    #[test]
+    fn test_locked_collection() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let path = dir.path().join("collection.anki2");
+        CollectionWriter::create(&path, "Deck")
+            .expect("failed to create collection")
+            .finish()
+            .expect("failed to write collection");
+
+        assert!(Collection::open(&path).is_ok());
+
+        // the same lock is held by Anki while profile is open
+        let anki = Connection::open(&path).expect("failed to open collection");
+        anki.execute_batch("PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE;")
+            .expect("failed to lock collection");
+
+        let error = Collection::open(&path)
+            .err()
+            .expect("locked collection should fail");
+        assert!(error.to_string().contains("locked by Anki"));
+
+        drop(anki);
+        assert!(Collection::open(&path).is_ok());
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(strip_html("<b>Kyiv</b> <br/>city"), "Kyiv city");
+        // int(sha1("Kyiv").hexdigest()[:8], 16)
+        assert_eq!(checksum("Kyiv"), 0xda2e_0541);
+    }
+}
@@ -0,0 +1,676 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anki_multitool_ds::{
+    card::{BASIC_MODEL, Card, Fields, known_model_fields},
+    error::{Error, Result},
+};
+use anki_multitool_util::commit::FileCommitBuffer;
+
+pub const DEFAULT_DELIMITER: u8 = b',';
+pub const TSV_DELIMITER: u8 = b'\t';
+
+const ID_COLUMN: &str = "id";
+const TAGS_COLUMN: &str = "tags";
+// note types which can be recognized by names of columns in header
+const GUESSED_MODELS: [&str; 3] = ["Basic", "Cloze", "Basic (optional reversed card)"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// First row contains names of columns: fields of note type, `tags` and `id`.
+    pub has_header: bool,
+    /// Note type of cards, if not set, it's recognized by names of columns or "Basic" is used.
+    pub model: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: DEFAULT_DELIMITER,
+            has_header: true,
+            model: None,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Default options for file, `.tsv` and `.txt` (Anki's "Notes in Plain Text") files are
+    /// tab separated.
+    pub fn for_path<P: AsRef<Path>>(path: P) -> Self {
+        let delimiter = match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("tsv" | "txt") => TSV_DELIMITER,
+            _ => DEFAULT_DELIMITER,
+        };
+
+        Self {
+            delimiter,
+            ..Self::default()
+        }
+    }
+}
+
+/// File headers of Anki's plain text export, e.g. `#separator:tab` or `#tags column:3`.
+#[derive(Default)]
+struct Directives {
+    found: bool,
+    separator: Option<u8>,
+    notetype: Option<String>,
+    columns: Option<String>,
+    tags_column: Option<usize>,
+}
+
+impl Directives {
+    /// Consumes leading `#key:value` lines of reader.
+    fn read(reader: &mut impl BufRead) -> Result<(Self, usize)> {
+        let mut directives = Self::default();
+        let mut lines = 0;
+
+        while reader.fill_buf()?.first() == Some(&b'#') {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            lines += 1;
+
+            let Some((key, value)) = line[1..].trim_end_matches(['\r', '\n']).split_once(':')
+            else {
+                continue;
+            };
+
+            directives.found = true;
+
+            match key.trim() {
+                "separator" => directives.separator = Some(Self::parse_separator(value)?),
+                "notetype" => directives.notetype = Some(value.trim().to_string()),
+                "columns" => directives.columns = Some(value.to_string()),
+                "tags column" => {
+                    directives.tags_column = Some(value.trim().parse().map_err(|_| {
+                        Error::invalid(format!("invalid tags column '{}'", value.trim()))
+                    })?)
+                }
+                _ => {}
+            }
+        }
+
+        Ok((directives, lines))
+    }
+
+    fn parse_separator(value: &str) -> Result<u8> {
+        match value.trim() {
+            "Comma" | "comma" => Ok(b','),
+            "Semicolon" | "semicolon" => Ok(b';'),
+            "Tab" | "tab" => Ok(b'\t'),
+            "Space" | "space" => Ok(b' '),
+            "Pipe" | "pipe" => Ok(b'|'),
+            "Colon" | "colon" => Ok(b':'),
+            _ => parse_delimiter(value),
+        }
+    }
+}
+
+/// Parses delimiter given as single ASCII character or `tab`.
+pub fn parse_delimiter(value: &str) -> Result<u8> {
+    match value {
+        "tab" | "\\t" => Ok(TSV_DELIMITER),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(Error::invalid(format!(
+            "invalid delimiter '{value}', expected single ASCII character or 'tab'"
+        ))),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Column {
+    Field(String),
+    Tags,
+    Id,
+}
+
+impl Column {
+    fn of(name: &str) -> Self {
+        match name.trim() {
+            n if n.eq_ignore_ascii_case(TAGS_COLUMN) => Column::Tags,
+            n if n.eq_ignore_ascii_case(ID_COLUMN) => Column::Id,
+            n => Column::Field(n.to_string()),
+        }
+    }
+}
+
+/// Mapping of columns of file to note fields.
+struct Layout {
+    model: String,
+    columns: Vec<Column>,
+    /// Column right after the last known one holds tags, as in Anki export with tags.
+    trailing_tags: bool,
+}
+
+impl Layout {
+    fn from_header(names: Vec<&str>, model: Option<String>) -> Result<Self> {
+        let mut columns: Vec<Column> = names.into_iter().map(Column::of).collect();
+        let fields: Vec<&str> = columns
+            .iter()
+            .filter_map(|c| match c {
+                Column::Field(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        if fields.is_empty() {
+            return Err(Error::invalid("header has no columns for note fields"));
+        }
+
+        let model = match model {
+            Some(model) => model,
+            None => {
+                let (model, known) = GUESSED_MODELS
+                    .iter()
+                    .find_map(|model| {
+                        let known = known_model_fields(model)?;
+                        (known.len() == fields.len()
+                            && known
+                                .iter()
+                                .zip(&fields)
+                                .all(|(k, f)| k.eq_ignore_ascii_case(f)))
+                        .then_some((model, known))
+                    })
+                    .ok_or_else(|| {
+                        Error::invalid(format!(
+                            "note type with fields {} is unknown, specify it with model option",
+                            fields.join(", ")
+                        ))
+                    })?;
+
+                // names of columns are case insensitive, fields of note are not
+                let mut known = known.iter();
+                for column in columns.iter_mut() {
+                    if let (Column::Field(name), Some(k)) = (&mut *column, known.next()) {
+                        *name = k.to_string();
+                    }
+                }
+
+                model.to_string()
+            }
+        };
+
+        Ok(Self {
+            model,
+            columns,
+            trailing_tags: false,
+        })
+    }
+
+    fn from_model(model: Option<String>, tags_column: Option<usize>) -> Result<Self> {
+        let model = model.unwrap_or_else(|| BASIC_MODEL.to_string());
+        let mut columns: Vec<Column> = known_model_fields(&model)
+            .ok_or_else(|| {
+                Error::invalid(format!(
+                    "fields of note type '{model}' are unknown, add header row to file"
+                ))
+            })?
+            .iter()
+            .map(|name| Column::Field(name.to_string()))
+            .collect();
+
+        if let Some(tags_column) = tags_column {
+            if tags_column == 0 || tags_column > columns.len() + 1 {
+                return Err(Error::invalid(format!("invalid tags column {tags_column}")));
+            }
+            columns.insert(tags_column - 1, Column::Tags);
+        }
+
+        Ok(Self {
+            model,
+            columns,
+            trailing_tags: tags_column.is_none(),
+        })
+    }
+
+    fn card(&self, record: &::csv::StringRecord) -> Result<Card> {
+        let max_len = self.columns.len() + usize::from(self.trailing_tags);
+
+        if record.len() > max_len {
+            return Err(Error::invalid(format!(
+                "row has {} columns, expected at most {max_len}",
+                record.len()
+            )));
+        }
+
+        let mut fields: Fields = self
+            .columns
+            .iter()
+            .filter_map(|c| match c {
+                Column::Field(name) => Some((name.clone(), String::new())),
+                _ => None,
+            })
+            .collect();
+        let mut tags = Vec::new();
+        let mut id = None;
+
+        for (i, value) in record.iter().enumerate() {
+            match self.columns.get(i) {
+                Some(Column::Field(name)) => {
+                    fields.insert(name.clone(), value.to_string());
+                }
+                Some(Column::Id) if !value.trim().is_empty() => {
+                    id = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| Error::invalid(format!("invalid note id '{value}'")))?,
+                    );
+                }
+                Some(Column::Id) => {}
+                Some(Column::Tags) | None => {
+                    tags.extend(value.split_whitespace().map(str::to_string));
+                }
+            }
+        }
+
+        if fields.values().all(|v| v.trim().is_empty()) {
+            return Err(Error::invalid("all fields are empty"));
+        }
+
+        Ok(Card::new(self.model.clone(), fields)
+            .with_tags(tags)
+            .with_id(id))
+    }
+}
+
+struct CsvCardStream {
+    path: PathBuf,
+    records: ::csv::StringRecordsIntoIter<BufReader<File>>,
+    layout: Layout,
+    // lines consumed by directives, `csv` counts lines from where it started reading
+    skipped_lines: u64,
+}
+
+impl CsvCardStream {
+    fn new<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<Self> {
+        use ::csv::ReaderBuilder;
+
+        if !path.as_ref().exists() {
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let path = path.as_ref();
+        let invalid = |e: Error| Error::parse(path, None, e);
+        let mut reader = BufReader::new(File::open(path)?);
+        let (directives, skipped_lines) = Directives::read(&mut reader).map_err(invalid)?;
+        let delimiter = directives.separator.unwrap_or(options.delimiter);
+        let model = options.model.clone().or(directives.notetype);
+        let mut records = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader)
+            .into_records();
+
+        // Anki export has no header row, but names of columns can be given with directive
+        let layout = if let Some(columns) = directives.columns {
+            Layout::from_header(columns.split(delimiter as char).collect(), model)
+        } else if options.has_header && !directives.found {
+            let header = records
+                .next()
+                .ok_or_else(|| Error::invalid("invalid CSV, header row is missing"))?
+                .map_err(|e| Error::invalid(format!("invalid CSV: {e}")))?;
+            Layout::from_header(header.iter().collect(), model)
+        } else {
+            Layout::from_model(model, directives.tags_column)
+        }
+        .map_err(invalid)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            records,
+            layout,
+            skipped_lines: skipped_lines as u64,
+        })
+    }
+}
+
+impl Iterator for CsvCardStream {
+    type Item = Result<Card>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e
+                    .position()
+                    .map(|p| (p.line() + self.skipped_lines) as usize);
+                return Some(Err(Error::parse(
+                    &self.path,
+                    line,
+                    format!("invalid CSV: {e}"),
+                )));
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or(0) + self.skipped_lines;
+
+        Some(
+            self.layout
+                .card(&record)
+                .map_err(|e| Error::parse(&self.path, Some(line as usize), e)),
+        )
+    }
+}
+
+pub struct FromCsvDeck {
+    path: PathBuf,
+    options: CsvOptions,
+}
+
+impl FromCsvDeck {
+    pub fn new<P: AsRef<Path>>(path: P, options: CsvOptions) -> Result<Self> {
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            options,
+        })
+    }
+
+    pub async fn for_each<A>(&self, action: A) -> Result<()>
+    where
+        A: AsyncFn(Card) -> Result<()>,
+    {
+        FileCommitBuffer::new(async |data| data, async |card| action(card).await)?
+            .exec_and_commit(CsvCardStream::new(&self.path, &self.options)?)
+            .await
+    }
+}
+
+pub struct ToCsvDeck {
+    path: PathBuf,
+    delimiter: u8,
+}
+
+impl ToCsvDeck {
+    /// Tab separated deck is written to `.tsv` file.
+    pub fn new(deck: &str, delimiter: u8) -> Self {
+        let extension = if delimiter == TSV_DELIMITER {
+            "tsv"
+        } else {
+            "csv"
+        };
+
+        Self {
+            path: PathBuf::from(format!("{deck}.{extension}")),
+            delimiter,
+        }
+    }
+
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
+    /// Writes header row with fields of the first card, `tags` column and `id` column for cards
+    /// with ids, all cards must have the same fields.
+    pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
+        use ::csv::WriterBuilder;
+        use std::sync::Mutex;
+
+        if self.path.exists() {
+            return Err(Error::FileExists(self.path.clone()));
+        }
+
+        let writer = Mutex::new(
+            WriterBuilder::new()
+                .delimiter(self.delimiter)
+                .from_writer(BufWriter::new(File::create(&self.path)?)),
+        );
+        let header: Mutex<Option<(Vec<String>, bool)>> = Mutex::new(None);
+
+        FileCommitBuffer::new(
+            async |card| Ok(card),
+            async |card: Card| {
+                let mut writer = writer
+                    .lock()
+                    .map_err(|_| Error::invalid("failed to lock writer"))?;
+                let mut header = header
+                    .lock()
+                    .map_err(|_| Error::invalid("failed to lock header"))?;
+                let fields: Vec<String> = card.fields.keys().cloned().collect();
+
+                let (header_fields, with_id) = match &*header {
+                    Some(header) => header,
+                    None => {
+                        let with_id = card.id.is_some();
+                        let mut row: Vec<&str> = Vec::new();
+
+                        if with_id {
+                            row.push(ID_COLUMN);
+                        }
+                        row.extend(fields.iter().map(String::as_str));
+                        row.push(TAGS_COLUMN);
+                        writer.write_record(&row).map_err(std::io::Error::from)?;
+
+                        header.insert((fields.clone(), with_id))
+                    }
+                };
+
+                if *header_fields != fields {
+                    return Err(Error::invalid(format!(
+                        "cards with fields {} and {} can't be written to single CSV file",
+                        header_fields.join(", "),
+                        fields.join(", ")
+                    )));
+                }
+
+                let mut row: Vec<String> = Vec::new();
+
+                if *with_id {
+                    row.push(card.id.map(|id| id.to_string()).unwrap_or_default());
+                }
+                row.extend(card.fields.into_values());
+                row.push(card.tags.join(" "));
+
+                writer
+                    .write_record(&row)
+                    .map_err(|e| std::io::Error::from(e).into())
+            },
+        )?
+        .exec_and_commit(cards)
+        .await?;
+
+        writer
+            .into_inner()
+            .map_err(|_| Error::invalid("failed to unlock writer"))?
+            .flush()?;
+
+        self.path
+            .to_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::read_to_string, sync::Mutex};
+    use tempfile::{NamedTempFile, TempDir};
+
+    async fn read_cards(content: &str, options: CsvOptions) -> Result<Vec<Card>> {
+        use std::io::Write;
+
+        let mut file = NamedTempFile::new().expect("failed to create temp file");
+        file.write_all(content.as_bytes())
+            .expect("failed to write to file");
+
+        let cards = Mutex::new(Vec::new());
+
+        FromCsvDeck::new(file.path(), options)?
+            .for_each(async |card| {
+                cards.lock().expect("failed to lock cards").push(card);
+                Ok(())
+            })
+            .await?;
+
+        Ok(cards.into_inner().expect("failed to unlock cards"))
+    }
+
+    #[tokio::test]
+    pub async fn test_from_csv_deck() {
+        let cards = read_cards(
+            "front,back,tags\nQ1,A1,geography capitals\n\"Q2, with comma\",\"multi\nline \"\"quoted\"\"\",\n",
+            CsvOptions::default(),
+        )
+        .await
+        .expect("failed to read cards");
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::basic("Q1", "A1")
+                    .with_tags(vec!["geography".to_string(), "capitals".to_string()]),
+                Card::basic("Q2, with comma", "multi\nline \"quoted\""),
+            ]
+        );
+
+        let cards = read_cards(
+            "Q1;A1\nQ2;A2;tag\n",
+            CsvOptions {
+                delimiter: b';',
+                has_header: false,
+                model: None,
+            },
+        )
+        .await
+        .expect("failed to read cards");
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::basic("Q1", "A1"),
+                Card::basic("Q2", "A2").with_tags(vec!["tag".to_string()]),
+            ]
+        );
+
+        let cards = read_cards(
+            "id,Word,Meaning\n7,серце,heart\n",
+            CsvOptions {
+                model: Some("Vocabulary".to_string()),
+                ..CsvOptions::default()
+            },
+        )
+        .await
+        .expect("failed to read cards");
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::new(
+                    "Vocabulary",
+                    Fields::from([
+                        ("Word".to_string(), "серце".to_string()),
+                        ("Meaning".to_string(), "heart".to_string()),
+                    ])
+                )
+                .with_id(Some(7))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_from_anki_plain_text() {
+        let cards = read_cards(
+            "#separator:tab\n#html:true\n#tags column:3\n{{c1::Kyiv}} is the capital\tcity\tgeography\n",
+            CsvOptions {
+                model: Some("Cloze".to_string()),
+                ..CsvOptions::default()
+            },
+        )
+        .await
+        .expect("failed to read cards");
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::cloze("{{c1::Kyiv}} is the capital", "city")
+                    .with_tags(vec!["geography".to_string()])
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_from_csv_deck() {
+        let error = read_cards("front,back\nQ1,A1\n,\n", CsvOptions::default())
+            .await
+            .expect_err("empty card should fail");
+        assert!(matches!(
+            error,
+            Error::Parse { line: Some(3), ref message, .. } if message == "all fields are empty"
+        ));
+
+        assert!(
+            read_cards("front,back\nQ1,A1,tags,extra\n", CsvOptions::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            read_cards("Word,Meaning\nсерце,heart\n", CsvOptions::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            read_cards("Q1,\"unclosed\n", CsvOptions::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_to_csv_deck() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let deck = dir.path().join("deck");
+        let deck = deck.to_str().expect("failed to convert path");
+        let cards = vec![
+            Card::basic("Q1", "multi\nline, \"quoted\"").with_tags(vec!["a".to_string()]),
+            Card::basic("Q2", "A2"),
+        ];
+
+        let path = ToCsvDeck::new(deck, DEFAULT_DELIMITER)
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write deck");
+
+        assert_eq!(
+            read_to_string(&path).expect("failed to read file"),
+            "Front,Back,tags\nQ1,\"multi\nline, \"\"quoted\"\"\",a\nQ2,A2,\n"
+        );
+        assert_eq!(
+            read_cards(
+                &read_to_string(&path).expect("failed to read file"),
+                CsvOptions::default()
+            )
+            .await
+            .expect("failed to read cards"),
+            cards
+        );
+        assert!(
+            ToCsvDeck::new(deck, DEFAULT_DELIMITER)
+                .write(cards.clone().into_iter())
+                .await
+                .is_err()
+        );
+
+        let path = ToCsvDeck::new(deck, TSV_DELIMITER)
+            .write(
+                vec![
+                    Card::basic("Q1", "A1").with_id(Some(1)),
+                    Card::cloze("{{c1::Kyiv}}", ""),
+                ]
+                .into_iter(),
+            )
+            .await
+            .expect_err("cards of different note types should fail");
+
+        assert_eq!(
+            path.to_string(),
+            "cards with fields Front, Back and Text, Back Extra can't be written to single CSV file"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+};
+
+const MODEL_KEY: &str = "model";
+const CLOZE_KEY: &str = "cloze";
+
+/// Deck with metadata, shared by YAML and TOML formats. Cards are kept as generic values until
+/// defaults of document are applied to them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DocumentRepr {
+    deck: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    cards: Vec<Value>,
+}
+
+/// Document is either bare list of cards, as in JSON format, or table with metadata.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DocumentShape {
+    Cards(Vec<Value>),
+    Document(DocumentRepr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeckDocument {
+    pub deck: Option<String>,
+    pub cards: Vec<Card>,
+}
+
+impl DeckDocument {
+    /// Builds cards of document: note type of document is used for cards without one, tags of
+    /// document are added to every card. `position_of` finds line and column of syntax error in
+    /// file at `path`.
+    pub fn from_deserializer<'de, D, P>(
+        path: &Path,
+        deserializer: D,
+        position_of: P,
+    ) -> Result<Self>
+    where
+        D: serde::Deserializer<'de>,
+        P: Fn(&D::Error) -> Option<(usize, usize)>,
+    {
+        let shape =
+            DocumentShape::deserialize(deserializer).map_err(|e| match position_of(&e) {
+                Some((line, column)) => Error::parse_at(path, line, column, e),
+                None => Error::parse(path, None, e),
+            })?;
+        let repr = match shape {
+            DocumentShape::Cards(cards) => DocumentRepr {
+                cards,
+                ..DocumentRepr::default()
+            },
+            DocumentShape::Document(repr) => repr,
+        };
+
+        if let Some(tag) = repr
+            .tags
+            .iter()
+            .find(|t| t.is_empty() || t.contains(char::is_whitespace))
+        {
+            return Err(Error::parse(
+                path,
+                None,
+                format!("invalid tag '{tag}' of deck, tags can't be empty or contain spaces"),
+            ));
+        }
+
+        let cards = repr
+            .cards
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut value)| {
+                if let (Some(model), Value::Object(card)) = (&repr.model, &mut value)
+                    && !card.contains_key(MODEL_KEY)
+                    && !card.contains_key(CLOZE_KEY)
+                {
+                    card.insert(MODEL_KEY.to_string(), Value::String(model.clone()));
+                }
+
+                let mut card: Card = serde_json::from_value(value).map_err(|e| {
+                    Error::parse(path, None, format!("invalid card #{}: {e}", i + 1))
+                })?;
+                card.add_tags(&repr.tags);
+
+                Ok(card)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            deck: repr.deck,
+            cards,
+        })
+    }
+}
+
+/// Document written on export, cards keep their own note types and tags.
+#[derive(Serialize)]
+pub(crate) struct DeckDocumentRef<'a> {
+    pub deck: &'a str,
+    pub cards: &'a [Card],
+}
@@ -0,0 +1,339 @@
+/// Tag of HTML along with its length in source.
+struct HtmlTag<'a> {
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, Option<&'a str>)>,
+    len: usize,
+}
+
+impl<'a> HtmlTag<'a> {
+    /// Parses tag at the start of string, `None` if string doesn't start with a tag.
+    fn parse(s: &'a str) -> Option<Self> {
+        let mut rest = s.strip_prefix('<')?;
+        let closing = rest.starts_with('/');
+
+        if closing {
+            rest = &rest[1..];
+        }
+
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+
+        if name_len == 0 || !rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        let name = rest[..name_len].to_ascii_lowercase();
+        let mut attributes = Vec::new();
+
+        rest = &rest[name_len..];
+
+        loop {
+            rest = rest.trim_start();
+
+            if let Some(end) = rest.strip_prefix('>').or_else(|| rest.strip_prefix("/>")) {
+                return Some(Self {
+                    name,
+                    closing,
+                    attributes,
+                    len: s.len() - end.len(),
+                });
+            }
+
+            let key_len = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/' | '"' | '\''))
+                .filter(|&len| len > 0)?;
+            let key = rest[..key_len].to_ascii_lowercase();
+
+            rest = rest[key_len..].trim_start();
+
+            let value = match rest.strip_prefix('=') {
+                Some(value) => {
+                    let value = value.trim_start();
+                    let (value, remaining) = match value.chars().next()? {
+                        quote @ ('"' | '\'') => {
+                            let end = value[1..].find(quote)? + 1;
+                            (&value[1..end], &value[end + 1..])
+                        }
+                        _ => {
+                            let end = value
+                                .find(|c: char| c.is_whitespace() || c == '>')
+                                .unwrap_or(value.len());
+                            (&value[..end], &value[end..])
+                        }
+                    };
+
+                    rest = remaining;
+                    Some(value)
+                }
+                None => None,
+            };
+
+            attributes.push((key, value));
+        }
+    }
+
+    /// Values of given attributes, if tag has no other ones. Missing attributes are empty.
+    fn attributes_of<const N: usize>(&self, keys: [&str; N]) -> Option<[&'a str; N]> {
+        let mut values = [""; N];
+
+        for (key, value) in &self.attributes {
+            let i = keys.iter().position(|k| k == key)?;
+            values[i] = (*value)?;
+        }
+
+        Some(values)
+    }
+}
+
+/// Checks whether destination of link or image can be written as is in Markdown.
+fn is_plain_destination(url: &str) -> bool {
+    !url.is_empty()
+        && !url.contains(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '<' | '>' | '\\'))
+}
+
+/// Escapes characters of text which have meaning in Markdown, `line_start` is set when text
+/// starts a line, where block markers like `#` or `1.` have to be escaped too.
+fn push_text(markdown: &mut String, text: &str, line_start: &mut bool) {
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if *line_start && c.is_ascii_digit() {
+            markdown.push(c);
+
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                markdown.push(digit);
+            }
+            if chars.peek().is_some_and(|c| matches!(c, '.' | ')')) {
+                markdown.push('\\');
+            }
+
+            *line_start = false;
+            continue;
+        }
+
+        let block_marker = *line_start && matches!(c, '#' | '>' | '-' | '+' | '=' | '~');
+
+        if block_marker || matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            markdown.push('\\');
+        }
+
+        markdown.push(c);
+
+        if c == '\n' {
+            *line_start = true;
+        } else if !c.is_whitespace() {
+            *line_start = false;
+        }
+    }
+}
+
+/// Writes content of `<code>` as code span, fence is longer than any run of backticks inside.
+fn push_code(markdown: &mut String, code: &str) {
+    let code = code
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    let longest = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest + 1);
+    let padding = if code.starts_with('`') || code.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+
+    markdown.push_str(&format!("{fence}{padding}{code}{padding}{fence}"));
+}
+
+/// Converts HTML of field to Markdown: bold, italic, code, links, images and line breaks are
+/// written in Markdown syntax, other tags are kept as raw HTML.
+pub(crate) fn to_markdown(html: &str) -> String {
+    let mut markdown = String::with_capacity(html.len());
+    // destinations of open links, `None` for links kept as raw HTML
+    let mut links: Vec<Option<&str>> = Vec::new();
+    let mut line_start = true;
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("<!--")
+            && let Some(end) = rest.find("-->")
+        {
+            markdown.push_str(&rest[..end + 3]);
+            rest = &rest[end + 3..];
+            line_start = false;
+            continue;
+        }
+
+        let Some(tag) = (c == '<').then(|| HtmlTag::parse(rest)).flatten() else {
+            let end = rest[c.len_utf8()..]
+                .find('<')
+                .map_or(rest.len(), |i| i + c.len_utf8());
+
+            push_text(&mut markdown, &rest[..end], &mut line_start);
+            rest = &rest[end..];
+            continue;
+        };
+
+        let raw = &rest[..tag.len];
+        let plain = tag.attributes.is_empty();
+
+        rest = &rest[tag.len..];
+        line_start = false;
+
+        // code span can hold only text
+        let code_end = rest
+            .find("</code>")
+            .filter(|&end| plain && !rest[..end].contains('<'));
+
+        match (tag.name.as_str(), tag.closing) {
+            ("b" | "strong", _) if plain => markdown.push_str("**"),
+            ("i" | "em", _) if plain => markdown.push('*'),
+            ("br", false) if plain => {
+                markdown.push_str("\\\n");
+                line_start = true;
+            }
+            ("code", false) if code_end.is_some() => {
+                let end = code_end.unwrap_or_default();
+
+                push_code(&mut markdown, &rest[..end]);
+                rest = &rest[end + "</code>".len()..];
+            }
+            ("a", false) => {
+                let href = tag
+                    .attributes_of(["href"])
+                    .map(|[href]| href)
+                    .filter(|href| is_plain_destination(href));
+
+                markdown.push_str(if href.is_some() { "[" } else { raw });
+                links.push(href);
+            }
+            ("a", true) => match links.pop().flatten() {
+                Some(href) => markdown.push_str(&format!("]({href})")),
+                None => markdown.push_str(raw),
+            },
+            ("img", false) => match tag.attributes_of(["src", "alt"]) {
+                Some([src, alt]) if is_plain_destination(src) => {
+                    markdown.push_str("![");
+                    push_text(&mut markdown, alt, &mut line_start);
+                    markdown.push_str(&format!("]({src})"));
+                    line_start = false;
+                }
+                _ => markdown.push_str(raw),
+            },
+            _ => markdown.push_str(raw),
+        }
+    }
+
+    markdown
+}
+
+/// Converts HTML of field to Markdown which is read literally: tags, comments and entities are
+/// kept as they are, punctuation of text is escaped and line breaks are written as `&#10;`, so
+/// value stays on a single line.
+pub(crate) fn to_literal(html: &str) -> String {
+    let mut markdown = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        let raw_len = match c {
+            '<' if rest.starts_with("<!--") => rest.find("-->").map(|end| end + 3),
+            '<' => HtmlTag::parse(rest).map(|tag| tag.len),
+            '&' => rest
+                .find(';')
+                .filter(|&end| {
+                    let name = rest[1..end].strip_prefix('#').unwrap_or(&rest[1..end]);
+                    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric())
+                })
+                .map(|end| end + 1),
+            _ => None,
+        };
+
+        if let Some(len) = raw_len {
+            markdown.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        match c {
+            '\n' => markdown.push_str("&#10;"),
+            c if c.is_ascii_punctuation() && c != '&' => {
+                markdown.push('\\');
+                markdown.push(c);
+            }
+            c => markdown.push(c),
+        }
+
+        rest = &rest[c.len_utf8()..];
+    }
+
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_literal, to_markdown};
+
+    #[test]
+    fn test_html_to_markdown() {
+        assert_eq!(
+            to_markdown("<b>bold</b> and <i>italic</i>"),
+            "**bold** and *italic*"
+        );
+        assert_eq!(to_markdown("a<br>b<br/>c"), "a\\\nb\\\nc");
+        assert_eq!(to_markdown("<code>a &lt; b</code>"), "`a < b`");
+        assert_eq!(to_markdown("<code>`x`</code>"), "`` `x` ``");
+        assert_eq!(
+            to_markdown(r#"<a href="https://doc.rust-lang.org">docs</a>"#),
+            "[docs](https://doc.rust-lang.org)"
+        );
+        assert_eq!(to_markdown(r#"<img src="heart.png">"#), "![](heart.png)");
+        assert_eq!(
+            to_markdown(r#"<img src='heart.png' alt="a [heart]">"#),
+            r"![a \[heart\]](heart.png)"
+        );
+        assert_eq!(
+            to_markdown("2 * 3 = 6, snake_case"),
+            r"2 \* 3 = 6, snake\_case"
+        );
+        assert_eq!(
+            to_markdown("1. not a list<br># not a heading"),
+            "1\\. not a list\\\n\\# not a heading"
+        );
+        assert_eq!(
+            to_markdown("&lt;tag&gt; &amp;&nbsp;x"),
+            "&lt;tag&gt; &amp;&nbsp;x"
+        );
+    }
+
+    #[test]
+    fn test_html_to_markdown_keeps_unsupported_tags() {
+        assert_eq!(
+            to_markdown(r#"<u>under</u> <span style="color: red">red</span>"#),
+            r#"<u>under</u> <span style="color: red">red</span>"#
+        );
+        assert_eq!(
+            to_markdown(r#"<a href="x y">link</a> <img src="a.png" width="10">"#),
+            r#"<a href="x y">link</a> <img src="a.png" width="10">"#
+        );
+        assert_eq!(to_markdown("<b>a</b><!-- note -->"), "**a**<!-- note -->");
+        assert_eq!(to_markdown("<code><b>x</b></code>"), "<code>**x**</code>");
+        assert_eq!(to_markdown("a < b"), r"a \< b");
+    }
+
+    #[test]
+    fn test_html_to_literal() {
+        assert_eq!(
+            to_literal("<div>a - b</div>\n\n## c"),
+            r"<div>a \- b</div>&#10;&#10;\#\# c"
+        );
+        assert_eq!(
+            to_literal("<!-- x --><b>*</b>&amp;&#39; & y"),
+            r"<!-- x --><b>\*</b>&amp;&#39; & y"
+        );
+    }
+}
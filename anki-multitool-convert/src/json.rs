@@ -1,96 +1,285 @@
-use anyhow::{Result, anyhow};
 use serde::de::DeserializeOwned;
 use std::{
+    fmt,
     fs::File,
+    io::BufReader,
     marker::PhantomData,
     path::{Path, PathBuf},
-    str::FromStr,
 };
 
-use anki_multitool_ds::card::Card;
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+};
 use anki_multitool_util::commit::FileCommitBuffer;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    /// Columns are counted in characters, so continuation bytes of UTF-8 sequence are skipped.
+    fn advance(&mut self, byte: u8) {
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if is_char_start(byte) {
+            self.column += 1;
+        }
+    }
+
+    /// Translates position reported by `serde_json` for a single element into position in file.
+    /// `serde_json` counts columns in bytes, they are converted to characters of the line.
+    fn offset(&self, element: &[u8], line: usize, column: usize) -> Self {
+        let column = element
+            .split(|&b| b == b'\n')
+            .nth(line.saturating_sub(1))
+            .map(|bytes| {
+                bytes[..column.min(bytes.len())]
+                    .iter()
+                    .filter(|&&b| is_char_start(b))
+                    .count()
+            })
+            .unwrap_or(column);
+
+        if line <= 1 {
+            Self {
+                line: self.line,
+                column: self.column + column.saturating_sub(1),
+            }
+        } else {
+            Self {
+                line: self.line + line - 1,
+                column,
+            }
+        }
+    }
+}
+
+/// Checks whether byte isn't continuation of multi-byte UTF-8 sequence.
+fn is_char_start(byte: u8) -> bool {
+    byte & 0xC0 != 0x80
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    Start,
+    BeforeElement { first: bool },
+    AfterElement,
+    End,
+    Failed,
+}
+
+/// Incremental reader of top-level JSON array, it keeps in memory only one element at a time.
+/// Scanner is aware of strings and escapes, so structural characters inside of values are
+/// not treated as part of array, each element is deserialized by `serde_json`.
 struct JsonArrayStream<T>
 where
     T: DeserializeOwned,
 {
-    file: File,
-    in_array: bool,
-    in_object: bool,
+    path: PathBuf,
+    reader: BufReader<File>,
+    position: Position,
+    state: StreamState,
     _type: PhantomData<T>,
 }
 
 impl<T: DeserializeOwned> JsonArrayStream<T> {
     fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         Ok(Self {
-            file: File::open(path)?,
-            in_array: false,
-            in_object: false,
+            reader: BufReader::new(File::open(&path)?),
+            path: path.as_ref().to_path_buf(),
+            position: Position::default(),
+            state: StreamState::Start,
             _type: PhantomData,
         })
     }
-}
 
-impl<T: DeserializeOwned> Iterator for JsonArrayStream<T> {
-    type Item = Result<T>;
+    fn peek_byte(&mut self) -> Result<Option<u8>> {
+        use std::io::BufRead;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        use std::io::{ErrorKind, Read};
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        use std::io::BufRead;
 
-        let mut buffer = [0u8];
-        let mut str_buffer = String::new();
+        let byte = self.peek_byte()?;
+
+        if let Some(b) = byte {
+            self.reader.consume(1);
+            self.position.advance(b);
+        }
+
+        Ok(byte)
+    }
 
+    fn skip_whitespaces(&mut self) -> Result<Option<u8>> {
         loop {
-            match self.file.read_exact(&mut buffer) {
-                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
-                    if self.in_array {
-                        return Some(Err(anyhow!("unexpected end of JSON array")));
-                    } else if self.in_object {
-                        return Some(Err(anyhow!("unexpected end of JSON object")));
-                    } else {
-                        return None;
-                    }
+            match self.peek_byte()? {
+                Some(b) if b.is_ascii_whitespace() => {
+                    self.next_byte()?;
                 }
-                Err(e) => {
-                    return Some(Err(e.into()));
+                other => return Ok(other),
+            }
+        }
+    }
+
+    fn error_at(&self, position: Position, msg: impl fmt::Display) -> Error {
+        Error::parse_at(
+            &self.path,
+            position.line,
+            position.column,
+            format!("invalid JSON: {msg}"),
+        )
+    }
+
+    fn unexpected_end(&self) -> Error {
+        self.error_at(self.position, "unexpected end of JSON array")
+    }
+
+    fn unexpected_char(&mut self, expected: &str) -> Error {
+        let position = self.position;
+
+        match self.next_byte() {
+            Ok(Some(b)) if b.is_ascii() => self.error_at(
+                position,
+                format!("expected {expected}, found '{}'", b as char),
+            ),
+            Ok(_) => self.error_at(position, format!("expected {expected}")),
+            Err(e) => e,
+        }
+    }
+
+    /// Reads raw bytes of single array element, it can be any JSON value.
+    fn read_element(&mut self) -> Result<Vec<u8>> {
+        let mut element = Vec::new();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            if depth == 0 && !in_string && !element.is_empty() {
+                match self.peek_byte()? {
+                    Some(b) if b.is_ascii_whitespace() || b == b',' || b == b']' => {
+                        return Ok(element);
+                    }
+                    None => return Err(self.unexpected_end()),
+                    _ => {}
                 }
-                Ok(_) => match buffer[0] {
-                    ws if ws.is_ascii_whitespace() => {
-                        if self.in_array {
-                            str_buffer.push(ws as char);
+            }
+
+            let Some(b) = self.next_byte()? else {
+                return Err(self.unexpected_end());
+            };
+
+            element.push(b);
+
+            if in_string {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => {
+                        in_string = false;
+
+                        if depth == 0 {
+                            return Ok(element);
                         }
                     }
-                    b'[' => {
-                        self.in_array = true;
+                    _ => {}
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => {
+                    depth = depth.saturating_sub(1);
+
+                    if depth == 0 {
+                        return Ok(element);
                     }
-                    b']' if self.in_array => {
-                        self.in_array = false;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn next_element(&mut self) -> Result<Option<T>> {
+        loop {
+            match self.state {
+                StreamState::Start => match self.skip_whitespaces()? {
+                    None => return Ok(None),
+                    Some(b'[') => {
+                        self.next_byte()?;
+                        self.state = StreamState::BeforeElement { first: true };
                     }
-                    b @ b',' if self.in_array => {
-                        if self.in_object {
-                            str_buffer.push(b as char);
-                        }
+                    Some(_) => return Err(self.unexpected_char("'['")),
+                },
+                StreamState::BeforeElement { first } => match self.skip_whitespaces()? {
+                    None => return Err(self.unexpected_end()),
+                    Some(b']') if first => {
+                        self.next_byte()?;
+                        self.state = StreamState::End;
                     }
-                    b @ b'{' if self.in_array => {
-                        self.in_object = true;
-                        str_buffer.push(b as char);
+                    Some(b']') => return Err(self.unexpected_char("JSON value after ','")),
+                    Some(_) => {
+                        let start = self.position;
+                        let element = self.read_element()?;
+
+                        self.state = StreamState::AfterElement;
+
+                        return serde_json::from_slice(&element).map(Some).map_err(|e| {
+                            let msg = e.to_string();
+                            let suffix = format!(" at line {} column {}", e.line(), e.column());
+
+                            self.error_at(
+                                start.offset(&element, e.line(), e.column()),
+                                msg.strip_suffix(suffix.as_str()).unwrap_or(msg.as_str()),
+                            )
+                        });
                     }
-                    b @ b'}' if self.in_object => {
-                        self.in_object = false;
-                        str_buffer.push(b as char);
-                        return Some(serde_json::from_str(&str_buffer).map_err(|e| e.into()));
+                },
+                StreamState::AfterElement => match self.skip_whitespaces()? {
+                    None => return Err(self.unexpected_end()),
+                    Some(b',') => {
+                        self.next_byte()?;
+                        self.state = StreamState::BeforeElement { first: false };
                     }
-                    b => {
-                        if self.in_array {
-                            str_buffer.push(b as char);
-                        } else {
-                            return Some(Err(anyhow!(
-                                "unexpected {} character in JSON array",
-                                b as char
-                            )));
-                        }
+                    Some(b']') => {
+                        self.next_byte()?;
+                        self.state = StreamState::End;
                     }
+                    Some(_) => return Err(self.unexpected_char("',' or ']'")),
+                },
+                StreamState::End => match self.skip_whitespaces()? {
+                    None => return Ok(None),
+                    Some(_) => return Err(self.unexpected_char("end of file after JSON array")),
                 },
+                StreamState::Failed => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for JsonArrayStream<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_element() {
+            Ok(element) => element.map(Ok),
+            Err(e) => {
+                self.state = StreamState::Failed;
+                Some(Err(e))
             }
         }
     }
@@ -111,12 +300,9 @@ impl FromJsonDeck {
     where
         A: AsyncFn(Card) -> Result<()>,
     {
-        FileCommitBuffer::new(
-            async |data| data,
-            async |data| action(Card::from_str(data.as_str())?).await,
-        )?
-        .exec_and_commit(JsonArrayStream::<Card>::new(&self.path)?)
-        .await
+        FileCommitBuffer::new(async |data| data, async |card| action(card).await)?
+            .exec_and_commit(JsonArrayStream::<Card>::new(&self.path)?)
+            .await
     }
 }
 
@@ -131,6 +317,12 @@ impl ToJsonDeck {
         }
     }
 
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
     pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
         use serde::{Serializer, ser::SerializeSeq};
         use serde_json::Serializer as JsonSerializer;
@@ -140,20 +332,20 @@ impl ToJsonDeck {
         };
 
         if self.path.exists() {
-            return Err(anyhow!("file {} already exists", self.path.display()));
+            return Err(Error::FileExists(self.path.clone()));
         }
 
         let mut serializer = JsonSerializer::new(BufWriter::new(File::create(&self.path)?));
         let seq = Arc::new(Mutex::new(serializer.serialize_seq(None)?));
 
         FileCommitBuffer::new(
-            async |data| Ok(data),
-            async |data| {
+            async |card| Ok(card),
+            async |card| {
                 Arc::clone(&seq)
                     .lock()
-                    .map_err(|_| anyhow!("failed to lock serializer"))?
-                    .serialize_element(&Card::from_str(data.as_str())?)
-                    .map_err(|e| e.into())
+                    .map_err(|_| Error::invalid("failed to lock serializer"))?
+                    .serialize_element(&card)
+                    .map_err(Error::from)
             },
         )?
         .exec_and_commit(cards)
@@ -161,22 +353,22 @@ impl ToJsonDeck {
 
         //TODO: maybe we should find better solution
         Arc::try_unwrap(seq)
-            .map_err(|_| anyhow!("failed to unwrap Arc"))?
+            .map_err(|_| Error::invalid("failed to unwrap Arc"))?
             .into_inner()
-            .map_err(|_| anyhow!("failed to unlock mutex"))?
+            .map_err(|_| Error::invalid("failed to unlock mutex"))?
             .end()?;
 
         self.path
             .to_str()
             .map(|s| s.to_string())
-            .ok_or_else(|| anyhow!("failed to convert path to string"))
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::BufRead;
+    use std::io::{BufRead, Seek};
     use std::{
         fs::read_to_string,
         io::Write,
@@ -314,40 +506,26 @@ mod tests {
         use tempfile::tempdir;
 
         let cards = vec![
-            Card {
-                front: "Q".to_string(),
-                back: "A".to_string(),
-            },
-            Card {
-                front: "Which color?".to_string(),
-                back: "Blue".to_string(),
-            },
-            Card {
-                front: "ABCD?".to_string(),
-                back: "Yes, EFGH".to_string(),
-            },
-            Card {
-                front: "2+2 ?".to_string(),
-                back: "4".to_string(),
-            },
-            Card {
-                front: "pi?".to_string(),
-                back: "It's definitely 3.14...".to_string(),
-            },
+            Card::basic("Q", "A"),
+            Card::basic("Which color?", "Blue"),
+            Card::basic("ABCD?", "Yes, EFGH"),
+            Card::basic("2+2 ?", "4"),
+            Card::basic("pi?", "It's definitely 3.14..."),
         ];
 
         let temp_dir = tempdir().expect("failed to create temp directory");
         let temp_path = temp_dir.path().join("test_deck.json");
 
-        let mut to_json_deck = ToJsonDeck::new("test_deck");
-        to_json_deck.path = temp_path.clone();
-
-        to_json_deck
-            .write(cards.into_iter())
-            .await
-            .expect("failed to write cards to JSON file");
-
-        assert!(temp_path.exists());
+        assert_eq!(
+            ToJsonDeck::new("test_deck")
+                .in_dir(temp_dir.path())
+                .write(cards.into_iter())
+                .await
+                .expect("failed to write cards to JSON file"),
+            temp_path
+                .to_str()
+                .expect("failed to convert path to string")
+        );
 
         assert_eq!(
             serde_json::from_str::<Value>(
@@ -384,6 +562,50 @@ mod tests {
         assert!(to_json_deck.write(vec![].into_iter()).await.is_err());
     }
 
+    #[tokio::test]
+    pub async fn test_json_deck_round_trip() {
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic("T-cell", "a type of lymphocyte - part of immune system"),
+            Card::basic("What is 3-2?", "1"),
+            Card::basic("multi\nline", "first line\nsecond line\r\n\nthird line"),
+            Card::basic(
+                "<b>bold</b> &amp; <i>italic</i>",
+                "<div class=\"answer\">x</div><br>",
+            ),
+            Card::basic("Як справи? 你好 🦀", "Добре - 很好 ✅"),
+            Card::basic("struct A {}", "{ \"a\": [1, 2] }, ]"),
+        ];
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("round_trip_deck.json");
+
+        let mut to_json_deck = ToJsonDeck::new("round_trip_deck");
+        to_json_deck.path = temp_path.clone();
+
+        to_json_deck
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write cards to JSON file");
+
+        let imported = Arc::new(Mutex::new(Vec::new()));
+
+        FromJsonDeck::new(&temp_path)
+            .expect("failed to create FromJsonDeck instance")
+            .for_each(async |card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+    }
+
     #[test]
     pub fn test_json_array_stream() {
         let json_file = NamedTempFile::new().expect("failed to create temp file");
@@ -422,30 +644,203 @@ mod tests {
                 .collect::<Result<Vec<_>>>()
                 .expect("failed to collect stream"),
             vec![
-                Card {
-                    front: "Q".to_string(),
-                    back: "A".to_string(),
-                },
-                Card {
-                    front: "Which color?".to_string(),
-                    back: "Blue".to_string(),
-                },
-                Card {
-                    front: "ABCD?".to_string(),
-                    back: "Yes, EFGH".to_string(),
-                },
-                Card {
-                    front: "2+2 ?".to_string(),
-                    back: "4".to_string(),
-                },
-                Card {
-                    front: "pi?".to_string(),
-                    back: "It's definitely 3.14...".to_string(),
-                }
+                Card::basic("Q", "A"),
+                Card::basic("Which color?", "Blue"),
+                Card::basic("ABCD?", "Yes, EFGH"),
+                Card::basic("2+2 ?", "4"),
+                Card::basic("pi?", "It's definitely 3.14...")
             ]
         );
     }
 
+    #[test]
+    pub fn test_json_array_stream_special_content() {
+        let json_file = NamedTempFile::new().expect("failed to create temp file");
+        write!(
+            &json_file,
+            r#"[
+                {{"front": "fn main() {{ println!(\"{{}}\", [1, 2]); }}", "back": "}}, {{ ] ["}},
+                {{"front": "escaped \" quote and \\", "back": "tab\tnewline\n"}},
+                {{"front": "Як справи?", "back": "Добре, дякую"}},
+                {{"front": "你好", "back": "hello 🦀", "extra": {{"nested": ["}}", {{}}]}}}}
+            ]"#
+        )
+        .expect("failed to write to temp file");
+
+        /// Only `front` and `back` are read, other keys of element are skipped.
+        #[derive(serde::Deserialize)]
+        struct BasicRecord {
+            front: String,
+            back: String,
+        }
+
+        let stream = JsonArrayStream::<BasicRecord>::new(json_file.path())
+            .expect("failed to create JsonArrayStream");
+
+        assert_eq!(
+            stream
+                .map(|record| record.map(|r| Card::basic(r.front, r.back)))
+                .collect::<Result<Vec<_>>>()
+                .expect("failed to collect stream"),
+            vec![
+                Card::basic(r#"fn main() { println!("{}", [1, 2]); }"#, "}, { ] ["),
+                Card::basic(r#"escaped " quote and \"#, "tab\tnewline\n"),
+                Card::basic("Як справи?", "Добре, дякую"),
+                Card::basic("你好", "hello 🦀"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_json_array_stream_basic_card_with_extra() {
+        let json_file = NamedTempFile::new().expect("failed to create temp file");
+        write!(
+            &json_file,
+            r#"[{{"front": "你好", "back": "hello", "extra": "greeting"}}]"#
+        )
+        .expect("failed to write to temp file");
+
+        let err = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream")
+            .collect::<Result<Vec<_>>>()
+            .expect_err("expected card with front, back and extra to be rejected");
+
+        assert!(matches!(err, Error::Parse { line: Some(1), .. }), "{err}");
+    }
+
+    #[test]
+    pub fn test_json_array_stream_empty_array() {
+        let json_file = NamedTempFile::new().expect("failed to create temp file");
+        writeln!(&json_file, "  [ \n ]  ").expect("failed to write to temp file");
+
+        let stream = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream");
+
+        assert!(
+            stream
+                .collect::<Result<Vec<_>>>()
+                .expect("failed to collect stream")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    pub fn test_json_array_stream_error_position() {
+        let json_file = NamedTempFile::new().expect("failed to create temp file");
+        write!(
+            &json_file,
+            "[\n  {{\"front\": \"Q\", \"back\": \"A\"}},\n  {{\"front\": \"Q\", \"back\": 42}}\n]"
+        )
+        .expect("failed to write to temp file");
+
+        let mut stream = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream");
+
+        assert!(stream.next().expect("expected first card").is_ok());
+
+        let err = stream
+            .next()
+            .expect("expected error")
+            .expect_err("expected invalid second card");
+
+        assert!(
+            matches!(&err, Error::Parse { line: Some(3), column: Some(27), message, .. } if message.starts_with("invalid JSON:")),
+            "{err}"
+        );
+        assert!(stream.next().is_none());
+
+        json_file
+            .as_file()
+            .set_len(0)
+            .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
+        write!(
+            &json_file,
+            "[\n  {{\"front\": \"Q\", \"back\": \"A\"}}\n  {{\"front\": \"Q\", \"back\": \"A\"}}\n]"
+        )
+        .expect("failed to write to temp file");
+
+        let err = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream")
+            .collect::<Result<Vec<_>>>()
+            .expect_err("expected missing comma")
+            .to_string();
+
+        assert_eq!(
+            err,
+            format!(
+                "{}:3:3: invalid JSON: expected ',' or ']', found '{{'",
+                json_file.path().display()
+            )
+        );
+
+        json_file
+            .as_file()
+            .set_len(0)
+            .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
+        write!(&json_file, "[\n  {{\"front\": \"Q\", \"back\": \"A\"}},\n]")
+            .expect("failed to write to temp file");
+
+        assert!(
+            JsonArrayStream::<Card>::new(json_file.path())
+                .expect("failed to create JsonArrayStream")
+                .collect::<Result<Vec<_>>>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    pub fn test_json_array_stream_error_position_non_ascii() {
+        let json_file = NamedTempFile::new().expect("failed to create temp file");
+        write!(&json_file, "[\n  {{\"front\": \"кіт\", \"back\": 42}}\n]")
+            .expect("failed to write to temp file");
+
+        let err = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream")
+            .collect::<Result<Vec<_>>>()
+            .expect_err("expected invalid card");
+
+        assert!(
+            matches!(&err, Error::Parse { line: Some(2), column: Some(29), message, .. } if message.starts_with("invalid JSON:")),
+            "{err}"
+        );
+
+        json_file
+            .as_file()
+            .set_len(0)
+            .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
+        write!(
+            &json_file,
+            "[{{\"front\": \"猫\", \"back\": \"кіт\"}} {{\"front\": \"Q\", \"back\": \"A\"}}]"
+        )
+        .expect("failed to write to temp file");
+
+        let err = JsonArrayStream::<Card>::new(json_file.path())
+            .expect("failed to create JsonArrayStream")
+            .collect::<Result<Vec<_>>>()
+            .expect_err("expected missing comma")
+            .to_string();
+
+        assert_eq!(
+            err,
+            format!(
+                "{}:1:32: invalid JSON: expected ',' or ']', found '{{'",
+                json_file.path().display()
+            )
+        );
+    }
+
     #[test]
     pub fn test_failed_json_array_stream() {
         let json_file = NamedTempFile::new().expect("failed to create temp file");
@@ -469,6 +864,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &json_file,
             r#"[
@@ -492,6 +891,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &json_file,
             r#"[
@@ -515,6 +918,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &json_file,
             r#"[
@@ -538,6 +945,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &json_file,
             r#"[
@@ -561,6 +972,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        json_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &json_file,
             r#"[
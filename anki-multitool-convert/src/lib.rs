@@ -1,2 +1,9 @@
+pub mod apkg;
+pub mod collection;
+pub mod csv;
+mod document;
+mod html;
 pub mod json;
 pub mod markdown;
+pub mod toml;
+pub mod yaml;
@@ -1,209 +1,1075 @@
-use anyhow::{Result, anyhow};
 use std::{
     fs::File,
-    io::{BufReader, Lines},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use anki_multitool_ds::card::Card;
+use anki_multitool_ds::{
+    card::{
+        BASIC_MODEL, CLOZE_MODEL, Card, Separator, has_cloze_deletions, known_model_fields,
+        unescape,
+    },
+    error::{Error, Result, text_position},
+};
 use anki_multitool_util::commit::FileCommitBuffer;
 
-#[derive(PartialEq, Eq)]
-enum TypeList {
-    Ordered,
-    Unordered,
-    Undefined,
+use crate::html;
+
+const ID_ATTRIBUTE: &str = "id";
+const MODEL_ATTRIBUTE: &str = "model";
+const FIELDS_ATTRIBUTE: &str = "fields";
+const TAGS_ATTRIBUTE: &str = "tags";
+pub const DEFAULT_HEADING_LEVEL: u8 = 2;
+/// Characters of separators, cloze deletions, attributes and escapes themselves.
+const LITERAL_ESCAPED: [char; 7] = ['-', ':', '|', '?', '{', '}', '\\'];
+
+/// Layout of cards in Markdown file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownStyle {
+    /// Every item of list is a card: `1. front - back`.
+    #[default]
+    List,
+    /// Heading is front of card, everything under it until the next heading of the same level
+    /// is back.
+    Headings,
 }
 
-impl TypeList {
-    fn is_ordered(&self) -> bool {
-        *self == TypeList::Ordered
-    }
+impl FromStr for MarkdownStyle {
+    type Err = Error;
 
-    fn is_unordered(&self) -> bool {
-        *self == TypeList::Unordered
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "list" => Ok(Self::List),
+            "headings" => Ok(Self::Headings),
+            _ => Err(Error::invalid(format!(
+                "unknown Markdown style '{s}', expected 'list' or 'headings'"
+            ))),
+        }
     }
 }
 
-#[derive(PartialEq, Eq)]
-enum ParserState {
-    InList,
-    InListItem,
-    Undefined,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    pub style: MarkdownStyle,
+    /// Level of headings which are fronts of cards in headings style.
+    pub heading_level: u8,
+    /// Top-level `#` headings are subdecks (`Parent::Child`) in headings style.
+    pub subdecks: bool,
+    /// Separator of fields of card in list style.
+    pub separator: Separator,
 }
 
-impl ParserState {
-    fn in_list(&self) -> bool {
-        *self == ParserState::InList
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            style: MarkdownStyle::default(),
+            heading_level: DEFAULT_HEADING_LEVEL,
+            subdecks: false,
+            separator: Separator::default(),
+        }
     }
+}
+
+impl MarkdownOptions {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=6).contains(&self.heading_level) {
+            return Err(Error::invalid(format!(
+                "heading level must be from 1 to 6, got {}",
+                self.heading_level
+            )));
+        }
+
+        if self.subdecks && self.style != MarkdownStyle::Headings {
+            return Err(Error::invalid(
+                "subdecks are supported only in headings style",
+            ));
+        }
 
-    fn in_list_item(&self) -> bool {
-        *self == ParserState::InListItem
+        if self.subdecks && self.heading_level == 1 {
+            return Err(Error::invalid(
+                "top-level headings are subdecks, so headings of cards must be of level 2 or deeper",
+            ));
+        }
+
+        Ok(())
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum TypeList {
+    Ordered,
+    Unordered,
+    Undefined,
+}
+
+/// Reads items of top-level list of Markdown file along with numbers of lines where they start.
+/// Whole document is parsed at once, so continuation lines, nested lists and code blocks belong
+/// to their item, content of item is rendered to HTML.
 struct MarkdownListStream {
-    lines: Lines<BufReader<File>>,
-    type_list: TypeList,
+    items: std::vec::IntoIter<Result<(usize, String)>>,
 }
 
 impl MarkdownListStream {
     fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        use std::io::BufRead;
-
         if !path.as_ref().exists() {
-            return Err(anyhow!("file {} doesn't exist", path.as_ref().display()));
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
         }
 
+        let content = std::fs::read_to_string(&path)?;
+
         Ok(Self {
-            lines: BufReader::new(File::open(path)?).lines(),
-            type_list: TypeList::Undefined,
+            items: read_list_items(path.as_ref(), &content).into_iter(),
         })
     }
 }
 
 impl Iterator for MarkdownListStream {
-    type Item = Result<String>;
+    type Item = Result<(usize, String)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+        self.items.next()
+    }
+}
 
-        loop {
-            let mut parser_state = ParserState::Undefined;
-            let mut text_buffer = String::new();
+/// Splits document into items of top-level list, reading stops at first error, so items before
+/// it are handled the same way as in valid document.
+fn read_list_items(path: &Path, content: &str) -> Vec<Result<(usize, String)>> {
+    use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+    let error = |offset: usize, message: &str| {
+        let (line, column) = text_position(content, offset);
+        Err(Error::parse_at(path, line, column, message))
+    };
 
-            if let Some(Ok(line)) = self.lines.next() {
-                // Skip empty lines
-                if line.trim().is_empty() {
-                    continue;
+    let mut items = Vec::new();
+    let mut type_list = TypeList::Undefined;
+    let mut depth = 0usize;
+    let mut code_block = false;
+    let mut item: Option<(usize, Vec<Event>)> = None;
+    // source of HTML block which starts item
+    let mut html_block: Option<std::ops::Range<usize>> = None;
+
+    for (event, range) in Parser::new(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::List(first)) if depth == 0 => {
+                let kind = if first.is_some() {
+                    TypeList::Ordered
+                } else {
+                    TypeList::Unordered
+                };
+
+                if type_list != TypeList::Undefined && type_list != kind {
+                    items.push(error(range.start, "mixed lists are not supported"));
+                    return items;
                 }
 
-                let mut events_found = false;
-                for event in Parser::new(&line) {
-                    events_found = true;
-                    match event {
-                        Event::Start(Tag::List(Some(_))) => {
-                            if self.type_list.is_unordered() {
-                                return Some(Err(anyhow!(
-                                    "mixed or nested list are not supported"
-                                )));
-                            }
-
-                            parser_state = ParserState::InList;
-                            self.type_list = TypeList::Ordered;
-                        }
-                        Event::Start(Tag::List(None)) => {
-                            if self.type_list.is_ordered() {
-                                return Some(Err(anyhow!(
-                                    "mixed or nested list are not supported"
-                                )));
-                            }
-
-                            parser_state = ParserState::InList;
-                            self.type_list = TypeList::Unordered;
-                        }
-                        Event::Start(Tag::Item) if parser_state.in_list() => {
-                            parser_state = ParserState::InListItem;
-                        }
-                        Event::Text(ref text) if parser_state.in_list_item() => {
-                            text_buffer.push_str(text);
-                        }
-                        Event::End(TagEnd::Item) if parser_state.in_list_item() => {
-                            return Some(Ok(text_buffer));
-                        }
-                        _ => {
-                            return Some(Err(anyhow!("unsupported format of markdown")));
+                type_list = kind;
+                depth = 1;
+            }
+            Event::End(TagEnd::List(_)) if depth == 1 => depth = 0,
+            Event::Start(Tag::Item) if depth == 1 => {
+                if let Some(offset) = find_lazy_line(content, range.start, range.end) {
+                    items.push(error(
+                        offset,
+                        "continuation line of list item has to be indented",
+                    ));
+                    return items;
+                }
+
+                item = Some((range.start, Vec::new()));
+            }
+            Event::End(TagEnd::Item) if depth == 1 => {
+                if let Some((start, events)) = item.take() {
+                    items.push(Ok((line_of(start), render_html(events))));
+                }
+            }
+            Event::Start(Tag::HtmlBlock)
+                if depth == 1 && item.as_ref().is_some_and(|(_, events)| events.is_empty()) =>
+            {
+                html_block = Some(range);
+            }
+            Event::End(TagEnd::HtmlBlock) if html_block.is_some() => {
+                if let (Some(range), Some((_, events))) = (html_block.take(), &mut item) {
+                    events.extend(inline_events(&content[range]));
+                }
+            }
+            _ if html_block.is_some() => {}
+            event if depth > 0 => {
+                match event {
+                    Event::Start(Tag::List(_)) => depth += 1,
+                    Event::End(TagEnd::List(_)) => depth -= 1,
+                    Event::Start(Tag::CodeBlock(_)) => code_block = true,
+                    Event::End(TagEnd::CodeBlock) => code_block = false,
+                    _ => {}
+                }
+
+                if let Some((_, events)) = &mut item {
+                    let escaped = content[..range.start].ends_with('\\');
+
+                    events.push(escape_literal(event, escaped, code_block));
+                }
+            }
+            _ => {
+                items.push(error(range.start, "unsupported format of markdown"));
+                return items;
+            }
+        }
+    }
+
+    items
+}
+
+/// Width of leading whitespace of line, tab stops are 4 columns wide as in CommonMark.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .fold(0, |width, c| {
+            if c == '\t' {
+                width + 4 - width % 4
+            } else {
+                width + 1
+            }
+        })
+}
+
+/// Finds line of list item which isn't indented up to content of item. CommonMark treats such
+/// lines as continuation of paragraph, so item with missing marker would be silently merged
+/// into previous one.
+fn find_lazy_line(content: &str, start: usize, end: usize) -> Option<usize> {
+    let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+    let first_line = content[line_start..].lines().next().unwrap_or("");
+
+    let marker_start = first_line.len() - first_line.trim_start().len();
+    let marker_len = first_line[marker_start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .count()
+        + 1;
+    let marker_end = indent_width(first_line) + marker_len;
+    let rest = first_line.get(marker_start + marker_len..).unwrap_or("");
+    let spaces = indent_width(rest);
+
+    let content_indent = if rest.trim().is_empty() || spaces > 4 {
+        marker_end + 1
+    } else {
+        marker_end + spaces
+    };
+
+    let mut offset = line_start + first_line.len() + 1;
+
+    for line in content.get(offset..end).unwrap_or("").split_inclusive('\n') {
+        if !line.trim().is_empty() && indent_width(line) < content_indent {
+            return Some(offset);
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
+
+/// Reads HTML block as inline content, so item starting with block-level tag like `<div>` is
+/// split into fields the same way as other items.
+fn inline_events(html: &str) -> Vec<pulldown_cmark::Event<'static>> {
+    use pulldown_cmark::{Event, Parser};
+
+    // text before tag keeps parser from starting HTML block, it's removed from events
+    let source = format!("x{html}");
+    let mut events: Vec<Event<'static>> = Parser::new(&source)
+        .into_offset_iter()
+        .map(|(event, range)| {
+            escape_literal(event, source[..range.start].ends_with('\\'), false).into_static()
+        })
+        .collect();
+
+    if let Some(Event::Text(text)) = events.get_mut(1) {
+        *text = text[1..].to_string().into();
+    }
+
+    events.retain(|event| !matches!(event, Event::Text(text) if text.is_empty()));
+    events
+}
+
+/// Escapes punctuation of text which is literal in Markdown: escaped characters, code and HTML,
+/// so separators in it don't split item into fields and braces don't start attributes. Escapes
+/// are removed along with splitting.
+fn escape_literal<'a>(
+    event: pulldown_cmark::Event<'a>,
+    escaped: bool,
+    code_block: bool,
+) -> pulldown_cmark::Event<'a> {
+    use pulldown_cmark::Event;
+
+    let escape = |text: &str| {
+        text.chars()
+            .flat_map(|c| [LITERAL_ESCAPED.contains(&c).then_some('\\'), Some(c)])
+            .flatten()
+            .collect::<String>()
+    };
+
+    match event {
+        Event::Text(text) if code_block => Event::Text(escape(&text).into()),
+        // escaped character starts its own text right after backslash
+        Event::Text(text) if escaped && text.starts_with(|c: char| c.is_ascii_punctuation()) => {
+            Event::Text(format!("\\{text}").into())
+        }
+        Event::Code(text) => Event::Code(escape(&text).into()),
+        Event::InlineHtml(html) => Event::InlineHtml(escape(&html).into()),
+        Event::Html(html) => Event::Html(escape(&html).into()),
+        event => event,
+    }
+}
+
+/// Maps events to HTML which Anki editor writes: `<b>`, `<i>`, `<br>` and `<img src="...">`,
+/// non-breaking spaces are kept as `&nbsp;` entities.
+fn anki_events<'a>(
+    events: impl IntoIterator<Item = pulldown_cmark::Event<'a>>,
+) -> Vec<pulldown_cmark::Event<'a>> {
+    use pulldown_cmark::{Event, Tag, TagEnd};
+
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('\u{a0}', "&nbsp;")
+    };
+    let html = |html: String| Event::InlineHtml(html.into());
+    let mut anki = Vec::new();
+    // source and alt text of image which is being read
+    let mut image: Option<(String, String)> = None;
+
+    for event in events {
+        match (event, &mut image) {
+            (Event::Start(Tag::Image { dest_url, .. }), _) => {
+                image = Some((dest_url.to_string(), String::new()));
+            }
+            (Event::End(TagEnd::Image), Some((src, alt))) => {
+                let alt = match alt.is_empty() {
+                    true => String::new(),
+                    false => format!(" alt=\"{}\"", escape(alt).replace('"', "&quot;")),
+                };
+
+                anki.push(html(format!(
+                    "<img src=\"{}\"{alt}>",
+                    escape(src).replace('"', "&quot;")
+                )));
+                image = None;
+            }
+            (Event::Text(text) | Event::Code(text), Some((_, alt))) => alt.push_str(&text),
+            (_, Some(_)) => {}
+            (Event::Start(Tag::Strong), _) => anki.push(html("<b>".to_string())),
+            (Event::End(TagEnd::Strong), _) => anki.push(html("</b>".to_string())),
+            (Event::Start(Tag::Emphasis), _) => anki.push(html("<i>".to_string())),
+            (Event::End(TagEnd::Emphasis), _) => anki.push(html("</i>".to_string())),
+            (Event::HardBreak, _) => anki.push(html("<br>".to_string())),
+            (Event::Text(text), _) => anki.push(html(escape(&text))),
+            (event, _) => anki.push(event),
+        }
+    }
+
+    anki
+}
+
+/// Renders content of card to HTML, the first paragraph holds values of fields, so it's written
+/// without `<p>`, the same way as in tight list.
+fn render_html(mut events: Vec<pulldown_cmark::Event>) -> String {
+    use pulldown_cmark::{Event, Tag, TagEnd};
+
+    if matches!(events.first(), Some(Event::Start(Tag::Paragraph)))
+        && let Some(end) = events
+            .iter()
+            .position(|e| matches!(e, Event::End(TagEnd::Paragraph)))
+    {
+        events.remove(end);
+        events.remove(0);
+    }
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, anki_events(events).into_iter());
+
+    html.trim_end().to_string()
+}
+
+/// Converts value of field to Markdown which `read` turns back into the same value: Markdown
+/// syntax if possible, then raw HTML as is or on a single line and literal form of HTML as the
+/// last resort.
+fn field_to_markdown(value: &str, read: impl Fn(&str) -> Option<String>) -> String {
+    [
+        html::to_markdown(value),
+        value.to_string(),
+        value.replace('\n', "&#10;"),
+    ]
+    .into_iter()
+    .find(|markdown| read(markdown).as_deref() == Some(value))
+    .unwrap_or_else(|| html::to_literal(value))
+}
+
+/// Reads Markdown of field as content of list item.
+fn read_item_field(markdown: &str) -> Option<String> {
+    let item = format!("1. {}", indent_continuation(markdown, 3));
+
+    match read_list_items(Path::new(""), &item).as_slice() {
+        [Ok((_, html))] => Some(unescape(html)),
+        _ => None,
+    }
+}
+
+/// Reads Markdown of field as heading of given level.
+fn read_heading_field(markdown: &str, level: u8) -> Option<String> {
+    let section = format!("{} {markdown}\n", "#".repeat(level as usize));
+
+    match read_sections(Path::new(""), &section, &headings_options(level)).as_slice() {
+        [Ok((_, section))] if section.back.is_empty() => Some(unescape(&section.front)),
+        _ => None,
+    }
+}
+
+/// Reads Markdown of field as content under heading of given level.
+fn read_content_field(markdown: &str, level: u8) -> Option<String> {
+    let section = format!("{} x\n\n{markdown}\n", "#".repeat(level as usize));
+
+    match read_sections(Path::new(""), &section, &headings_options(level)).as_slice() {
+        [Ok((_, section))] => Some(section.back.clone()),
+        _ => None,
+    }
+}
+
+fn headings_options(level: u8) -> MarkdownOptions {
+    MarkdownOptions {
+        style: MarkdownStyle::Headings,
+        heading_level: level,
+        ..MarkdownOptions::default()
+    }
+}
+
+/// Card of headings style before parsing of its fields.
+struct Section {
+    /// Subdeck given by top-level heading, relative to deck of file.
+    subdeck: Option<String>,
+    front: String,
+    back: String,
+}
+
+impl Section {
+    fn new(subdeck: &Option<String>, front: String, back: Vec<pulldown_cmark::Event>) -> Self {
+        Self {
+            subdeck: subdeck.clone(),
+            front,
+            back: render_html(back),
+        }
+    }
+}
+
+/// Splits document into sections under headings of given level along with numbers of lines of
+/// headings. Headings of higher levels end section, with subdecks top-level ones set subdeck
+/// of following cards, other content outside of sections isn't supported.
+fn read_sections(
+    path: &Path,
+    content: &str,
+    options: &MarkdownOptions,
+) -> Vec<Result<(usize, Section)>> {
+    use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+
+    enum Target<'a> {
+        Outside,
+        Front(usize, Vec<Event<'a>>),
+        Subdeck(String),
+        Title,
+        Back(usize, String, Vec<Event<'a>>),
+    }
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+    let error = |offset: usize, message: &str| {
+        let (line, column) = text_position(content, offset);
+        Err(Error::parse_at(path, line, column, message))
+    };
+
+    let mut sections = Vec::new();
+    let mut subdeck: Option<String> = None;
+    let mut target = Target::Outside;
+    let mut depth = 0usize;
+
+    for (event, range) in Parser::new(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. })
+                if depth == 0 && level as u8 <= options.heading_level =>
+            {
+                if let Target::Back(line, front, events) =
+                    std::mem::replace(&mut target, Target::Outside)
+                {
+                    sections.push(Ok((line, Section::new(&subdeck, front, events))));
+                }
+
+                target = if level as u8 == options.heading_level {
+                    Target::Front(line_of(range.start), Vec::new())
+                } else if options.subdecks && level == HeadingLevel::H1 {
+                    Target::Subdeck(String::new())
+                } else {
+                    Target::Title
+                };
+                depth += 1;
+            }
+            Event::End(TagEnd::Heading(_))
+                if depth == 1
+                    && matches!(
+                        target,
+                        Target::Front(..) | Target::Subdeck(_) | Target::Title
+                    ) =>
+            {
+                depth = 0;
+                target = match target {
+                    Target::Front(line, events) => {
+                        let mut front = String::new();
+                        pulldown_cmark::html::push_html(
+                            &mut front,
+                            anki_events(events).into_iter(),
+                        );
+
+                        Target::Back(line, front.trim().to_string(), Vec::new())
+                    }
+                    Target::Subdeck(name) if name.trim().is_empty() => {
+                        sections.push(error(range.start, "name of subdeck is empty"));
+                        return sections;
+                    }
+                    Target::Subdeck(name) => {
+                        subdeck = Some(name.trim().to_string());
+                        Target::Outside
+                    }
+                    _ => Target::Outside,
+                };
+            }
+            event => {
+                match event {
+                    Event::Start(_) => depth += 1,
+                    Event::End(_) => depth -= 1,
+                    _ => {}
+                }
+
+                match &mut target {
+                    Target::Front(_, events) => {
+                        let escaped = content[..range.start].ends_with('\\');
+
+                        events.push(escape_literal(event, escaped, false));
+                    }
+                    Target::Back(_, _, events) => events.push(event),
+                    Target::Subdeck(name) => {
+                        if let Event::Text(text) | Event::Code(text) = event {
+                            name.push_str(&text);
                         }
                     }
+                    Target::Title => {}
+                    Target::Outside => {
+                        sections.push(error(
+                            range.start,
+                            "content outside of card, it has to be under heading",
+                        ));
+                        return sections;
+                    }
                 }
+            }
+        }
+    }
+
+    if let Target::Back(line, front, events) = target {
+        sections.push(Ok((line, Section::new(&subdeck, front, events))));
+    }
+
+    sections
+}
+
+/// Attributes of card written as trailing block of list item:
+/// `{id: 1700000000000; model: Name; fields: A, B; tags: tag1, tag2}`.
+#[derive(Default)]
+struct CardAttributes {
+    id: Option<u64>,
+    model: Option<String>,
+    fields: Option<Vec<String>>,
+    tags: Vec<String>,
+}
+
+impl CardAttributes {
+    /// Splits list item into card text and attributes, if trailing block doesn't consist of
+    /// known attributes, it's treated as part of text.
+    fn split(text: &str) -> (&str, Self) {
+        let trimmed = text.trim_end();
+
+        let Some(start) = trimmed.strip_suffix('}').and_then(|t| t.rfind('{')) else {
+            return (text, Self::default());
+        };
 
-                // If no events were found for this line, it might be whitespace or other content
-                // Continue to next line instead of terminating
-                if !events_found {
-                    continue;
+        // escaped brace is part of text
+        if trimmed[..start]
+            .chars()
+            .rev()
+            .take_while(|c| *c == '\\')
+            .count()
+            % 2
+            == 1
+        {
+            return (text, Self::default());
+        }
+
+        let mut attributes = Self::default();
+
+        for entry in trimmed[start + 1..trimmed.len() - 1].split(';') {
+            let Some((key, value)) = entry.split_once(':') else {
+                return (text, Self::default());
+            };
+
+            match key.trim() {
+                ID_ATTRIBUTE => match value.trim().parse() {
+                    Ok(id) => attributes.id = Some(id),
+                    Err(_) => return (text, Self::default()),
+                },
+                MODEL_ATTRIBUTE => attributes.model = Some(value.trim().to_string()),
+                FIELDS_ATTRIBUTE => {
+                    attributes.fields = Some(
+                        value
+                            .split(',')
+                            .map(|f| f.trim().to_string())
+                            .filter(|f| !f.is_empty())
+                            .collect(),
+                    )
                 }
-            } else {
-                // No more lines to read
-                return None;
+                TAGS_ATTRIBUTE => attributes.tags.extend(
+                    value
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string),
+                ),
+                _ => return (text, Self::default()),
             }
         }
+
+        (trimmed[..start].trim_end(), attributes)
+    }
+
+    /// Escapes trailing block of text which would be read as attributes of card.
+    fn escape(text: &str) -> String {
+        if Self::split(text).0.len() == text.len() {
+            return text.to_string();
+        }
+
+        let start = text.trim_end().rfind('{').unwrap_or_default();
+
+        format!("{}\\{}", &text[..start], &text[start..])
+    }
+
+    fn of(card: &Card) -> Self {
+        if card.is_basic() || (card.is_cloze() && has_cloze_deletions(card.front())) {
+            return Self {
+                id: card.id,
+                tags: card.tags.clone(),
+                ..Self::default()
+            };
+        }
+
+        let fields: Vec<String> = card.fields.keys().cloned().collect();
+        let is_known = known_model_fields(&card.model)
+            .is_some_and(|known| known.iter().copied().eq(fields.iter().map(String::as_str)));
+
+        Self {
+            id: card.id,
+            model: Some(card.model.clone()),
+            fields: (!is_known).then_some(fields),
+            tags: card.tags.clone(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.id.is_none() && self.model.is_none() && self.fields.is_none() && self.tags.is_empty()
+    }
+}
+
+impl std::fmt::Display for CardAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut entries = Vec::new();
+
+        if let Some(id) = self.id {
+            entries.push(format!("{ID_ATTRIBUTE}: {id}"));
+        }
+
+        if let Some(model) = &self.model {
+            entries.push(format!("{MODEL_ATTRIBUTE}: {model}"));
+        }
+
+        if let Some(fields) = &self.fields {
+            entries.push(format!("{FIELDS_ATTRIBUTE}: {}", fields.join(", ")));
+        }
+
+        if !self.tags.is_empty() {
+            entries.push(format!("{TAGS_ATTRIBUTE}: {}", self.tags.join(", ")));
+        }
+
+        write!(f, " {{{}}}", entries.join("; "))
+    }
+}
+
+/// Note type of card, card with cloze deletions and without explicit note type is "Cloze" note.
+fn model_of<'a>(attributes: &'a CardAttributes, text: &str) -> &'a str {
+    attributes.model.as_deref().unwrap_or(
+        if attributes.fields.is_none() && has_cloze_deletions(text) {
+            CLOZE_MODEL
+        } else {
+            BASIC_MODEL
+        },
+    )
+}
+
+fn known_fields(model: &str) -> Result<&'static [&'static str]> {
+    known_model_fields(model).ok_or_else(|| {
+        Error::invalid(format!(
+            "fields of note type '{model}' are unknown, specify them with '{{{FIELDS_ATTRIBUTE}: ...}}'"
+        ))
+    })
+}
+
+/// Parses list item into card, item with cloze deletions (`{{c1::...}}`) and without
+/// explicit note type becomes "Cloze" note.
+fn parse_card(text: &str, separator: Separator) -> Result<Card> {
+    let (text, attributes) = CardAttributes::split(text);
+    let model = model_of(&attributes, text);
+
+    let card = match &attributes.fields {
+        Some(fields) => Card::parse_with(
+            text,
+            model,
+            &fields.iter().map(String::as_str).collect::<Vec<_>>(),
+            separator,
+        ),
+        None if model == BASIC_MODEL => Card::parse_basic(text, separator),
+        None => Card::parse_with(text, model, known_fields(model)?, separator),
+    }?;
+
+    Ok(card.with_tags(attributes.tags).with_id(attributes.id))
+}
+
+/// Parses section of headings style into card, heading is the first field of note and content
+/// under it is the second one.
+fn parse_section(section: &Section) -> Result<Card> {
+    let (front, attributes) = CardAttributes::split(&section.front);
+    let front = unescape(front.trim());
+
+    if front.is_empty() {
+        return Err(Error::invalid("heading of card is empty"));
+    }
+
+    let model = model_of(&attributes, &front);
+    let names = match &attributes.fields {
+        Some(fields) => fields.iter().map(String::as_str).collect(),
+        None => known_fields(model)?.to_vec(),
+    };
+
+    if names.len() < 2 && !section.back.is_empty() {
+        return Err(Error::invalid(format!(
+            "note type '{model}' has single field, there is no field for content under heading"
+        )));
+    }
+
+    let card = Card::new(
+        model,
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let value = match i {
+                    0 => front.as_str(),
+                    1 => section.back.as_str(),
+                    _ => "",
+                };
+
+                (name.to_string(), value.to_string())
+            })
+            .collect(),
+    );
+
+    Ok(card
+        .with_tags(attributes.tags.clone())
+        .with_id(attributes.id))
+}
+
+/// Formats card as one item of list, separators inside of values are escaped, so card is read
+/// back the same way.
+fn format_card(card: &Card, separator: Separator) -> String {
+    let values: Vec<&str> = card.fields.values().map(String::as_str).collect();
+    // trailing empty fields are restored on import, so there is no need to write them
+    let len = match card.is_basic() {
+        true => values.len(),
+        false => values.len() - values.iter().rev().take_while(|v| v.is_empty()).count(),
+    };
+    let values: Vec<String> = values[..len.max(1).min(values.len())]
+        .iter()
+        .map(|value| field_to_markdown(value, read_item_field))
+        .collect();
+    let (init, last) = values.split_at(values.len() - 1);
+
+    // "? " can separate only values ending with "?" and is lost before empty value, other cards
+    // are written with " - ", which splits items without "? " on import
+    if separator == Separator::Question
+        && (init.is_empty() || last[0].is_empty() || init.iter().any(|v| !v.ends_with('?')))
+    {
+        let text = values
+            .iter()
+            .map(|value| Separator::Dash.escape(value))
+            .collect::<Vec<_>>()
+            .join(Separator::Dash.as_str());
+
+        return format!(
+            "{}{}",
+            CardAttributes::escape(separator.escape(&text).trim_end()),
+            CardAttributes::of(card)
+        );
+    }
+
+    let mut text = String::new();
+
+    for value in init {
+        let value = match separator {
+            Separator::Question => &value[..value.len() - 1],
+            _ => value,
+        };
+
+        text.push_str(&separator.escape(value));
+        text.push_str(separator.as_str());
     }
+
+    // separator at the end of the last value would be read as one before empty value
+    match last[0].ends_with(separator.as_str().trim_end()) && separator != Separator::Question {
+        true => text.push_str(&separator.escape(&last[0])),
+        false => text.push_str(&last[0]),
+    }
+
+    format!(
+        "{}{}",
+        CardAttributes::escape(text.trim_end()),
+        CardAttributes::of(card)
+    )
+}
+
+/// Formats card as heading with its front followed by back, only notes with at most two
+/// non-empty fields fit into this layout.
+fn format_section(card: &Card, level: u8) -> Result<String> {
+    if card.fields.values().skip(2).any(|v| !v.is_empty()) {
+        return Err(Error::invalid(format!(
+            "note '{}' of type '{}' has more than two fields, it can't be written in headings style",
+            card.front(),
+            card.model
+        )));
+    }
+
+    let front = CardAttributes::escape(&field_to_markdown(card.front(), |markdown| {
+        read_heading_field(markdown, level)
+    }));
+    let heading = format!(
+        "{} {front}{}",
+        "#".repeat(level as usize),
+        CardAttributes::of(card)
+    );
+
+    Ok(match card.back() {
+        "" => format!("{heading}\n"),
+        back => format!(
+            "{heading}\n\n{}\n",
+            field_to_markdown(back, |markdown| read_content_field(markdown, level))
+        ),
+    })
+}
+
+/// Indents lines of multi-line card after the first one, so they stay in its list item.
+fn indent_continuation(text: &str, width: usize) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("{:width$}{line}", "")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 pub struct FromMarkdownDeck {
     path: PathBuf,
+    options: MarkdownOptions,
 }
 
 impl FromMarkdownDeck {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         Ok(Self {
             path: path.as_ref().to_path_buf(),
+            options: MarkdownOptions::default(),
         })
     }
 
+    pub fn with_options(mut self, options: MarkdownOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn for_each<A>(&self, action: A) -> Result<()>
     where
         A: AsyncFn(Card) -> Result<()>,
     {
-        FileCommitBuffer::new(
-            async |data| data,
-            async |data| action(Card::from_str(data.as_str())?).await,
-        )?
-        .exec_and_commit(MarkdownListStream::new(&self.path)?)
-        .await
+        self.for_each_in_subdeck(async |_, card| action(card).await)
+            .await
+    }
+
+    /// Passes every card along with subdeck it belongs to, subdecks are known only in headings
+    /// style with subdecks enabled.
+    pub async fn for_each_in_subdeck<A>(&self, action: A) -> Result<()>
+    where
+        A: AsyncFn(Option<String>, Card) -> Result<()>,
+    {
+        self.options.validate()?;
+
+        match self.options.style {
+            MarkdownStyle::List => {
+                FileCommitBuffer::new(
+                    async |data: Result<(usize, String)>| {
+                        let (line, text) = data?;
+                        parse_card(&text, self.options.separator)
+                            .map(|card| (None, card))
+                            .map_err(|e| Error::parse(&self.path, Some(line), e))
+                    },
+                    async |(subdeck, card)| action(subdeck, card).await,
+                )?
+                .exec_and_commit(MarkdownListStream::new(&self.path)?)
+                .await
+            }
+            MarkdownStyle::Headings => {
+                if !self.path.exists() {
+                    return Err(Error::FileNotFound(self.path.clone()));
+                }
+
+                let content = std::fs::read_to_string(&self.path)?;
+
+                FileCommitBuffer::new(
+                    async |data: Result<(usize, Section)>| {
+                        let (line, section) = data?;
+                        parse_section(&section)
+                            .map(|card| (section.subdeck, card))
+                            .map_err(|e| Error::parse(&self.path, Some(line), e))
+                    },
+                    async |(subdeck, card)| action(subdeck, card).await,
+                )?
+                .exec_and_commit(read_sections(&self.path, &content, &self.options).into_iter())
+                .await
+            }
+        }
     }
 }
 
 pub struct ToMarkdownDeck {
     path: PathBuf,
+    options: MarkdownOptions,
 }
 
 impl ToMarkdownDeck {
     pub fn new(deck: &str) -> Self {
         Self {
             path: PathBuf::from(format!("{deck}.md")),
+            options: MarkdownOptions::default(),
         }
     }
 
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
+    pub fn with_options(mut self, options: MarkdownOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
+        self.write_in_subdecks(cards.map(|card| (None, card))).await
+    }
+
+    /// Writes cards along with subdecks they belong to, in headings style with subdecks enabled
+    /// every subdeck starts with top-level heading, so cards have to be grouped by subdeck.
+    pub async fn write_in_subdecks(
+        &self,
+        cards: impl Iterator<Item = (Option<String>, Card)>,
+    ) -> Result<String> {
         use std::io::{BufWriter, Write};
-        use std::sync::{
-            Arc, Mutex,
-            atomic::{AtomicUsize, Ordering},
-        };
+        use std::sync::Mutex;
+
+        self.options.validate()?;
 
         if self.path.exists() {
-            return Err(anyhow::anyhow!(
-                "file {} already exists",
-                self.path.display()
-            ));
+            return Err(Error::FileExists(self.path.clone()));
         }
 
-        let writer = Arc::new(Mutex::new(BufWriter::new(File::create(&self.path)?)));
-        let counter = Arc::new(AtomicUsize::new(1));
+        // writer, number of written cards and subdeck of the last one
+        let state = Mutex::new((
+            BufWriter::new(File::create(&self.path)?),
+            0usize,
+            None::<String>,
+        ));
 
         FileCommitBuffer::new(
-            async |data| Ok(data),
-            async |data| {
-                let writer = Arc::clone(&writer);
-                let counter = Arc::clone(&counter);
-                let card = Card::from_str(data.as_str())?;
+            async |item| Ok(item),
+            async |(subdeck, card): (Option<String>, Card)| {
+                let mut state = state
+                    .lock()
+                    .map_err(|_| Error::invalid("failed to lock writer"))?;
+                let (writer, count, current) = &mut *state;
+
+                *count += 1;
+
+                match self.options.style {
+                    MarkdownStyle::List => {
+                        let marker = format!("{count}. ");
+
+                        writeln!(
+                            writer,
+                            "{marker}{}",
+                            indent_continuation(
+                                &format_card(&card, self.options.separator),
+                                marker.len()
+                            )
+                        )?;
+                    }
+                    MarkdownStyle::Headings => {
+                        if *count > 1 {
+                            writeln!(writer)?;
+                        }
 
-                writeln!(
-                    match writer.lock() {
-                        Ok(w) => w,
-                        Err(_) => return Err(anyhow::anyhow!("failed to lock writer")),
-                    },
-                    "{}. {} - {}",
-                    counter.load(Ordering::Relaxed),
-                    card.front,
-                    card.back
-                )?;
+                        if self.options.subdecks && subdeck != *current {
+                            let Some(name) = &subdeck else {
+                                return Err(Error::invalid(
+                                    "cards of deck itself have to go before cards of subdecks",
+                                ));
+                            };
 
-                counter.fetch_add(1, Ordering::Relaxed);
+                            writeln!(writer, "# {name}\n")?;
+                            *current = subdeck;
+                        }
 
-                Ok(())
+                        write!(
+                            writer,
+                            "{}",
+                            format_section(&card, self.options.heading_level)?
+                        )?;
+                    }
+                }
+
+                Ok(())
             },
         )?
         .exec_and_commit(cards)
@@ -212,7 +1078,7 @@ impl ToMarkdownDeck {
         self.path
             .to_str()
             .map(|s| s.to_string())
-            .ok_or_else(|| anyhow::anyhow!("failed to convert path to string"))
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
     }
 }
 
@@ -291,232 +1157,1162 @@ mod tests {
 - 2+2 ? - 4
 - pi? - It's definitely 3.14..."#
         )
-        .expect("failed to write to temp file");
+        .expect("failed to write to temp file");
+
+        FromMarkdownDeck::new(md_file.path())
+            .expect("failed to create FromMarkdownDeck")
+            .for_each(async |data| {
+                writeln!(
+                    Arc::clone(&file).lock().expect("failed to get file"),
+                    "{data}"
+                )?;
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        file.lock()
+            .expect("failed to get file")
+            .rewind()
+            .expect("failed to rewind temp file");
+        let file_guard = file.lock().expect("failed to get file");
+        let mut lines = BufReader::new(file_guard.as_file()).lines();
+
+        assert_eq!(lines.next().unwrap().unwrap(), "Q - A");
+        assert_eq!(lines.next().unwrap().unwrap(), "Which color? - Blue");
+        assert_eq!(lines.next().unwrap().unwrap(), "ABCD? - Yes, EFGH");
+        assert_eq!(lines.next().unwrap().unwrap(), "2+2 ? - 4");
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "pi? - It's definitely 3.14..."
+        );
+        assert!(lines.next().is_none(), "expected no more lines in the file");
+    }
+
+    #[tokio::test]
+    pub async fn test_from_markdown_deck_keeps_card_content() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        let cards = Arc::new(Mutex::new(Vec::new()));
+
+        writeln!(
+            &md_file,
+            r#"1. Як справи? - Добре - дякую
+2. 你好 - hello 🦀"#
+        )
+        .expect("failed to write to temp file");
+
+        FromMarkdownDeck::new(md_file.path())
+            .expect("failed to create FromMarkdownDeck")
+            .for_each(async |card| {
+                Arc::clone(&cards)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(
+            *cards.lock().expect("failed to get cards"),
+            vec![
+                Card::basic("Як справи?", "Добре - дякую"),
+                Card::basic("你好", "hello 🦀"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_from_markdown_deck_ordered_list() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        let file = Arc::new(Mutex::new(
+            NamedTempFile::new().expect("failed to create temp file"),
+        ));
+
+        writeln!(
+            &md_file,
+            r#"1. Q - A
+2. Which color? - Blue
+3 ABCD? - Yes, EFGH
+4. 2+2 ? - 4
+5. pi? - It's definitely 3.14..."#
+        )
+        .expect("failed to write to temp file");
+
+        assert!(matches!(
+            FromMarkdownDeck::new(md_file.path())
+                .expect("failed to create FromMarkdownDeck")
+                .for_each(async |data| {
+                    writeln!(
+                        Arc::clone(&file).lock().expect("failed to get file"),
+                        "{data}"
+                    )?;
+                    Ok(())
+                })
+                .await,
+            Err(Error::Parse { line: Some(3), .. })
+        ));
+
+        assert_eq!(
+            metadata(file.lock().expect("failed to get file").path())
+                .expect("failed to get metadata of file")
+                .len(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_from_markdown_deck_unordered_list() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        let file = Arc::new(Mutex::new(
+            NamedTempFile::new().expect("failed to create temp file"),
+        ));
+
+        writeln!(
+            &md_file,
+            r#"- Q - A
+- Which color? - Blue
+- ABCD? - Yes, EFGH
+2+2 ? - 4
+- pi? - It's definitely 3.14..."#
+        )
+        .expect("failed to write to temp file");
+
+        assert!(
+            FromMarkdownDeck::new(md_file.path())
+                .expect("failed to create FromMarkdownDeck")
+                .for_each(async |data| {
+                    writeln!(
+                        Arc::clone(&file).lock().expect("failed to get file"),
+                        "{data}"
+                    )?;
+                    Ok(())
+                })
+                .await
+                .is_err()
+        );
+
+        assert_eq!(
+            metadata(file.lock().expect("faield to get file").path())
+                .expect("failed to get metadata of file")
+                .len(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_to_markdown_deck() {
+        use tempfile::tempdir;
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("test_deck.md");
+
+        let cards = vec![
+            Card::from_str("Q - A").expect("failed to create card"),
+            Card::from_str("Which color? - Blue").expect("failed to create card"),
+            Card::from_str("ABCD? - Yes, EFGH").expect("failed to create card"),
+            Card::from_str("2+2 ? - 4").expect("failed to create card"),
+            Card::from_str("pi? - It's definitely 3.14...").expect("failed to create card"),
+        ];
+
+        let mut to_markdown_deck = ToMarkdownDeck::new("test_deck");
+        to_markdown_deck.path = temp_path.clone();
+
+        to_markdown_deck
+            .write(cards.into_iter())
+            .await
+            .expect("failed to write card to Markdown file");
+
+        assert!(temp_path.exists());
+
+        assert_eq!(
+            read_to_string(&temp_path).expect("failed to read file"),
+            r#"1. Q - A
+2. Which color? - Blue
+3. ABCD? - Yes, EFGH
+4. 2+2 ? - 4
+5. pi? - It's definitely 3.14...
+"#
+        )
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_to_markdown_deck() {
+        use tempfile::tempdir;
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("failed_test_deck.md");
+
+        File::create(&temp_path).expect("failed to create Markdown file");
+
+        let mut to_markdown_deck = ToMarkdownDeck::new("failed_test_deck");
+        to_markdown_deck.path = temp_path;
+
+        assert!(
+            to_markdown_deck
+                .write(vec![Card::default()].into_iter())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_custom_note_types() {
+        use anki_multitool_ds::card::Fields;
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic("Q", "A"),
+            Card::new(
+                "Basic (and reversed card)",
+                Fields::from([
+                    ("Front".to_string(), "Kyiv".to_string()),
+                    ("Back".to_string(), "Capital of Ukraine".to_string()),
+                ]),
+            ),
+            Card::new(
+                "Vocabulary",
+                Fields::from([
+                    ("Word".to_string(), "серце".to_string()),
+                    ("Meaning".to_string(), "heart".to_string()),
+                    ("Example".to_string(), "моє серце".to_string()),
+                ]),
+            ),
+        ];
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("custom_deck.md");
+
+        let mut to_markdown_deck = ToMarkdownDeck::new("custom_deck");
+        to_markdown_deck.path = temp_path.clone();
+
+        to_markdown_deck
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write cards to Markdown file");
+
+        assert_eq!(
+            read_to_string(&temp_path).expect("failed to read file"),
+            r#"1. Q - A
+2. Kyiv - Capital of Ukraine {model: Basic (and reversed card)}
+3. серце - heart - моє серце {model: Vocabulary; fields: Word, Meaning, Example}
+"#
+        );
+
+        let imported = Arc::new(Mutex::new(Vec::new()));
+
+        FromMarkdownDeck::new(&temp_path)
+            .expect("failed to create FromMarkdownDeck")
+            .for_each(async |card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_cloze() {
+        use tempfile::tempdir;
+
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+
+        writeln!(
+            &md_file,
+            r#"- {{{{c1::Kyiv}}}} is the capital of {{{{c2::Ukraine}}}}
+- {{{{c1::T-cell::cell}}}} is a type of lymphocyte - part of immune system
+- Q - A"#
+        )
+        .expect("failed to write to temp file");
+
+        let cards = Arc::new(Mutex::new(Vec::new()));
+
+        FromMarkdownDeck::new(md_file.path())
+            .expect("failed to create FromMarkdownDeck")
+            .for_each(async |card| {
+                Arc::clone(&cards)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        let cards = cards.lock().expect("failed to get cards").clone();
+
+        assert_eq!(
+            cards,
+            vec![
+                Card::cloze("{{c1::Kyiv}} is the capital of {{c2::Ukraine}}", ""),
+                Card::cloze(
+                    "{{c1::T-cell::cell}} is a type of lymphocyte",
+                    "part of immune system"
+                ),
+                Card::basic("Q", "A"),
+            ]
+        );
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("cloze_deck.md");
+
+        let mut to_markdown_deck = ToMarkdownDeck::new("cloze_deck");
+        to_markdown_deck.path = temp_path.clone();
+
+        to_markdown_deck
+            .write(cards.into_iter())
+            .await
+            .expect("failed to write cards to Markdown file");
+
+        assert_eq!(
+            read_to_string(&temp_path).expect("failed to read file"),
+            r#"1. {{c1::Kyiv}} is the capital of {{c2::Ukraine}}
+2. {{c1::T-cell::cell}} is a type of lymphocyte - part of immune system
+3. Q - A
+"#
+        );
+    }
+
+    fn formatted(card: &Card) -> String {
+        format_card(card, Separator::default())
+    }
+
+    #[test]
+    pub fn test_parse_card_attributes() {
+        let card =
+            parse_card("fn() {} - closure", Separator::default()).expect("failed to parse card");
+
+        assert!(card.is_basic());
+        assert_eq!(card.front(), "fn() {}");
+
+        let card = parse_card(
+            "Text - Extra {model: Custom; fields: Text, Extra, Source}",
+            Separator::default(),
+        )
+        .expect("failed to parse card");
+
+        assert_eq!(card.model, "Custom");
+        assert_eq!(
+            card.fields.keys().cloned().collect::<Vec<_>>(),
+            vec!["Text", "Extra", "Source"]
+        );
+        assert_eq!(card.fields["Source"], "");
+
+        assert!(parse_card("Text - Extra {model: Custom}", Separator::default()).is_err());
+
+        let card = parse_card(
+            "Kyiv - Ukraine {tags: geography, capitals europe}",
+            Separator::default(),
+        )
+        .expect("failed to parse card");
+
+        assert_eq!(
+            card,
+            Card::basic("Kyiv", "Ukraine").with_tags(vec![
+                "geography".to_string(),
+                "capitals".to_string(),
+                "europe".to_string()
+            ])
+        );
+        assert_eq!(
+            formatted(&card),
+            "Kyiv - Ukraine {tags: geography, capitals, europe}"
+        );
+
+        let card = Card::cloze("{{c1::Kyiv}}", "").with_tags(vec!["geography".to_string()]);
+
+        assert_eq!(formatted(&card), "{{c1::Kyiv}} {tags: geography}");
+        assert_eq!(
+            parse_card(&formatted(&card), Separator::default()).expect("failed to parse card"),
+            card
+        );
+
+        assert_eq!(formatted(&Card::basic("Q", "A")), "Q - A");
+
+        let card = Card::basic("Q", "A")
+            .with_tags(vec!["geography".to_string()])
+            .with_id(Some(1700000000000));
+
+        assert_eq!(
+            formatted(&card),
+            "Q - A {id: 1700000000000; tags: geography}"
+        );
+        assert_eq!(
+            parse_card(&formatted(&card), Separator::default()).expect("failed to parse card"),
+            card
+        );
+
+        let card =
+            parse_card("Q - A {id: latest}", Separator::default()).expect("failed to parse card");
+
+        assert_eq!(card.back(), "A {id: latest}");
+        assert_eq!(card.id, None);
+    }
+
+    #[test]
+    pub fn test_markdown_stream_ordered_list() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        writeln!(
+            &md_file,
+            r#"1. Q - A
+2. Which color? - Blue
+3. ABCD? - Yes, EFGH
+4. 2+2 ? - 4
+5. pi? - It's definitely 3.14...
+"#
+        )
+        .expect("faield to write to temp file");
+
+        let stream =
+            MarkdownListStream::new(md_file.path()).expect("failed to create MarkdownListStream");
+
+        assert_eq!(
+            stream
+                .map(|item| item.map(|(_, text)| text))
+                .collect::<Result<Vec<_>>>()
+                .expect("failed to collect stream"),
+            vec![
+                "Q - A".to_string(),
+                "Which color? - Blue".to_string(),
+                "ABCD? - Yes, EFGH".to_string(),
+                "2+2 ? - 4".to_string(),
+                "pi? - It's definitely 3.14...".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_markdown_stream_unordered_list() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        writeln!(
+            &md_file,
+            r#"- Q - A
+- Which color? - Blue
+- ABCD? - Yes, EFGH
+- 2+2 ? - 4
+- pi? - It's definitely 3.14...
+"#
+        )
+        .expect("faield to write to temp file");
+
+        let stream =
+            MarkdownListStream::new(md_file.path()).expect("failed to create MarkdownListStream");
+
+        assert_eq!(
+            stream
+                .map(|item| item.map(|(_, text)| text))
+                .collect::<Result<Vec<_>>>()
+                .expect("failed to collect stream"),
+            vec![
+                "Q - A".to_string(),
+                "Which color? - Blue".to_string(),
+                "ABCD? - Yes, EFGH".to_string(),
+                "2+2 ? - 4".to_string(),
+                "pi? - It's definitely 3.14...".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_markdown_stream_multiline_items() {
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        writeln!(
+            &md_file,
+            r#"1. How to print a line? - Use **`println!`**:
+   ```rust
+   println!("{{}}", 42);
+   ```
+2. Collections of _std_? - Most used are:
+   - `Vec`
+   - `HashMap`
+
+3. What is a borrow? - A reference
+   to a value owned by someone else.
+
+   It can't outlive the owner.
+4. 2 < 3? - Yes {{tags: math}}
+"#
+        )
+        .expect("faield to write to temp file");
+
+        let stream =
+            MarkdownListStream::new(md_file.path()).expect("failed to create MarkdownListStream");
+
+        assert_eq!(
+            stream
+                .collect::<Result<Vec<_>>>()
+                .expect("failed to collect stream"),
+            vec![
+                (
+                    1,
+                    "How to print a line? - Use <b><code>println!</code></b>:\n<pre><code class=\"language-rust\">println!(\"\\{\\}\", 42);\n</code></pre>".to_string()
+                ),
+                (
+                    5,
+                    "Collections of <i>std</i>? - Most used are:\n<ul>\n<li><code>Vec</code></li>\n<li><code>HashMap</code></li>\n</ul>".to_string()
+                ),
+                (
+                    9,
+                    "What is a borrow? - A reference\nto a value owned by someone else.\n<p>It can't outlive the owner.</p>".to_string()
+                ),
+                (13, "2 &lt; 3? - Yes {tags: math}".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_multiline_card() {
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic(
+                "How to print a line?",
+                "Use <code>println!</code>:<pre><code>println!(\"{}\", 42);\n</code></pre>",
+            ),
+            Card::basic("Q", "A"),
+        ];
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("multiline_deck.md");
+
+        ToMarkdownDeck::new("multiline_deck")
+            .in_dir(temp_dir.path())
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write cards to Markdown file");
+
+        assert_eq!(
+            read_to_string(&temp_path).expect("failed to read file"),
+            r#"1. How to print a line? - Use <code>println!</code>:<pre><code>println!("{}", 42);
+   </code></pre>
+2. Q - A
+"#
+        );
+
+        let imported = Arc::new(Mutex::new(Vec::new()));
+
+        FromMarkdownDeck::new(&temp_path)
+            .expect("failed to create FromMarkdownDeck")
+            .for_each(async |card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_formatting() {
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic(
+                "What is <b>ownership</b>?",
+                "<i>Set</i> of rules<br>of memory management",
+            ),
+            Card::basic(
+                "Where is <code>snake_case</code> used?",
+                r#"See <a href="https://rust-lang.org">docs</a> <img src="heart.png">"#,
+            ),
+            Card::basic(
+                "2 * 3&nbsp;=",
+                "<u>6</u> <span style=\"color: red\">!</span>",
+            ),
+            Card::basic("Raw <div>block</div>", "<b>unbalanced"),
+        ];
+
+        for (options, expected) in [
+            (
+                MarkdownOptions::default(),
+                r#"1. What is **ownership**? - *Set* of rules\
+   of memory management
+2. Where is `snake_case` used? - See [docs](https://rust-lang.org) ![](heart.png)
+3. 2 \* 3&nbsp;= - <u>6</u> <span style="color: red">!</span>
+4. Raw <div>block</div> - <b>unbalanced
+"#,
+            ),
+            (
+                MarkdownOptions {
+                    style: MarkdownStyle::Headings,
+                    ..MarkdownOptions::default()
+                },
+                r#"## What is **ownership**?
+
+*Set* of rules\
+of memory management
+
+## Where is `snake_case` used?
+
+See [docs](https://rust-lang.org) ![](heart.png)
+
+## 2 \* 3&nbsp;=
+
+<u>6</u> <span style="color: red">!</span>
+
+## Raw <div>block</div>
+
+<b>unbalanced
+"#,
+            ),
+        ] {
+            let temp_dir = tempdir().expect("failed to create temp directory");
+            let path = ToMarkdownDeck::new("formatting")
+                .in_dir(temp_dir.path())
+                .with_options(options)
+                .write(cards.clone().into_iter())
+                .await
+                .expect("failed to write cards to Markdown file");
+
+            assert_eq!(
+                read_to_string(&path).expect("failed to read file"),
+                expected
+            );
+
+            let imported = Arc::new(Mutex::new(Vec::new()));
+
+            FromMarkdownDeck::new(&path)
+                .expect("failed to create FromMarkdownDeck")
+                .with_options(options)
+                .for_each(async |card| {
+                    Arc::clone(&imported)
+                        .lock()
+                        .expect("failed to get cards")
+                        .push(card);
+                    Ok(())
+                })
+                .await
+                .expect("failed to process cards");
+
+            assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_block_html() {
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic("<div>a</div>", "b"),
+            Card::basic("A", "A\n\n## y"),
+            Card::basic("Q", "<pre>a\n\n## b</pre>"),
+            Card::basic("R", "a\n\n*b*"),
+        ];
+
+        for (options, expected) in [
+            (
+                MarkdownOptions::default(),
+                r"1. <div>a</div> - b
+2. A - A&#10;&#10;## y
+3. Q - <pre>a&#10;&#10;## b</pre>
+4. R - a&#10;&#10;\*b\*
+",
+            ),
+            (
+                MarkdownOptions {
+                    style: MarkdownStyle::Headings,
+                    ..MarkdownOptions::default()
+                },
+                r"## <div>a</div>
+
+b
+
+## A
+
+A&#10;&#10;## y
+
+## Q
+
+<pre>a
+
+## b</pre>
+
+## R
+
+a&#10;&#10;\*b\*
+",
+            ),
+        ] {
+            let temp_dir = tempdir().expect("failed to create temp directory");
+            let path = ToMarkdownDeck::new("block")
+                .in_dir(temp_dir.path())
+                .with_options(options)
+                .write(cards.clone().into_iter())
+                .await
+                .expect("failed to write cards to Markdown file");
+
+            assert_eq!(
+                read_to_string(&path).expect("failed to read file"),
+                expected
+            );
+
+            let imported = Arc::new(Mutex::new(Vec::new()));
+
+            FromMarkdownDeck::new(&path)
+                .expect("failed to create FromMarkdownDeck")
+                .with_options(options)
+                .for_each(async |card| {
+                    Arc::clone(&imported)
+                        .lock()
+                        .expect("failed to get cards")
+                        .push(card);
+                    Ok(())
+                })
+                .await
+                .expect("failed to process cards");
+
+            assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_attributes_in_text() {
+        use tempfile::tempdir;
+
+        let cards = vec![
+            Card::basic("Set {tags: a}", "b"),
+            Card::basic("Q", "Set {tags: a}"),
+            Card::basic("Set {tags: a}", "")
+                .with_tags(vec!["b".to_string()])
+                .with_id(Some(1)),
+        ];
+
+        for (options, expected) in [
+            (
+                MarkdownOptions::default(),
+                r"1. Set {tags: a} - b
+2. Q - Set \{tags: a}
+3. Set {tags: a} - {id: 1; tags: b}
+",
+            ),
+            (
+                MarkdownOptions {
+                    style: MarkdownStyle::Headings,
+                    ..MarkdownOptions::default()
+                },
+                r"## Set \{tags: a}
+
+b
+
+## Q
+
+Set {tags: a}
+
+## Set \{tags: a} {id: 1; tags: b}
+",
+            ),
+        ] {
+            let temp_dir = tempdir().expect("failed to create temp directory");
+            let path = ToMarkdownDeck::new("attributes")
+                .in_dir(temp_dir.path())
+                .with_options(options)
+                .write(cards.clone().into_iter())
+                .await
+                .expect("failed to write cards to Markdown file");
+
+            assert_eq!(
+                read_to_string(&path).expect("failed to read file"),
+                expected
+            );
+
+            let imported = Arc::new(Mutex::new(Vec::new()));
+
+            FromMarkdownDeck::new(&path)
+                .expect("failed to create FromMarkdownDeck")
+                .with_options(options)
+                .for_each(async |card| {
+                    Arc::clone(&imported)
+                        .lock()
+                        .expect("failed to get cards")
+                        .push(card);
+                    Ok(())
+                })
+                .await
+                .expect("failed to process cards");
+
+            assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+        }
+    }
+
+    #[test]
+    pub fn test_markdown_to_anki_html() {
+        let render = |markdown: &str| render_html(pulldown_cmark::Parser::new(markdown).collect());
+
+        assert_eq!(
+            render("**bold**, __bold__, *italic* and _italic_"),
+            "<b>bold</b>, <b>bold</b>, <i>italic</i> and <i>italic</i>"
+        );
+        assert_eq!(render("line\\\nbreak"), "line<br>break");
+        assert_eq!(
+            render("![a \"heart\"](img/heart.png) ![](<my file.png>)"),
+            r#"<img src="img/heart.png" alt="a &quot;heart&quot;"> <img src="my file.png">"#
+        );
+        assert_eq!(render("a&nbsp;b &lt; c"), "a&nbsp;b &lt; c");
+        assert_eq!(
+            field_to_markdown("<b>bold </b>text", read_item_field),
+            "<b>bold </b>text",
+            "Markdown doesn't allow space before closing delimiter"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_markdown_deck_separators() {
+        use tempfile::tempdir;
+
+        let md_file = NamedTempFile::new().expect("failed to create temp file");
+        writeln!(
+            &md_file,
+            r#"1. What is 3-2? - 1
+2. Not a \- separator - dash
+3. `a - b` - code
+4. Escaped \\ backslash - a\b"#
+        )
+        .expect("faield to write to temp file");
+
+        let imported = Arc::new(Mutex::new(Vec::new()));
 
         FromMarkdownDeck::new(md_file.path())
             .expect("failed to create FromMarkdownDeck")
-            .for_each(async |data| {
-                writeln!(
-                    Arc::clone(&file).lock().expect("failed to get file"),
-                    "{data}"
-                )?;
+            .for_each(async |card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
                 Ok(())
             })
             .await
             .expect("failed to process cards");
 
-        file.lock()
-            .expect("failed to get file")
-            .rewind()
-            .expect("failed to rewind temp file");
-        let file_guard = file.lock().expect("failed to get file");
-        let mut lines = BufReader::new(file_guard.as_file()).lines();
-
-        assert_eq!(lines.next().unwrap().unwrap(), "Q - A");
-        assert_eq!(lines.next().unwrap().unwrap(), "Which color? - Blue");
-        assert_eq!(lines.next().unwrap().unwrap(), "ABCD? - Yes, EFGH");
-        assert_eq!(lines.next().unwrap().unwrap(), "2+2 ? - 4");
         assert_eq!(
-            lines.next().unwrap().unwrap(),
-            "pi? - It's definitely 3.14..."
+            *imported.lock().expect("failed to get cards"),
+            vec![
+                Card::basic("What is 3-2?", "1"),
+                Card::basic("Not a - separator", "dash"),
+                Card::basic("<code>a - b</code>", "code"),
+                Card::basic("Escaped \\ backslash", "a\\b"),
+            ]
         );
-        assert!(lines.next().is_none(), "expected no more lines in the file");
-    }
 
-    #[tokio::test]
-    pub async fn test_failed_from_markdown_deck_ordered_list() {
-        let md_file = NamedTempFile::new().expect("failed to create temp file");
-        let file = Arc::new(Mutex::new(
-            NamedTempFile::new().expect("failed to create temp file"),
-        ));
+        let cards = vec![
+            Card::basic("Is 3-2 equal to 1?", "Yes - it is"),
+            Card::basic("a - b | c :: d? e?", "f"),
+            Card::basic("Ends with separator -?", "x"),
+            Card::basic("Empty back", ""),
+            Card::basic("Ends with dash -", ""),
+            Card::basic("Question?", ""),
+            Card::basic("Q", "Ends with dash -"),
+        ];
 
-        writeln!(
-            &md_file,
-            r#"1. Q - A
-2. Which color? - Blue
-3 ABCD? - Yes, EFGH
-4. 2+2 ? - 4
-5. pi? - It's definitely 3.14..."#
-        )
-        .expect("failed to write to temp file");
+        for separator in [
+            Separator::Dash,
+            Separator::DoubleColon,
+            Separator::Pipe,
+            Separator::Question,
+        ] {
+            let temp_dir = tempdir().expect("failed to create temp directory");
+            let options = MarkdownOptions {
+                separator,
+                ..MarkdownOptions::default()
+            };
+
+            let path = ToMarkdownDeck::new("separators")
+                .in_dir(temp_dir.path())
+                .with_options(options)
+                .write(cards.clone().into_iter())
+                .await
+                .expect("failed to write cards to Markdown file");
+            let imported = Arc::new(Mutex::new(Vec::new()));
 
-        assert!(
-            FromMarkdownDeck::new(md_file.path())
+            FromMarkdownDeck::new(&path)
                 .expect("failed to create FromMarkdownDeck")
-                .for_each(async |data| {
-                    writeln!(
-                        Arc::clone(&file).lock().expect("failed to get file"),
-                        "{data}"
-                    )?;
+                .with_options(options)
+                .for_each(async |card| {
+                    Arc::clone(&imported)
+                        .lock()
+                        .expect("failed to get cards")
+                        .push(card);
                     Ok(())
                 })
                 .await
-                .is_err()
-        );
+                .expect("failed to process cards");
+
+            assert_eq!(
+                *imported.lock().expect("failed to get cards"),
+                cards,
+                "{separator:?}"
+            );
+        }
+
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let options = MarkdownOptions {
+            separator: Separator::Question,
+            ..MarkdownOptions::default()
+        };
+        let cards = vec![
+            Card::basic("Not a question", "A"),
+            Card::basic("Why? Because - of it", "Is it?"),
+            Card::basic("Is it?", "Yes? Really - no"),
+            Card::cloze("{{c1::Kyiv}} - capital? Yes", ""),
+        ];
+        let path = ToMarkdownDeck::new("questions")
+            .in_dir(temp_dir.path())
+            .with_options(options)
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write cards to Markdown file");
 
         assert_eq!(
-            metadata(file.lock().expect("failed to get file").path())
-                .expect("failed to get metadata of file")
-                .len(),
-            0
+            read_to_string(&path).expect("failed to read file"),
+            r#"1. Not a question - A
+2. Why\? Because \- of it - Is it?
+3. Is it? Yes? Really - no
+4. {{c1::Kyiv}} \- capital\? Yes
+"#
         );
+
+        let imported = Arc::new(Mutex::new(Vec::new()));
+
+        FromMarkdownDeck::new(&path)
+            .expect("failed to create FromMarkdownDeck")
+            .with_options(options)
+            .for_each(async |card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push(card);
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(*imported.lock().expect("failed to get cards"), cards);
     }
 
     #[tokio::test]
-    pub async fn test_failed_from_markdown_deck_unordered_list() {
+    pub async fn test_from_markdown_deck_headings() {
         let md_file = NamedTempFile::new().expect("failed to create temp file");
-        let file = Arc::new(Mutex::new(
-            NamedTempFile::new().expect("failed to create temp file"),
-        ));
-
         writeln!(
             &md_file,
-            r#"- Q - A
-- Which color? - Blue
-- ABCD? - Yes, EFGH
-  2+2 ? - 4
-- pi? - It's definitely 3.14..."#
+            r#"# Lecture 5: Ownership
+
+## What is ownership?
+
+Set of rules of **memory management**.
+
+### Rules
+
+- Each value has an owner.
+- There can only be one owner at a time.
+
+## What does `move` do? {{tags: rust closures}}
+Moves captured values into closure.
+## {{{{c1::Borrow checker}}}} validates references
+"#
         )
         .expect("failed to write to temp file");
 
-        assert!(
-            FromMarkdownDeck::new(md_file.path())
-                .expect("failed to create FromMarkdownDeck")
-                .for_each(async |data| {
-                    writeln!(
-                        Arc::clone(&file).lock().expect("failed to get file"),
-                        "{data}"
-                    )?;
-                    Ok(())
-                })
-                .await
-                .is_err()
-        );
+        let cards = Arc::new(Mutex::new(Vec::new()));
+
+        FromMarkdownDeck::new(md_file.path())
+            .expect("failed to create FromMarkdownDeck")
+            .with_options(MarkdownOptions {
+                style: MarkdownStyle::Headings,
+                ..MarkdownOptions::default()
+            })
+            .for_each_in_subdeck(async |subdeck, card| {
+                Arc::clone(&cards)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push((subdeck, card));
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
 
         assert_eq!(
-            metadata(file.lock().expect("faield to get file").path())
-                .expect("failed to get metadata of file")
-                .len(),
-            0
+            *cards.lock().expect("failed to get cards"),
+            vec![
+                (
+                    None,
+                    Card::basic(
+                        "What is ownership?",
+                        "Set of rules of <b>memory management</b>.\n<h3>Rules</h3>\n<ul>\n<li>Each value has an owner.</li>\n<li>There can only be one owner at a time.</li>\n</ul>"
+                    )
+                ),
+                (
+                    None,
+                    Card::basic(
+                        "What does <code>move</code> do?",
+                        "Moves captured values into closure."
+                    )
+                    .with_tags(vec!["rust".to_string(), "closures".to_string()])
+                ),
+                (
+                    None,
+                    Card::cloze("{{c1::Borrow checker}} validates references", "")
+                ),
+            ]
         );
     }
 
     #[tokio::test]
-    pub async fn test_to_markdown_deck() {
+    pub async fn test_markdown_deck_headings_with_subdecks() {
         use tempfile::tempdir;
 
-        let temp_dir = tempdir().expect("failed to create temp directory");
-        let temp_path = temp_dir.path().join("test_deck.md");
-
+        let options = MarkdownOptions {
+            style: MarkdownStyle::Headings,
+            heading_level: 3,
+            subdecks: true,
+            ..MarkdownOptions::default()
+        };
         let cards = vec![
-            Card::from_str("Q - A").expect("failed to create card"),
-            Card::from_str("Which color? - Blue").expect("failed to create card"),
-            Card::from_str("ABCD? - Yes, EFGH").expect("failed to create card"),
-            Card::from_str("2+2 ? - 4").expect("failed to create card"),
-            Card::from_str("pi? - It's definitely 3.14...").expect("failed to create card"),
+            (None, Card::basic("Q", "A")),
+            (
+                Some("Verbs".to_string()),
+                Card::basic("бігти", "to run")
+                    .with_id(Some(1700000000000))
+                    .with_tags(vec!["verbs".to_string()]),
+            ),
+            (Some("Verbs".to_string()), Card::basic("Empty back", "")),
+            (
+                Some("Nouns::Animals".to_string()),
+                Card::basic("кіт", "cat<p>Small domesticated carnivore.</p>"),
+            ),
         ];
 
-        let mut to_markdown_deck = ToMarkdownDeck::new("test_deck");
-        to_markdown_deck.path = temp_path.clone();
+        let temp_dir = tempdir().expect("failed to create temp directory");
+        let temp_path = temp_dir.path().join("ukrainian.md");
 
-        to_markdown_deck
-            .write(cards.into_iter())
+        ToMarkdownDeck::new("ukrainian")
+            .in_dir(temp_dir.path())
+            .with_options(options)
+            .write_in_subdecks(cards.clone().into_iter())
             .await
-            .expect("failed to write card to Markdown file");
-
-        assert!(temp_path.exists());
+            .expect("failed to write cards to Markdown file");
 
         assert_eq!(
             read_to_string(&temp_path).expect("failed to read file"),
-            r#"1. Q - A
-2. Which color? - Blue
-3. ABCD? - Yes, EFGH
-4. 2+2 ? - 4
-5. pi? - It's definitely 3.14...
-"#
-        )
-    }
+            r#"### Q
 
-    #[tokio::test]
-    pub async fn test_failed_to_markdown_deck() {
-        use tempfile::tempdir;
+A
 
-        let temp_dir = tempdir().expect("failed to create temp directory");
-        let temp_path = temp_dir.path().join("failed_test_deck.md");
+# Verbs
 
-        File::create(&temp_path).expect("failed to create Markdown file");
+### бігти {id: 1700000000000; tags: verbs}
 
-        let mut to_markdown_deck = ToMarkdownDeck::new("failed_test_deck");
-        to_markdown_deck.path = temp_path;
+to run
 
-        assert!(
-            to_markdown_deck
-                .write(vec![Card::default()].into_iter())
-                .await
-                .is_err()
-        );
-    }
+### Empty back
 
-    #[test]
-    pub fn test_markdown_stream_ordered_list() {
-        let md_file = NamedTempFile::new().expect("failed to create temp file");
-        writeln!(
-            &md_file,
-            r#"1. Q - A
-2. Which color? - Blue
-3. ABCD? - Yes, EFGH
-4. 2+2 ? - 4
-5. pi? - It's definitely 3.14...
+# Nouns::Animals
+
+### кіт
+
+cat<p>Small domesticated carnivore.</p>
 "#
-        )
-        .expect("faield to write to temp file");
+        );
 
-        let stream =
-            MarkdownListStream::new(md_file.path()).expect("failed to create MarkdownListStream");
+        let imported = Arc::new(Mutex::new(Vec::new()));
 
-        assert_eq!(
-            stream
-                .collect::<Result<Vec<_>>>()
-                .expect("failed to collect stream"),
-            vec![
-                "Q - A".to_string(),
-                "Which color? - Blue".to_string(),
-                "ABCD? - Yes, EFGH".to_string(),
-                "2+2 ? - 4".to_string(),
-                "pi? - It's definitely 3.14...".to_string(),
-            ]
+        FromMarkdownDeck::new(&temp_path)
+            .expect("failed to create FromMarkdownDeck")
+            .with_options(options)
+            .for_each_in_subdeck(async |subdeck, card| {
+                Arc::clone(&imported)
+                    .lock()
+                    .expect("failed to get cards")
+                    .push((subdeck, card));
+                Ok(())
+            })
+            .await
+            .expect("failed to process cards");
+
+        assert_eq!(*imported.lock().expect("failed to get cards"), cards);
+
+        assert!(
+            ToMarkdownDeck::new("unordered")
+                .in_dir(temp_dir.path())
+                .with_options(options)
+                .write_in_subdecks(
+                    vec![
+                        (Some("Verbs".to_string()), Card::basic("Q", "A")),
+                        (None, Card::basic("Q", "A")),
+                    ]
+                    .into_iter()
+                )
+                .await
+                .is_err()
         );
     }
 
-    #[test]
-    pub fn test_markdown_stream_unordered_list() {
-        let md_file = NamedTempFile::new().expect("failed to create temp file");
-        writeln!(
-            &md_file,
-            r#"- Q - A
-- Which color? - Blue
-- ABCD? - Yes, EFGH
-- 2+2 ? - 4
-- pi? - It's definitely 3.14...
-"#
-        )
-        .expect("faield to write to temp file");
+    #[tokio::test]
+    pub async fn test_failed_from_markdown_deck_headings() {
+        let headings = MarkdownOptions {
+            style: MarkdownStyle::Headings,
+            ..MarkdownOptions::default()
+        };
+        let cases = [
+            ("Intro\n\n## Q\n\nA\n", headings, Some(1)),
+            ("## Q\n\nA\n\n# Verbs\n\nrun\n", headings, Some(7)),
+            ("## Q\n\nA\n\n## {model: Custom}\n\nA\n", headings, Some(5)),
+            ("## Q {model: Custom}\n\nA\n", headings, Some(1)),
+            (
+                "## Q\n\nA\n",
+                MarkdownOptions {
+                    subdecks: true,
+                    ..MarkdownOptions::default()
+                },
+                None,
+            ),
+            (
+                "# Q\n\nA\n",
+                MarkdownOptions {
+                    heading_level: 1,
+                    subdecks: true,
+                    ..headings
+                },
+                None,
+            ),
+            (
+                "## Q\n\nA\n",
+                MarkdownOptions {
+                    heading_level: 7,
+                    ..headings
+                },
+                None,
+            ),
+        ];
 
-        let stream =
-            MarkdownListStream::new(md_file.path()).expect("failed to create MarkdownListStream");
+        for (content, options, line) in cases {
+            let md_file = NamedTempFile::new().expect("failed to create temp file");
+            write!(&md_file, "{content}").expect("failed to write to temp file");
 
-        assert_eq!(
-            stream
-                .collect::<Result<Vec<_>>>()
-                .expect("failed to collect stream"),
-            vec![
-                "Q - A".to_string(),
-                "Which color? - Blue".to_string(),
-                "ABCD? - Yes, EFGH".to_string(),
-                "2+2 ? - 4".to_string(),
-                "pi? - It's definitely 3.14...".to_string(),
-            ]
-        );
+            let result = FromMarkdownDeck::new(md_file.path())
+                .expect("failed to create FromMarkdownDeck")
+                .with_options(options)
+                .for_each(async |_| Ok(()))
+                .await;
+
+            match line {
+                Some(line) => assert!(
+                    matches!(result, Err(Error::Parse { line: Some(l), .. }) if l == line),
+                    "unexpected result for {content:?}: {result:?}"
+                ),
+                None => assert!(
+                    matches!(result, Err(Error::Invalid(_))),
+                    "unexpected result for {content:?}: {result:?}"
+                ),
+            }
+        }
     }
 
     #[test]
@@ -541,6 +2337,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"     1. Q - A
@@ -560,13 +2360,17 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"1. Q - A
 2. Which color? - Blue
-    - ABCD? - Yes, EFGH
-    - ABCD? - Yes, EFGH
-    - ABCD? - Yes, EFGH
+
+ABCD? - Yes, EFGH
+
 3. 2+2 ? - 4
 4. pi? - It's definitely 3.14...
 "#
@@ -581,6 +2385,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"# TITLE
@@ -620,6 +2428,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"     - Q - A
@@ -639,13 +2451,17 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"- Q - A
 - Which color? - Blue
-    1. ABCD? - Yes, EFGH
-    2. ABCD? - Yes, EFGH
-    3. ABCD? - Yes, EFGH
+
+ABCD? - Yes, EFGH
+
 - 2+2 ? - 4
 - pi? - It's definitely 3.14...
 "#
@@ -660,6 +2476,10 @@ mod tests {
             .as_file()
             .set_len(0)
             .expect("failed to clear temp file");
+        md_file
+            .as_file()
+            .rewind()
+            .expect("failed to rewind temp file");
         writeln!(
             &md_file,
             r#"# TITLE
@@ -0,0 +1,210 @@
+use std::{
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result, text_position},
+};
+
+use crate::document::{DeckDocument, DeckDocumentRef};
+
+/// TOML deck is parsed as a whole, so invalid card is reported before any card is imported.
+pub struct FromTomlDeck {
+    document: DeckDocument,
+}
+
+impl FromTomlDeck {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let content = read_to_string(&path)?;
+        let document = DeckDocument::from_deserializer(
+            path.as_ref(),
+            ::toml::Deserializer::new(&content),
+            |e| e.span().map(|span| text_position(&content, span.start)),
+        )?;
+
+        Ok(Self { document })
+    }
+
+    /// Name of deck given in document.
+    pub fn deck(&self) -> Option<&str> {
+        self.document.deck.as_deref()
+    }
+
+    pub async fn for_each<A>(&self, action: A) -> Result<()>
+    where
+        A: AsyncFn(Card) -> Result<()>,
+    {
+        for card in self.document.cards.iter().cloned() {
+            action(card).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ToTomlDeck {
+    deck: String,
+    path: PathBuf,
+}
+
+impl ToTomlDeck {
+    pub fn new(deck: &str) -> Self {
+        Self {
+            deck: deck.to_string(),
+            path: PathBuf::from(format!("{deck}.toml")),
+        }
+    }
+
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
+    pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
+        if self.path.exists() {
+            return Err(Error::FileExists(self.path.clone()));
+        }
+
+        let cards: Vec<Card> = cards.collect();
+
+        write(
+            &self.path,
+            ::toml::to_string(&DeckDocumentRef {
+                deck: &self.deck,
+                cards: &cards,
+            })
+            .map_err(|e| Error::invalid(format!("failed to write TOML: {e}")))?,
+        )?;
+
+        self.path
+            .to_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anki_multitool_ds::card::Fields;
+    use std::{io::Write, sync::Mutex};
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn toml_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("failed to create temp file");
+        file.write_all(content.as_bytes())
+            .expect("failed to write to file");
+        file
+    }
+
+    async fn collect(deck: &FromTomlDeck) -> Vec<Card> {
+        let cards = Mutex::new(Vec::new());
+
+        deck.for_each(async |card| {
+            cards.lock().expect("failed to lock cards").push(card);
+            Ok(())
+        })
+        .await
+        .expect("failed to read cards");
+
+        cards.into_inner().expect("failed to unlock cards")
+    }
+
+    #[tokio::test]
+    pub async fn test_from_toml_deck() {
+        let file = toml_file(
+            r#"
+deck = "Ukrainian Words"
+model = "Vocabulary"
+tags = ["ukrainian"]
+
+[[cards]]
+tags = ["body"]
+fields = { Word = "серце", Meaning = """
+heart,
+also a core of something""" }
+
+[[cards]]
+front = "What is the capital of Ukraine?"
+back = "Kyiv"
+model = "Basic"
+"#,
+        );
+
+        let deck = FromTomlDeck::new(file.path()).expect("failed to read deck");
+
+        assert_eq!(deck.deck(), Some("Ukrainian Words"));
+        assert_eq!(
+            collect(&deck).await,
+            vec![
+                Card::new(
+                    "Vocabulary",
+                    Fields::from([
+                        ("Word".to_string(), "серце".to_string()),
+                        (
+                            "Meaning".to_string(),
+                            "heart,\nalso a core of something".to_string()
+                        ),
+                    ])
+                )
+                .with_tags(vec!["body".to_string(), "ukrainian".to_string()]),
+                Card::basic("What is the capital of Ukraine?", "Kyiv")
+                    .with_tags(vec!["ukrainian".to_string()]),
+            ]
+        );
+
+        assert!(FromTomlDeck::new(toml_file("[[cards]]\nfront = \"Q1\"\n").path()).is_err());
+
+        let error = FromTomlDeck::new(toml_file("deck = \"Words\"\ncards = [").path())
+            .err()
+            .expect("unclosed array should fail");
+        assert!(matches!(
+            error,
+            Error::Parse {
+                line: Some(2),
+                column: Some(10),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn test_to_toml_deck() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let deck = dir.path().join("deck");
+        let deck = deck.to_str().expect("failed to convert path");
+        let cards = vec![
+            Card::basic("Q1", "multi\nline").with_tags(vec!["a".to_string()]),
+            Card::new(
+                "Vocabulary",
+                Fields::from([
+                    ("Word".to_string(), "серце".to_string()),
+                    ("Meaning".to_string(), "heart".to_string()),
+                ]),
+            )
+            .with_id(Some(7)),
+        ];
+
+        let path = ToTomlDeck::new(deck)
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write deck");
+        let written = FromTomlDeck::new(&path).expect("failed to read deck");
+
+        assert_eq!(written.deck(), Some(deck));
+        assert_eq!(collect(&written).await, cards);
+        assert!(
+            ToTomlDeck::new(deck)
+                .write(cards.into_iter())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+};
+
+use crate::document::{DeckDocument, DeckDocumentRef};
+
+/// YAML deck is parsed as a whole, so invalid card is reported before any card is imported.
+pub struct FromYamlDeck {
+    document: DeckDocument,
+}
+
+impl FromYamlDeck {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Err(Error::FileNotFound(path.as_ref().to_path_buf()));
+        }
+
+        let reader = BufReader::new(File::open(&path)?);
+        let document = DeckDocument::from_deserializer(
+            path.as_ref(),
+            serde_norway::Deserializer::from_reader(reader),
+            |e| {
+                e.location()
+                    .map(|location| (location.line(), location.column()))
+            },
+        )?;
+
+        Ok(Self { document })
+    }
+
+    /// Name of deck given in document.
+    pub fn deck(&self) -> Option<&str> {
+        self.document.deck.as_deref()
+    }
+
+    pub async fn for_each<A>(&self, action: A) -> Result<()>
+    where
+        A: AsyncFn(Card) -> Result<()>,
+    {
+        for card in self.document.cards.iter().cloned() {
+            action(card).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ToYamlDeck {
+    deck: String,
+    path: PathBuf,
+}
+
+impl ToYamlDeck {
+    pub fn new(deck: &str) -> Self {
+        Self {
+            deck: deck.to_string(),
+            path: PathBuf::from(format!("{deck}.yaml")),
+        }
+    }
+
+    /// Writes file into given directory instead of current one.
+    pub fn in_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.path = dir.as_ref().join(&self.path);
+        self
+    }
+
+    pub async fn write(&self, cards: impl Iterator<Item = Card>) -> Result<String> {
+        if self.path.exists() {
+            return Err(Error::FileExists(self.path.clone()));
+        }
+
+        let cards: Vec<Card> = cards.collect();
+
+        serde_norway::to_writer(
+            BufWriter::new(File::create(&self.path)?),
+            &DeckDocumentRef {
+                deck: &self.deck,
+                cards: &cards,
+            },
+        )
+        .map_err(|e| Error::invalid(format!("failed to write YAML: {e}")))?;
+
+        self.path
+            .to_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| Error::invalid("failed to convert path to string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anki_multitool_ds::card::Fields;
+    use std::{fs::read_to_string, io::Write, sync::Mutex};
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn yaml_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("failed to create temp file");
+        file.write_all(content.as_bytes())
+            .expect("failed to write to file");
+        file
+    }
+
+    async fn collect(deck: &FromYamlDeck) -> Vec<Card> {
+        let cards = Mutex::new(Vec::new());
+
+        deck.for_each(async |card| {
+            cards.lock().expect("failed to lock cards").push(card);
+            Ok(())
+        })
+        .await
+        .expect("failed to read cards");
+
+        cards.into_inner().expect("failed to unlock cards")
+    }
+
+    #[tokio::test]
+    pub async fn test_from_yaml_deck() {
+        let file = yaml_file(
+            r#"
+deck: Ukrainian Words
+model: Vocabulary
+tags: [ukrainian]
+cards:
+  - fields:
+      Word: серце
+      Meaning: |
+        heart,
+        also a core of something
+    tags: [body]
+  - front: What is the capital of Ukraine?
+    back: Kyiv
+    model: Basic
+  - cloze: "{{c1::Kyiv}} is the capital"
+"#,
+        );
+
+        let deck = FromYamlDeck::new(file.path()).expect("failed to read deck");
+
+        assert_eq!(deck.deck(), Some("Ukrainian Words"));
+        assert_eq!(
+            collect(&deck).await,
+            vec![
+                Card::new(
+                    "Vocabulary",
+                    Fields::from([
+                        ("Word".to_string(), "серце".to_string()),
+                        (
+                            "Meaning".to_string(),
+                            "heart,\nalso a core of something\n".to_string()
+                        ),
+                    ])
+                )
+                .with_tags(vec!["body".to_string(), "ukrainian".to_string()]),
+                Card::basic("What is the capital of Ukraine?", "Kyiv")
+                    .with_tags(vec!["ukrainian".to_string()]),
+                Card::cloze("{{c1::Kyiv}} is the capital", "")
+                    .with_tags(vec!["ukrainian".to_string()]),
+            ]
+        );
+
+        let file = yaml_file("- front: Q1\n  back: A1\n");
+        let deck = FromYamlDeck::new(file.path()).expect("failed to read deck");
+
+        assert_eq!(deck.deck(), None);
+        assert_eq!(collect(&deck).await, vec![Card::basic("Q1", "A1")]);
+    }
+
+    #[tokio::test]
+    pub async fn test_failed_from_yaml_deck() {
+        let error = FromYamlDeck::new(yaml_file("cards:\n  - front: Q1\n").path())
+            .err()
+            .expect("card without back should fail");
+        assert!(
+            matches!(error, Error::Parse { message, .. } if message.starts_with("invalid card #1"))
+        );
+
+        let error = FromYamlDeck::new(yaml_file("deck: Words\ncards: [\n").path())
+            .err()
+            .expect("unclosed list should fail");
+        assert!(matches!(
+            error,
+            Error::Parse {
+                line: Some(3),
+                column: Some(1),
+                ..
+            }
+        ));
+
+        assert!(FromYamlDeck::new(yaml_file("tags: [two words]\n").path()).is_err());
+        assert!(FromYamlDeck::new(yaml_file("unknown: key\n").path()).is_err());
+        assert!(matches!(
+            FromYamlDeck::new("missing.yaml"),
+            Err(Error::FileNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn test_to_yaml_deck() {
+        let dir = TempDir::new().expect("failed to create temp dir");
+        let deck = dir.path().join("deck");
+        let deck = deck.to_str().expect("failed to convert path");
+        let cards = vec![
+            Card::basic("Q1", "multi\nline").with_tags(vec!["a".to_string()]),
+            Card::cloze("{{c1::Kyiv}}", "").with_id(Some(7)),
+        ];
+
+        let path = ToYamlDeck::new(deck)
+            .write(cards.clone().into_iter())
+            .await
+            .expect("failed to write deck");
+
+        assert!(
+            read_to_string(&path)
+                .expect("failed to read file")
+                .starts_with(&format!("deck: {deck}\ncards:\n"))
+        );
+
+        let written = FromYamlDeck::new(&path).expect("failed to read deck");
+
+        assert_eq!(written.deck(), Some(deck));
+        assert_eq!(collect(&written).await, cards);
+        assert!(
+            ToYamlDeck::new(deck)
+                .write(cards.into_iter())
+                .await
+                .is_err()
+        );
+    }
+}
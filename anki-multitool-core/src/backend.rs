@@ -0,0 +1,364 @@
+use std::path::PathBuf;
+
+use anki_multitool_convert::collection::Collection;
+use anki_multitool_ds::{
+    card::{Card, Fields},
+    error::{Error, Result},
+    http::request::{ApiRequest, DuplicateScope, Note},
+};
+use anki_multitool_request::client::AnkiClient;
+
+/// Storage of decks used by `ToolController`. Batch operations return result of every note
+/// separately, so single broken note doesn't fail the whole batch.
+pub trait Backend: Sync {
+    fn deck_names(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    fn deck_exists(&self, deck: &str) -> impl Future<Output = Result<bool>> + Send {
+        async move { Ok(self.deck_names().await?.iter().any(|d| d == deck)) }
+    }
+
+    /// Creates deck and returns its id, existing deck is an error.
+    fn create_deck(&self, deck: &str) -> impl Future<Output = Result<u64>> + Send;
+
+    fn delete_decks(&self, decks: Vec<String>) -> impl Future<Output = Result<()>> + Send;
+
+    /// Cards of notes in deck and its subdecks, with ids of notes.
+    fn cards(&self, deck: &str) -> impl Future<Output = Result<Vec<Card>>> + Send;
+
+    /// Adds notes to deck and returns their ids.
+    fn add_notes(
+        &self,
+        deck: &str,
+        cards: Vec<Card>,
+        duplicate_scope: DuplicateScope,
+    ) -> impl Future<Output = Result<Vec<Result<u64>>>> + Send;
+
+    /// Updates given fields of notes, other fields are left untouched.
+    fn update_notes(
+        &self,
+        notes: Vec<(u64, Fields)>,
+    ) -> impl Future<Output = Result<Vec<Result<()>>>> + Send;
+
+    /// Replaces tags of notes.
+    fn update_tags(
+        &self,
+        notes: Vec<(u64, Vec<String>)>,
+    ) -> impl Future<Output = Result<Vec<Result<()>>>> + Send;
+
+    fn delete_notes(&self, ids: Vec<u64>) -> impl Future<Output = Result<()>> + Send;
+
+    /// Stores file in media folder of collection and returns name it's stored with.
+    fn store_media(&self, name: &str, data: Vec<u8>)
+    -> impl Future<Output = Result<String>> + Send;
+
+    /// Content of file in media folder of collection, `None` if there is no such file.
+    fn retrieve_media(&self, name: &str) -> impl Future<Output = Result<Option<Vec<u8>>>> + Send;
+
+    fn delete_media(&self, name: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Checks that response of `multi` request has result for every action.
+fn check_results<T>(results: Vec<T>, expected: usize) -> Result<Vec<T>> {
+    if results.len() != expected {
+        return Err(Error::Response(format!(
+            "expected {expected} results in response, got {}",
+            results.len()
+        )));
+    }
+
+    Ok(results)
+}
+
+fn no_result() -> Error {
+    Error::Response("no result in response".to_string())
+}
+
+impl Backend for AnkiClient {
+    async fn deck_names(&self) -> Result<Vec<String>> {
+        self.deck_names_req()
+            .await?
+            .into_result()?
+            .into_names_res()
+            .ok_or_else(no_result)
+    }
+
+    async fn create_deck(&self, deck: &str) -> Result<u64> {
+        self.create_deck_req(deck)
+            .await?
+            .into_result()?
+            .into_id_res()
+            .ok_or_else(no_result)
+    }
+
+    async fn delete_decks(&self, decks: Vec<String>) -> Result<()> {
+        self.delete_decks_req(decks).await?.into_empty_result()
+    }
+
+    async fn cards(&self, deck: &str) -> Result<Vec<Card>> {
+        Ok(self
+            .notes_info_req(deck)
+            .await?
+            .into_result()?
+            .into_notes_info_res()
+            .ok_or_else(no_result)?
+            .into_iter()
+            .map(Card::from)
+            .collect())
+    }
+
+    async fn add_notes(
+        &self,
+        deck: &str,
+        cards: Vec<Card>,
+        duplicate_scope: DuplicateScope,
+    ) -> Result<Vec<Result<u64>>> {
+        let count = cards.len();
+        let actions = cards
+            .into_iter()
+            .map(|card| {
+                ApiRequest::make_add_note_req(
+                    Note::from_card(deck.to_string(), card).with_dup_scope(duplicate_scope),
+                )
+            })
+            .collect();
+        let responses = self
+            .multi_req(actions)
+            .await?
+            .into_result()?
+            .into_multi_res()
+            .ok_or_else(no_result)?;
+
+        Ok(check_results(responses, count)?
+            .into_iter()
+            .map(|response| {
+                response
+                    .into_result()?
+                    .into_id_res()
+                    .ok_or_else(|| Error::Response("no id of note in response".to_string()))
+            })
+            .collect())
+    }
+
+    async fn update_notes(&self, notes: Vec<(u64, Fields)>) -> Result<Vec<Result<()>>> {
+        let count = notes.len();
+        let actions = notes
+            .into_iter()
+            .map(|(id, fields)| ApiRequest::make_update_note_fields_req(id, fields))
+            .collect();
+        let responses = self
+            .multi_req(actions)
+            .await?
+            .into_result()?
+            .into_multi_res()
+            .ok_or_else(no_result)?;
+
+        Ok(check_results(responses, count)?
+            .into_iter()
+            .map(|response| response.into_empty_result())
+            .collect())
+    }
+
+    async fn update_tags(&self, notes: Vec<(u64, Vec<String>)>) -> Result<Vec<Result<()>>> {
+        let count = notes.len();
+        let actions = notes
+            .into_iter()
+            .map(|(id, tags)| ApiRequest::make_update_note_tags_req(id, tags))
+            .collect();
+        let responses = self
+            .multi_req(actions)
+            .await?
+            .into_result()?
+            .into_multi_res()
+            .ok_or_else(no_result)?;
+
+        Ok(check_results(responses, count)?
+            .into_iter()
+            .map(|response| response.into_empty_result())
+            .collect())
+    }
+
+    async fn delete_notes(&self, ids: Vec<u64>) -> Result<()> {
+        self.delete_notes_req(ids).await?.into_empty_result()
+    }
+
+    async fn store_media(&self, name: &str, data: Vec<u8>) -> Result<String> {
+        self.store_media_file_req(name, &data)
+            .await?
+            .into_result()?
+            .into_text_res()
+            .ok_or_else(no_result)
+    }
+
+    async fn retrieve_media(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.retrieve_media_file_req(name)
+            .await?
+            .into_result()?
+            .into_media_res()
+            .ok_or_else(|| Error::Response(format!("invalid content of media file '{name}'")))
+    }
+
+    async fn delete_media(&self, name: &str) -> Result<()> {
+        self.delete_media_file_req(name).await?.into_empty_result()
+    }
+}
+
+/// `collection.anki2` of Anki profile, it's opened read-only, so Anki has to be closed and
+/// decks can't be changed.
+pub struct LocalCollection {
+    path: PathBuf,
+}
+
+impl LocalCollection {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn read_only<T>(&self) -> Result<T> {
+        Err(Error::Collection(format!(
+            "collection {} is opened read-only, changes of decks require AnkiConnect",
+            self.path.display()
+        )))
+    }
+}
+
+impl Backend for LocalCollection {
+    async fn deck_names(&self) -> Result<Vec<String>> {
+        Collection::open(&self.path)?.deck_names()
+    }
+
+    async fn create_deck(&self, _deck: &str) -> Result<u64> {
+        self.read_only()
+    }
+
+    async fn delete_decks(&self, _decks: Vec<String>) -> Result<()> {
+        self.read_only()
+    }
+
+    async fn cards(&self, deck: &str) -> Result<Vec<Card>> {
+        let collection = Collection::open(&self.path)?;
+
+        if !collection.deck_names()?.iter().any(|d| d == deck) {
+            return Err(Error::DeckNotFound(deck.to_string()));
+        }
+
+        collection.cards(Some(deck))
+    }
+
+    async fn add_notes(
+        &self,
+        _deck: &str,
+        _cards: Vec<Card>,
+        _duplicate_scope: DuplicateScope,
+    ) -> Result<Vec<Result<u64>>> {
+        self.read_only()
+    }
+
+    async fn update_notes(&self, _notes: Vec<(u64, Fields)>) -> Result<Vec<Result<()>>> {
+        self.read_only()
+    }
+
+    async fn update_tags(&self, _notes: Vec<(u64, Vec<String>)>) -> Result<Vec<Result<()>>> {
+        self.read_only()
+    }
+
+    async fn delete_notes(&self, _ids: Vec<u64>) -> Result<()> {
+        self.read_only()
+    }
+
+    async fn store_media(&self, _name: &str, _data: Vec<u8>) -> Result<String> {
+        self.read_only()
+    }
+
+    /// Media of profile is kept in `collection.media` folder next to collection file.
+    async fn retrieve_media(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path.with_extension("media").join(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_media(&self, _name: &str) -> Result<()> {
+        self.read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::Builder;
+
+    use crate::{
+        CsvOptions, ImportOptions, MarkdownOptions, SyncOptions, ToolController,
+        memory::MemoryBackend,
+    };
+
+    #[tokio::test]
+    async fn test_controller_with_memory_backend() {
+        let mut file = Builder::new()
+            .suffix(".md")
+            .tempfile()
+            .expect("failed to create temp file");
+        file.write_all(b"1. Q1 - A1\n2. Q2 - A2\n")
+            .expect("failed to write to file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let report = controller
+            .convert_md_to_deck(
+                file.path(),
+                &MarkdownOptions::default(),
+                &ImportOptions {
+                    deck: Some("Words".to_string()),
+                    ..ImportOptions::default()
+                },
+            )
+            .await
+            .expect("failed to import deck");
+
+        assert_eq!(report.added, 2);
+        assert_eq!(
+            controller.deck_list().await.expect("failed to get decks"),
+            vec!["Words"]
+        );
+
+        std::fs::write(file.path(), "1. Q1 - A1 changed\n").expect("failed to write to file");
+
+        let report = controller
+            .sync_deck(
+                file.path(),
+                &MarkdownOptions::default(),
+                &CsvOptions::default(),
+                &SyncOptions {
+                    deck: Some("Words".to_string()),
+                    delete: true,
+                    dry_run: false,
+                },
+            )
+            .await
+            .expect("failed to sync deck");
+
+        assert_eq!(report.changes.len(), 2);
+        assert!(report.failures.is_empty());
+        assert_eq!(
+            controller
+                .backend
+                .cards("Words")
+                .await
+                .expect("failed to get cards"),
+            vec![Card::basic("Q1", "A1 changed").with_id(Some(1))]
+        );
+        assert!(
+            controller
+                .diff_deck(
+                    "Words",
+                    file.path(),
+                    &MarkdownOptions::default(),
+                    &CsvOptions::default()
+                )
+                .await
+                .expect("failed to diff deck")
+                .is_empty()
+        );
+    }
+}
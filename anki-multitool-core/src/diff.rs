@@ -0,0 +1,176 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use anki_multitool_ds::card::Card;
+
+use crate::sync::{self, SyncChange};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    /// Value in deck.
+    pub old: String,
+    /// Value in file.
+    pub new: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagsChange {
+    /// Tags in deck.
+    pub old: Vec<String>,
+    /// Tags in file.
+    pub new: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModifiedCard {
+    pub id: u64,
+    pub front: String,
+    pub fields: IndexMap<String, FieldChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<TagsChange>,
+}
+
+/// Difference between deck and file: `added` cards are only in file, `removed` ones are only
+/// in deck.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeckDiff {
+    pub deck: String,
+    pub added: Vec<Card>,
+    pub removed: Vec<Card>,
+    pub modified: Vec<ModifiedCard>,
+    pub unchanged: usize,
+}
+
+impl DeckDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Cards are matched the same way as during sync, so diff shows exactly what sync with
+/// deletion would do.
+pub(crate) fn diff(deck: String, notes: Vec<Card>, cards: Vec<Card>) -> DeckDiff {
+    let (changes, unchanged) = sync::plan(notes.clone(), cards, true);
+    let mut notes: HashMap<u64, Card> = notes
+        .into_iter()
+        .filter_map(|note| note.id.map(|id| (id, note)))
+        .collect();
+    let mut diff = DeckDiff {
+        deck,
+        unchanged,
+        ..DeckDiff::default()
+    };
+
+    for change in changes {
+        match change {
+            SyncChange::Add(card) => diff.added.push(card),
+            SyncChange::Update {
+                id,
+                front,
+                fields,
+                tags,
+            } => {
+                let old = notes.get(&id);
+
+                diff.modified.push(ModifiedCard {
+                    id,
+                    front,
+                    fields: fields
+                        .into_iter()
+                        .map(|(name, new)| {
+                            let old = old
+                                .and_then(|note| note.fields.get(&name))
+                                .cloned()
+                                .unwrap_or_default();
+                            (name, FieldChange { old, new })
+                        })
+                        .collect(),
+                    tags: tags.map(|new| TagsChange {
+                        old: old.map(|note| note.tags.clone()).unwrap_or_default(),
+                        new,
+                    }),
+                });
+            }
+            SyncChange::Delete { id, .. } => diff.removed.extend(notes.remove(&id)),
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let notes = vec![
+            Card::basic("Q1", "A1").with_id(Some(1)),
+            Card::basic("Q2", "A2").with_id(Some(2)),
+            Card::basic("Q3", "A3").with_id(Some(3)),
+        ];
+        let cards = vec![
+            Card::basic("Q1", "A1"),
+            Card::basic("Q2", "A2 changed"),
+            Card::basic("Q4", "A4"),
+        ];
+
+        let diff = diff("Deck".to_string(), notes, cards);
+
+        assert!(!diff.is_empty());
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added, vec![Card::basic("Q4", "A4")]);
+        assert_eq!(diff.removed, vec![Card::basic("Q3", "A3").with_id(Some(3))]);
+        assert_eq!(
+            diff.modified,
+            vec![ModifiedCard {
+                id: 2,
+                front: "Q2".to_string(),
+                fields: IndexMap::from([(
+                    "Back".to_string(),
+                    FieldChange {
+                        old: "A2".to_string(),
+                        new: "A2 changed".to_string()
+                    }
+                )]),
+                tags: None,
+            }]
+        );
+
+        let diff = super::diff(
+            "Deck".to_string(),
+            vec![Card::basic("Q1", "A1").with_id(Some(1))],
+            vec![Card::basic("Q1", "A1").with_id(Some(1))],
+        );
+
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_diff_tags() {
+        let diff = diff(
+            "Deck".to_string(),
+            vec![
+                Card::basic("Q", "A")
+                    .with_tags(vec!["old".to_string()])
+                    .with_id(Some(1)),
+            ],
+            vec![Card::basic("Q", "A").with_tags(vec!["new".to_string()])],
+        );
+
+        assert_eq!(diff.unchanged, 0);
+        assert_eq!(
+            diff.modified,
+            vec![ModifiedCard {
+                id: 1,
+                front: "Q".to_string(),
+                fields: IndexMap::new(),
+                tags: Some(TagsChange {
+                    old: vec!["old".to_string()],
+                    new: vec!["new".to_string()],
+                }),
+            }]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anki_multitool_ds::{card::Card, error::Result};
+
+use crate::{Backend, media};
+
+/// Folder next to exported file, where media files referenced by cards are saved.
+pub const MEDIA_DIR: &str = "media";
+
+/// Settings applied to every card during export of deck.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Write ids of notes, so file can be synced back to the same notes later.
+    pub with_ids: bool,
+    /// Directory where file is written, empty path is current directory.
+    pub dir: PathBuf,
+}
+
+impl ExportOptions {
+    pub(crate) fn apply(&self, card: Card) -> Card {
+        if self.with_ids {
+            card
+        } else {
+            card.with_id(None)
+        }
+    }
+}
+
+/// Cards of deck along with subdeck they belong to, name of subdeck is relative to deck. Cards
+/// of deck itself go first, then subdecks in order of names.
+pub(crate) async fn cards_by_subdeck<B: Backend>(
+    backend: &B,
+    deck: &str,
+) -> Result<Vec<(Option<String>, Card)>> {
+    let prefix = format!("{deck}::");
+    let mut subdecks: Vec<String> = backend
+        .deck_names()
+        .await?
+        .into_iter()
+        .filter(|name| name.starts_with(&prefix))
+        .collect();
+
+    // cards of subdeck are listed for its parents too, so the deepest subdeck claims them
+    subdecks.sort_by_key(|name| std::cmp::Reverse(name.matches("::").count()));
+
+    let mut seen = HashSet::new();
+    let mut groups = Vec::new();
+
+    for name in subdecks.iter().map(String::as_str).chain([deck]) {
+        let cards: Vec<Card> = backend
+            .cards(name)
+            .await?
+            .into_iter()
+            .filter(|card| card.id.is_none_or(|id| seen.insert(id)))
+            .collect();
+
+        groups.push((
+            (name != deck).then(|| name[prefix.len()..].to_string()),
+            cards,
+        ));
+    }
+
+    groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(groups
+        .into_iter()
+        .flat_map(|(subdeck, cards)| cards.into_iter().map(move |card| (subdeck.clone(), card)))
+        .collect())
+}
+
+/// Downloads media files referenced by cards and points references to `media` folder, where
+/// they are saved by `save_media` after file is written. Files missing in collection and
+/// references which aren't plain names of files are left as is.
+pub(crate) async fn download_media<'c, B: Backend>(
+    backend: &B,
+    cards: impl IntoIterator<Item = &'c mut Card>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut cards: Vec<&mut Card> = cards.into_iter().collect();
+    let mut files = Vec::new();
+    let mut names = HashMap::new();
+
+    for card in &cards {
+        for reference in card
+            .fields
+            .values()
+            .flat_map(|value| media::references(value))
+        {
+            if names.contains_key(reference) || reference.contains(['/', '\\']) {
+                continue;
+            }
+
+            if let Some(data) = backend.retrieve_media(reference).await? {
+                names.insert(reference.to_string(), format!("{MEDIA_DIR}/{reference}"));
+                files.push((reference.to_string(), data));
+            }
+        }
+    }
+
+    if !names.is_empty() {
+        for card in &mut cards {
+            for value in card.fields.values_mut() {
+                *value = media::rewrite(value, &names);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Writes downloaded media files to `media` folder in `dir`, existing files are replaced.
+pub(crate) fn save_media(dir: &Path, files: Vec<(String, Vec<u8>)>) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let media_dir = dir.join(MEDIA_DIR);
+
+    std::fs::create_dir_all(&media_dir)?;
+
+    for (name, data) in files {
+        std::fs::write(media_dir.join(name), data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    use crate::{MarkdownOptions, MarkdownStyle, ToolController, memory::MemoryBackend};
+    use anki_multitool_ds::http::request::DuplicateScope;
+
+    async fn backend_with(deck: &str, cards: Vec<Card>) -> MemoryBackend {
+        let backend = MemoryBackend::default();
+
+        backend
+            .create_deck(deck)
+            .await
+            .expect("failed to create deck");
+        backend
+            .add_notes(deck, cards, DuplicateScope::default())
+            .await
+            .expect("failed to add notes");
+        backend
+    }
+
+    #[tokio::test]
+    async fn test_export_markdown_subdecks() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let backend = backend_with("Lecture", vec![Card::basic("Q - ?", "A")]).await;
+
+        for (subdeck, card) in [
+            ("Lecture::Verbs", Card::basic("бігти", "to run")),
+            ("Lecture::Nouns", Card::basic("кіт", "cat")),
+        ] {
+            backend
+                .create_deck(subdeck)
+                .await
+                .expect("failed to create deck");
+            backend
+                .add_notes(subdeck, vec![card], DuplicateScope::default())
+                .await
+                .expect("failed to add notes");
+        }
+
+        let exported = ToolController::with_backend(backend)
+            .convert_deck_to_md(
+                "Lecture",
+                &MarkdownOptions {
+                    style: MarkdownStyle::Headings,
+                    subdecks: true,
+                    ..MarkdownOptions::default()
+                },
+                &ExportOptions {
+                    dir: dir.path().to_path_buf(),
+                    ..ExportOptions::default()
+                },
+            )
+            .await
+            .expect("failed to export deck");
+
+        assert_eq!(
+            std::fs::read_to_string(exported).expect("failed to read file"),
+            "## Q - ?\n\nA\n\n# Nouns\n\n## кіт\n\ncat\n\n# Verbs\n\n## бігти\n\nto run\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_into_missing_dir() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let controller = ToolController::with_backend(
+            backend_with("Lecture", vec![Card::basic("Q", "A")]).await,
+        );
+
+        assert!(
+            controller
+                .convert_deck_to_md(
+                    "Lecture",
+                    &MarkdownOptions::default(),
+                    &ExportOptions {
+                        dir: dir.path().join("export"),
+                        ..ExportOptions::default()
+                    },
+                )
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_media() {
+        let backend = backend_with(
+            "Sounds",
+            vec![
+                Card::basic("кіт", "cat [sound:cat.mp3]"),
+                Card::basic("кошеня", "kitten [sound:cat.mp3]"),
+            ],
+        )
+        .await;
+
+        backend
+            .store_media("cat.mp3", b"meow".to_vec())
+            .await
+            .expect("failed to store media");
+
+        let out = tempdir().expect("failed to create temp directory");
+        let file = ToolController::with_backend(backend)
+            .convert_deck_to_json(
+                "Sounds",
+                &ExportOptions {
+                    dir: out.path().to_path_buf(),
+                    ..ExportOptions::default()
+                },
+            )
+            .await
+            .expect("failed to export deck");
+
+        assert!(
+            std::fs::read_to_string(file)
+                .expect("failed to read file")
+                .contains("cat [sound:media/cat.mp3]")
+        );
+        assert_eq!(
+            std::fs::read(out.path().join("media").join("cat.mp3")).expect("failed to read audio"),
+            b"meow"
+        );
+    }
+}
@@ -0,0 +1,618 @@
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+    http::request::DuplicateScope,
+};
+
+use crate::{
+    Backend,
+    media::{self, LocalMedia, StoredMedia},
+};
+
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Settings applied to every card during import of deck.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Tags added to every imported card in addition to tags from file.
+    pub tags: Vec<String>,
+    /// Number of notes sent to Anki with single request.
+    pub batch_size: usize,
+    /// Stop at the first card which can't be added and remove everything added by import.
+    pub atomic: bool,
+    /// Deck to import into, name of file is used if not set.
+    pub deck: Option<String>,
+    /// Add cards to existing deck instead of creating new one, duplicates are skipped.
+    pub append: bool,
+    /// Where Anki looks for duplicates of imported cards.
+    pub duplicate_scope: DuplicateScope,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            atomic: false,
+            deck: None,
+            append: false,
+            duplicate_scope: DuplicateScope::default(),
+        }
+    }
+}
+
+impl ImportOptions {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.batch_size == 0 {
+            return Err(Error::invalid("batch size must be greater than 0"));
+        }
+
+        if self
+            .deck
+            .as_ref()
+            .is_some_and(|deck| deck.trim().is_empty())
+        {
+            return Err(Error::invalid("deck name can't be empty"));
+        }
+
+        match self
+            .tags
+            .iter()
+            .find(|t| t.is_empty() || t.contains(char::is_whitespace))
+        {
+            Some(tag) => Err(Error::invalid(format!(
+                "invalid tag '{tag}', tags can't be empty or contain spaces"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Card which Anki refused to add.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoteFailure {
+    /// Position of card in file, starting from 1.
+    pub position: usize,
+    pub front: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub deck: String,
+    /// Whether deck was created by import or cards were appended to existing one.
+    pub new_deck: bool,
+    pub added: usize,
+    /// Ids of added notes, in order of cards in file.
+    pub note_ids: Vec<u64>,
+    /// Duplicates which were skipped in append mode.
+    pub skipped: usize,
+    /// Media files uploaded to collection.
+    pub media: usize,
+    pub failures: Vec<NoteFailure>,
+}
+
+/// Card waiting to be sent: its position in file, subdeck it goes to (`None` is deck of import
+/// itself) and the card.
+type PendingCard = (usize, Option<String>, Card);
+
+/// Collects cards and sends them to backend in chunks, result of each note in chunk is known.
+/// Ids of added notes are kept in report to be able to roll import back.
+pub(crate) struct NotesBatch<'a, B: Backend> {
+    backend: &'a B,
+    options: &'a ImportOptions,
+    pushed: AtomicUsize,
+    pending: Mutex<Vec<PendingCard>>,
+    /// Subdecks used by import and whether they were created by it.
+    subdecks: Mutex<Vec<(String, bool)>>,
+    /// Directory which paths of media files are relative to.
+    media_dir: PathBuf,
+    /// Local media files referenced by pushed cards.
+    media: Mutex<LocalMedia>,
+    /// Names of media files stored in collection.
+    stored: Mutex<HashSet<String>>,
+    /// Media files stored by import, they are kept only in atomic mode to be able to roll
+    /// import back.
+    stored_media: Mutex<Vec<StoredMedia>>,
+    report: Mutex<ImportReport>,
+}
+
+impl<'a, B: Backend> NotesBatch<'a, B> {
+    pub(crate) fn new(
+        backend: &'a B,
+        deck: &str,
+        new_deck: bool,
+        media_dir: &Path,
+        options: &'a ImportOptions,
+    ) -> Self {
+        Self {
+            backend,
+            options,
+            pushed: AtomicUsize::new(0),
+            pending: Mutex::new(Vec::with_capacity(options.batch_size)),
+            subdecks: Mutex::new(Vec::new()),
+            media_dir: media_dir.to_path_buf(),
+            media: Mutex::new(LocalMedia::new()),
+            stored: Mutex::new(HashSet::new()),
+            stored_media: Mutex::new(Vec::new()),
+            report: Mutex::new(ImportReport {
+                deck: deck.to_string(),
+                new_deck,
+                ..ImportReport::default()
+            }),
+        }
+    }
+
+    pub(crate) async fn push(&self, card: Card) -> Result<()> {
+        self.push_into(None, card).await
+    }
+
+    /// Adds card to subdeck (`Deck::Subdeck`) of deck, subdeck is created if it doesn't exist.
+    /// Local media files referenced by card are stored in collection once its note is added.
+    pub(crate) async fn push_into(&self, subdeck: Option<String>, mut card: Card) -> Result<()> {
+        card.add_tags(&self.options.tags);
+        media::localize(&self.media_dir, &mut card, &mut *self.lock_media()?)?;
+
+        let deck = match subdeck {
+            Some(subdeck) => Some(self.prepare_subdeck(&subdeck).await?),
+            None => None,
+        };
+        let position = self.pushed.fetch_add(1, Ordering::Relaxed) + 1;
+        let is_full = {
+            let mut pending = self.lock_pending()?;
+
+            pending.push((position, deck, card));
+            pending.len() >= self.options.batch_size
+        };
+
+        if is_full { self.flush().await } else { Ok(()) }
+    }
+
+    pub(crate) async fn flush(&self) -> Result<()> {
+        let mut chunk = std::mem::take(&mut *self.lock_pending()?);
+        let deck = self.lock_report()?.deck.clone();
+
+        // cards of the same subdeck go one after another, so chunk is sent in runs of one deck
+        while let Some((_, subdeck, _)) = chunk.first() {
+            let run_deck = subdeck.clone().unwrap_or_else(|| deck.clone());
+            let len = chunk
+                .iter()
+                .take_while(|(_, other, _)| other == subdeck)
+                .count();
+
+            self.send(&run_deck, chunk.drain(..len).collect()).await?;
+
+            if let Some(failure) = self.lock_report()?.failures.first()
+                && self.options.atomic
+            {
+                return Err(Error::invalid(format!(
+                    "failed to add card #{} '{}': {}",
+                    failure.position, failure.front, failure.error
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns report of import, in atomic mode failed import is rolled back: added notes and
+    /// decks, which were created by import, are removed from collection, stored media files
+    /// are deleted or get their previous content back.
+    pub(crate) async fn finish(self, result: Result<()>) -> Result<ImportReport> {
+        match result {
+            Ok(()) => self
+                .report
+                .into_inner()
+                .map_err(|_| Error::invalid("failed to unlock report")),
+            Err(e) if self.options.atomic => match self.rollback().await {
+                Ok(()) => Err(Error::RolledBack(Box::new(e))),
+                Err(rollback) => Err(Error::RollbackFailed {
+                    error: Box::new(e),
+                    rollback: Box::new(rollback),
+                }),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn send(&self, deck: &str, chunk: Vec<PendingCard>) -> Result<()> {
+        let (positions, cards): (Vec<_>, Vec<_>) = chunk
+            .into_iter()
+            .map(|(position, _, card)| ((position, card.front().to_string()), card))
+            .unzip();
+        let references: Vec<Vec<String>> = cards
+            .iter()
+            .map(|card| media::card_references(card).map(str::to_string).collect())
+            .collect();
+        let results = self
+            .backend
+            .add_notes(deck, cards, self.options.duplicate_scope)
+            .await?;
+        let mut added_references = Vec::new();
+
+        {
+            let mut report = self.lock_report()?;
+
+            for (((position, front), result), references) in
+                positions.into_iter().zip(results).zip(references)
+            {
+                match result {
+                    Ok(id) => {
+                        report.note_ids.push(id);
+                        report.added += 1;
+                        added_references.extend(references);
+                    }
+                    Err(Error::DuplicateNote) if self.options.append => report.skipped += 1,
+                    Err(e) => report.failures.push(NoteFailure {
+                        position,
+                        front,
+                        error: e.to_string(),
+                    }),
+                }
+            }
+        }
+
+        self.store_media(added_references).await
+    }
+
+    /// Stores local media files referenced by added notes, every file is stored once, no
+    /// matter how many notes refer to it.
+    async fn store_media(&self, references: Vec<String>) -> Result<()> {
+        let files: Vec<(String, PathBuf)> = {
+            let media = self.lock_media()?;
+            let mut stored = self.lock_stored()?;
+
+            references
+                .into_iter()
+                .filter_map(|name| {
+                    let path = media.get(&name)?.clone();
+                    stored.insert(name.clone()).then_some((name, path))
+                })
+                .collect()
+        };
+
+        for (name, path) in files {
+            let stored = media::store(self.backend, &name, &path, self.options.atomic).await?;
+
+            if self.options.atomic {
+                self.lock_stored_media()?.push(stored);
+            }
+
+            self.lock_report()?.media += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns full name of subdeck, subdeck which doesn't exist yet is created.
+    async fn prepare_subdeck(&self, subdeck: &str) -> Result<String> {
+        let deck = format!("{}::{subdeck}", self.lock_report()?.deck);
+
+        if self
+            .lock_subdecks()?
+            .iter()
+            .any(|(known, _)| *known == deck)
+        {
+            return Ok(deck);
+        }
+
+        let created = !self.backend.deck_exists(&deck).await?;
+
+        if created {
+            self.backend.create_deck(&deck).await?;
+        }
+
+        self.lock_subdecks()?.push((deck.clone(), created));
+        Ok(deck)
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        let (note_ids, deck, new_deck) = {
+            let mut report = self.lock_report()?;
+            (
+                std::mem::take(&mut report.note_ids),
+                report.deck.clone(),
+                report.new_deck,
+            )
+        };
+
+        let mut decks: Vec<String> = self
+            .lock_subdecks()?
+            .iter()
+            .filter(|(_, created)| *created)
+            .map(|(subdeck, _)| subdeck.clone())
+            .collect();
+
+        if new_deck {
+            decks.push(deck);
+        }
+
+        if !note_ids.is_empty() {
+            self.backend.delete_notes(note_ids).await?;
+        }
+
+        if !decks.is_empty() {
+            self.backend.delete_decks(decks).await?;
+        }
+
+        let stored_media = std::mem::take(&mut *self.lock_stored_media()?);
+
+        media::restore(self.backend, stored_media).await
+    }
+
+    fn lock_pending(&self) -> Result<MutexGuard<'_, Vec<PendingCard>>> {
+        self.pending
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock pending notes"))
+    }
+
+    fn lock_subdecks(&self) -> Result<MutexGuard<'_, Vec<(String, bool)>>> {
+        self.subdecks
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock subdecks"))
+    }
+
+    fn lock_media(&self) -> Result<MutexGuard<'_, LocalMedia>> {
+        self.media
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock media"))
+    }
+
+    fn lock_stored(&self) -> Result<MutexGuard<'_, HashSet<String>>> {
+        self.stored
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock stored media"))
+    }
+
+    fn lock_stored_media(&self) -> Result<MutexGuard<'_, Vec<StoredMedia>>> {
+        self.stored_media
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock stored media"))
+    }
+
+    fn lock_report(&self) -> Result<MutexGuard<'_, ImportReport>> {
+        self.report
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock report"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    use crate::{MarkdownOptions, MarkdownStyle, ToolController, memory::MemoryBackend};
+
+    #[tokio::test]
+    async fn test_import_markdown_subdecks() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("Lecture.md");
+        std::fs::write(
+            &path,
+            "## Q - ?\n\nA\n\n# Verbs\n\n## бігти\n\nto run\n\n# Nouns\n\n## кіт\n\ncat\n",
+        )
+        .expect("failed to write to file");
+
+        let md_options = MarkdownOptions {
+            style: MarkdownStyle::Headings,
+            subdecks: true,
+            ..MarkdownOptions::default()
+        };
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let report = controller
+            .convert_md_to_deck(&path, &md_options, &ImportOptions::default())
+            .await
+            .expect("failed to import deck");
+
+        assert_eq!((report.deck.as_str(), report.added), ("Lecture", 3));
+        assert_eq!(
+            controller.deck_list().await.expect("failed to get decks"),
+            vec!["Lecture", "Lecture::Verbs", "Lecture::Nouns"]
+        );
+        assert_eq!(
+            controller
+                .backend
+                .cards("Lecture::Verbs")
+                .await
+                .expect("failed to get cards"),
+            vec![Card::basic("бігти", "to run").with_id(Some(2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_atomic_import_rollback_of_subdecks() {
+        let backend = MemoryBackend::default();
+        let options = ImportOptions {
+            atomic: true,
+            ..ImportOptions::default()
+        };
+
+        backend
+            .create_deck("Lecture")
+            .await
+            .expect("failed to create deck");
+
+        // card refused by Anki rolls back notes and subdecks created by atomic import
+        let batch = NotesBatch::new(&backend, "Lecture", true, Path::new(""), &options);
+        let result = async {
+            batch
+                .push_into(Some("Verbs".to_string()), Card::basic("бігти", "to run"))
+                .await?;
+            batch
+                .push_into(Some("Nouns".to_string()), Card::basic("", "cat"))
+                .await?;
+            batch.flush().await
+        }
+        .await;
+
+        assert!(matches!(
+            batch.finish(result).await,
+            Err(Error::RolledBack(_))
+        ));
+        assert!(
+            backend
+                .deck_names()
+                .await
+                .expect("failed to get decks")
+                .is_empty()
+        );
+        assert!(
+            backend
+                .notes
+                .lock()
+                .expect("failed to lock notes")
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_atomic_import_rollback_of_media() {
+        let dir = tempdir().expect("failed to create temp directory");
+        std::fs::write(dir.path().join("cat.mp3"), b"meow").expect("failed to write audio");
+        std::fs::write(dir.path().join("dog.mp3"), b"woof").expect("failed to write audio");
+
+        let backend = MemoryBackend::default();
+        let options = ImportOptions {
+            atomic: true,
+            ..ImportOptions::default()
+        };
+
+        backend
+            .create_deck("Sounds")
+            .await
+            .expect("failed to create deck");
+        backend
+            .store_media("dog.mp3", b"bark".to_vec())
+            .await
+            .expect("failed to store media");
+
+        // new file is deleted and replaced one gets its previous content back
+        let batch = NotesBatch::new(&backend, "Sounds", true, dir.path(), &options);
+        let result = async {
+            batch
+                .push(Card::basic("кіт", "[sound:cat.mp3] [sound:dog.mp3]"))
+                .await?;
+            batch.flush().await?;
+            batch.push(Card::basic("", "empty")).await?;
+            batch.flush().await
+        }
+        .await;
+
+        assert!(matches!(
+            batch.finish(result).await,
+            Err(Error::RolledBack(_))
+        ));
+        assert_eq!(
+            *backend.media.lock().expect("failed to lock media"),
+            vec![("dog.mp3".to_string(), b"bark".to_vec())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_media() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("Sounds.json");
+        std::fs::write(dir.path().join("cat.mp3"), b"meow").expect("failed to write audio");
+        std::fs::write(
+            &path,
+            r#"[
+                {"front": "кіт", "back": "cat [sound:cat.mp3]"},
+                {"front": "кошеня", "back": "kitten [sound:cat.mp3]"}
+            ]"#,
+        )
+        .expect("failed to write to file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let report = controller
+            .convert_json_to_deck(&path, &ImportOptions::default())
+            .await
+            .expect("failed to import deck");
+
+        assert_eq!((report.added, report.media), (2, 1));
+        assert_eq!(
+            controller
+                .backend
+                .retrieve_media("cat.mp3")
+                .await
+                .expect("failed to retrieve media"),
+            Some(b"meow".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_media_of_added_notes_only() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("Sounds.json");
+        std::fs::write(dir.path().join("cat.mp3"), b"meow").expect("failed to write audio");
+        std::fs::write(dir.path().join("dog.mp3"), b"woof").expect("failed to write audio");
+        std::fs::write(
+            &path,
+            r#"[
+                {"front": "", "back": "cat [sound:cat.mp3]"},
+                {"front": "пес", "back": "dog [sound:dog.mp3]"}
+            ]"#,
+        )
+        .expect("failed to write to file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let report = controller
+            .convert_json_to_deck(&path, &ImportOptions::default())
+            .await
+            .expect("failed to import deck");
+
+        assert_eq!(
+            (report.added, report.failures.len(), report.media),
+            (1, 1, 1)
+        );
+        assert_eq!(
+            controller
+                .backend
+                .retrieve_media("cat.mp3")
+                .await
+                .expect("failed to retrieve media"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_media_outside_of_deck_dir() {
+        let dir = tempdir().expect("failed to create temp directory");
+        let deck_dir = dir.path().join("deck");
+        let path = deck_dir.join("Secrets.md");
+        std::fs::create_dir(&deck_dir).expect("failed to create directory");
+        std::fs::write(dir.path().join("secret.png"), b"secret").expect("failed to write file");
+        std::fs::write(&path, "1. Q - ![](../secret.png)\n").expect("failed to write to file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+
+        assert!(
+            controller
+                .convert_md_to_deck(
+                    &path,
+                    &MarkdownOptions::default(),
+                    &ImportOptions::default()
+                )
+                .await
+                .is_err()
+        );
+        assert!(
+            controller
+                .backend
+                .media
+                .lock()
+                .expect("failed to lock media")
+                .is_empty()
+        );
+    }
+}
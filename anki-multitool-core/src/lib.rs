@@ -1,23 +1,69 @@
-use anyhow::{Result, anyhow};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anki_multitool_convert::{
+    apkg::{FromApkgDeck, ToApkgDeck},
+    csv::{FromCsvDeck, ToCsvDeck},
     json::{FromJsonDeck, ToJsonDeck},
     markdown::{FromMarkdownDeck, ToMarkdownDeck},
+    toml::{FromTomlDeck, ToTomlDeck},
+    yaml::{FromYamlDeck, ToYamlDeck},
 };
-use anki_multitool_ds::{card::Card, http::request::Note};
+use anki_multitool_ds::card::Card;
 use anki_multitool_request::client::AnkiClient;
 use anki_multitool_util::file;
 
-pub struct ToolController {
-    pub client: AnkiClient,
+mod backend;
+mod diff;
+mod export;
+mod import;
+mod media;
+#[cfg(test)]
+mod memory;
+mod sync;
+
+pub use anki_multitool_convert::{
+    csv::{CsvOptions, parse_delimiter},
+    markdown::{DEFAULT_HEADING_LEVEL, MarkdownOptions, MarkdownStyle},
+};
+pub use anki_multitool_ds::{
+    card::Separator,
+    error::{Error, Result, text_position},
+    http::request::{ApiKey, DuplicateScope},
+};
+pub use backend::{Backend, LocalCollection};
+pub use diff::{DeckDiff, FieldChange, ModifiedCard, TagsChange};
+pub use export::{ExportOptions, MEDIA_DIR};
+use import::NotesBatch;
+pub use import::{DEFAULT_BATCH_SIZE, ImportOptions, ImportReport, NoteFailure};
+use media::LocalMedia;
+pub use sync::{SyncChange, SyncFailure, SyncOptions, SyncReport};
+
+pub struct ToolController<B: Backend = AnkiClient> {
+    pub backend: B,
 }
 
 impl ToolController {
     pub fn new(host: String, port: u16) -> Self {
-        Self {
-            client: AnkiClient::new(host, port),
-        }
+        Self::with_backend(AnkiClient::new(host, port))
+    }
+
+    /// Sends key with every request, for AnkiConnect which requires it.
+    pub fn with_api_key(self, api_key: ApiKey) -> Self {
+        Self::with_backend(self.backend.with_api_key(api_key))
+    }
+}
+
+impl ToolController<LocalCollection> {
+    /// Controller reading decks from collection file, works while Anki is closed, but can't
+    /// change decks.
+    pub fn with_collection<P: Into<PathBuf>>(path: P) -> Self {
+        Self::with_backend(LocalCollection::new(path))
+    }
+}
+
+impl<B: Backend> ToolController<B> {
+    pub fn with_backend(backend: B) -> Self {
+        Self { backend }
     }
 
     pub fn version(&self) -> &str {
@@ -25,98 +71,479 @@ impl ToolController {
     }
 
     pub async fn deck_list(&self) -> Result<Vec<String>> {
-        self.client
-            .deck_names_req()
-            .await?
-            .into_result()?
-            .into_names_res()
-            .ok_or_else(|| anyhow!("no result in response"))
+        self.backend.deck_names().await
     }
 
     pub async fn new_deck(&self, deck: &str) -> Result<u64> {
-        self.client
-            .create_deck_req(deck)
-            .await?
-            .into_result()?
-            .into_id_res()
-            .ok_or_else(|| anyhow!("no result in response"))
-    }
-
-    pub async fn convert_deck_to_json(&self, deck: &str) -> Result<String> {
-        ToJsonDeck::new(deck)
-            .write(
-                self.client
-                    .notes_info_req(deck)
-                    .await?
-                    .into_result()?
-                    .into_notes_info_res()
-                    .ok_or_else(|| anyhow!("no result in response"))?
-                    .into_iter()
-                    .map(|note| Card {
-                        front: note.fields.front.value,
-                        back: note.fields.back.value,
-                    }),
-            )
-            .await
-    }
-
-    pub async fn convert_deck_to_md(&self, deck: &str) -> Result<String> {
-        ToMarkdownDeck::new(deck)
-            .write(
-                self.client
-                    .notes_info_req(deck)
-                    .await?
-                    .into_result()?
-                    .into_notes_info_res()
-                    .ok_or_else(|| anyhow!("no result in response"))?
+        self.backend.create_deck(deck).await
+    }
+
+    pub async fn convert_deck_to_json(
+        &self,
+        deck: &str,
+        options: &ExportOptions,
+    ) -> Result<String> {
+        let mut cards = self.backend.cards(deck).await?;
+        let media = export::download_media(&self.backend, &mut cards).await?;
+        let file = ToJsonDeck::new(deck)
+            .in_dir(&options.dir)
+            .write(cards.into_iter().map(|card| options.apply(card)))
+            .await?;
+
+        export::save_media(&options.dir, media)?;
+        Ok(file)
+    }
+
+    /// Exports deck to Markdown file, with subdecks cards of every subdeck are written under
+    /// top-level heading with its name.
+    pub async fn convert_deck_to_md(
+        &self,
+        deck: &str,
+        md_options: &MarkdownOptions,
+        options: &ExportOptions,
+    ) -> Result<String> {
+        md_options.validate()?;
+
+        let mut cards: Vec<(Option<String>, Card)> = if md_options.subdecks {
+            export::cards_by_subdeck(&self.backend, deck).await?
+        } else {
+            self.backend
+                .cards(deck)
+                .await?
+                .into_iter()
+                .map(|card| (None, card))
+                .collect()
+        };
+
+        let media =
+            export::download_media(&self.backend, cards.iter_mut().map(|(_, card)| card)).await?;
+        let file = ToMarkdownDeck::new(deck)
+            .in_dir(&options.dir)
+            .with_options(*md_options)
+            .write_in_subdecks(
+                cards
                     .into_iter()
-                    .map(|note| Card {
-                        front: note.fields.front.value,
-                        back: note.fields.back.value,
-                    }),
+                    .map(|(subdeck, card)| (subdeck, options.apply(card))),
             )
-            .await
+            .await?;
+
+        export::save_media(&options.dir, media)?;
+        Ok(file)
     }
 
-    pub async fn convert_json_to_deck<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        if !path.as_ref().exists() {
-            return Err(anyhow!("file {} does not exist", path.as_ref().display()));
-        }
+    pub async fn convert_deck_to_csv(
+        &self,
+        deck: &str,
+        csv_options: &CsvOptions,
+        options: &ExportOptions,
+    ) -> Result<String> {
+        let mut cards = self.backend.cards(deck).await?;
+        let media = export::download_media(&self.backend, &mut cards).await?;
+        let file = ToCsvDeck::new(deck, csv_options.delimiter)
+            .in_dir(&options.dir)
+            .write(cards.into_iter().map(|card| options.apply(card)))
+            .await?;
+
+        export::save_media(&options.dir, media)?;
+        Ok(file)
+    }
 
-        let deck_name = file::to_file_name(path.as_ref())?;
-        self.new_deck(deck_name.as_str()).await?;
-
-        FromJsonDeck::new(path)?
-            .for_each(async |card| {
-                self.client
-                    .add_note_req(Note::new(deck_name.to_string(), card.front, card.back))
-                    .await?
-                    .into_result()
-                    .map(|_| ())
-            })
+    pub async fn convert_deck_to_yaml(
+        &self,
+        deck: &str,
+        options: &ExportOptions,
+    ) -> Result<String> {
+        let mut cards = self.backend.cards(deck).await?;
+        let media = export::download_media(&self.backend, &mut cards).await?;
+        let file = ToYamlDeck::new(deck)
+            .in_dir(&options.dir)
+            .write(cards.into_iter().map(|card| options.apply(card)))
             .await?;
 
-        Ok(deck_name)
+        export::save_media(&options.dir, media)?;
+        Ok(file)
     }
 
-    pub async fn convert_md_to_deck<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        if !path.as_ref().exists() {
-            return Err(anyhow!("file {} does not exist", path.as_ref().display()));
+    pub async fn convert_deck_to_toml(
+        &self,
+        deck: &str,
+        options: &ExportOptions,
+    ) -> Result<String> {
+        let mut cards = self.backend.cards(deck).await?;
+        let media = export::download_media(&self.backend, &mut cards).await?;
+        let file = ToTomlDeck::new(deck)
+            .in_dir(&options.dir)
+            .write(cards.into_iter().map(|card| options.apply(card)))
+            .await?;
+
+        export::save_media(&options.dir, media)?;
+        Ok(file)
+    }
+
+    pub async fn convert_json_to_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        let batch = self.start_import(path.as_ref(), None, options).await?;
+        let result = async {
+            FromJsonDeck::new(path)?
+                .for_each(async |card| batch.push(card).await)
+                .await?;
+            batch.flush().await
+        }
+        .await;
+
+        batch.finish(result).await
+    }
+
+    /// Imports Markdown deck, with subdecks cards under top-level headings go to subdecks of
+    /// deck, which are created if needed.
+    pub async fn convert_md_to_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        md_options: &MarkdownOptions,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        md_options.validate()?;
+
+        let batch = self.start_import(path.as_ref(), None, options).await?;
+        let result = async {
+            FromMarkdownDeck::new(path)?
+                .with_options(*md_options)
+                .for_each_in_subdeck(async |subdeck, card| batch.push_into(subdeck, card).await)
+                .await?;
+            batch.flush().await
         }
+        .await;
 
-        let deck_name = file::to_file_name(path.as_ref())?;
-        self.new_deck(deck_name.as_str()).await?;
-
-        FromMarkdownDeck::new(path)?
-            .for_each(async |card| {
-                self.client
-                    .add_note_req(Note::new(deck_name.to_string(), card.front, card.back))
-                    .await?
-                    .into_result()
-                    .map(|_| ())
-            })
+        batch.finish(result).await
+    }
+
+    pub async fn convert_csv_to_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        csv_options: &CsvOptions,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        let batch = self.start_import(path.as_ref(), None, options).await?;
+        let result = async {
+            FromCsvDeck::new(path, csv_options.clone())?
+                .for_each(async |card| batch.push(card).await)
+                .await?;
+            batch.flush().await
+        }
+        .await;
+
+        batch.finish(result).await
+    }
+
+    /// Imports YAML deck, name of deck is taken from document if it's given there.
+    pub async fn convert_yaml_to_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        let document = FromYamlDeck::new(path.as_ref())?;
+        let batch = self
+            .start_import(path.as_ref(), document.deck(), options)
             .await?;
+        let result = async {
+            document
+                .for_each(async |card| batch.push(card).await)
+                .await?;
+            batch.flush().await
+        }
+        .await;
+
+        batch.finish(result).await
+    }
+
+    /// Imports TOML deck, name of deck is taken from document if it's given there.
+    pub async fn convert_toml_to_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        let document = FromTomlDeck::new(path.as_ref())?;
+        let batch = self
+            .start_import(path.as_ref(), document.deck(), options)
+            .await?;
+        let result = async {
+            document
+                .for_each(async |card| batch.push(card).await)
+                .await?;
+            batch.flush().await
+        }
+        .await;
 
-        Ok(deck_name)
+        batch.finish(result).await
     }
+
+    /// Syncs deck with JSON, Markdown, CSV, YAML or TOML file, format is chosen by extension of
+    /// file.
+    /// `md_options` are used only for Markdown file and `csv_options` only for CSV file.
+    pub async fn sync_deck<P: AsRef<Path>>(
+        &self,
+        path: P,
+        md_options: &MarkdownOptions,
+        csv_options: &CsvOptions,
+        options: &SyncOptions,
+    ) -> Result<SyncReport> {
+        let path = path.as_ref();
+        let (document_deck, cards, media) =
+            read_cards_with_media(path, md_options, csv_options).await?;
+        let deck = match options.deck.clone().or(document_deck) {
+            Some(deck) => deck,
+            None => file::to_file_name(path)?,
+        };
+        let notes = if self.backend.deck_exists(&deck).await? {
+            self.backend.cards(&deck).await?
+        } else if options.dry_run {
+            Vec::new()
+        } else {
+            self.new_deck(&deck).await?;
+            Vec::new()
+        };
+
+        let (changes, unchanged) = sync::plan(notes, cards, options.delete);
+        let (changes, failures, media) = if options.dry_run {
+            (changes, Vec::new(), 0)
+        } else {
+            sync::apply(&self.backend, &deck, changes, &media).await?
+        };
+
+        Ok(SyncReport {
+            deck,
+            dry_run: options.dry_run,
+            changes,
+            unchanged,
+            media,
+            failures,
+        })
+    }
+
+    /// Compares deck with JSON, Markdown, CSV, YAML, TOML or apkg file, format is chosen by
+    /// extension of file.
+    /// `md_options` are used only for Markdown file and `csv_options` only for CSV file.
+    pub async fn diff_deck<P: AsRef<Path>>(
+        &self,
+        deck: &str,
+        path: P,
+        md_options: &MarkdownOptions,
+        csv_options: &CsvOptions,
+    ) -> Result<DeckDiff> {
+        let (_, cards, _) = read_cards_with_media(path.as_ref(), md_options, csv_options).await?;
+        let notes = self.backend.cards(deck).await?;
+
+        Ok(diff::diff(deck.to_string(), notes, cards))
+    }
+
+    /// Validates input and prepares target deck: creates new one, or reuses existing deck in
+    /// append mode. Deck is chosen by options, then by name given in file, then by name of file.
+    async fn start_import<'a>(
+        &'a self,
+        path: &Path,
+        deck: Option<&str>,
+        options: &'a ImportOptions,
+    ) -> Result<NotesBatch<'a, B>> {
+        if !path.exists() {
+            return Err(Error::FileNotFound(path.to_path_buf()));
+        }
+
+        options.validate()?;
+
+        let deck_name = match (&options.deck, deck) {
+            (Some(deck), _) => deck.clone(),
+            (None, Some(deck)) => deck.to_string(),
+            (None, None) => file::to_file_name(path)?,
+        };
+        let new_deck = !(options.append && self.backend.deck_exists(&deck_name).await?);
+
+        if new_deck {
+            self.new_deck(deck_name.as_str()).await?;
+        }
+
+        Ok(NotesBatch::new(
+            &self.backend,
+            &deck_name,
+            new_deck,
+            path.parent().unwrap_or(Path::new("")),
+            options,
+        ))
+    }
+}
+
+/// Builds Anki package from JSON, Markdown, CSV, YAML or TOML file without connection to Anki.
+/// Deck is named by file, if file format doesn't carry name of deck. Local media files
+/// referenced by cards are packed along with them.
+pub async fn convert_file_to_apkg<P: AsRef<Path>>(
+    path: P,
+    md_options: &MarkdownOptions,
+    options: &ExportOptions,
+) -> Result<String> {
+    let (deck, cards, media) = read_cards_with_media(
+        path.as_ref(),
+        md_options,
+        &CsvOptions::for_path(path.as_ref()),
+    )
+    .await?;
+    let deck = match deck {
+        Some(deck) => deck,
+        None => file::to_file_name(path)?,
+    };
+
+    ToApkgDeck::new(&deck)
+        .in_dir(&options.dir)
+        .with_media(media.into_iter().collect())
+        .write(cards.into_iter().map(|card| options.apply(card)))
+        .await
+}
+
+/// Builds Anki package from Markdown file, like `convert_file_to_apkg`, but other files are
+/// rejected.
+pub async fn convert_md_to_apkg<P: AsRef<Path>>(
+    path: P,
+    md_options: &MarkdownOptions,
+    options: &ExportOptions,
+) -> Result<String> {
+    check_extension(path.as_ref(), "md", "Markdown")?;
+    convert_file_to_apkg(path, md_options, options).await
+}
+
+/// Builds Anki package from JSON file, like `convert_file_to_apkg`, but other files are
+/// rejected.
+pub async fn convert_json_to_apkg<P: AsRef<Path>>(
+    path: P,
+    options: &ExportOptions,
+) -> Result<String> {
+    check_extension(path.as_ref(), "json", "JSON")?;
+    convert_file_to_apkg(path, &MarkdownOptions::default(), options).await
+}
+
+/// Converts Anki package to Markdown file without connection to Anki.
+pub async fn convert_apkg_to_md<P: AsRef<Path>>(
+    path: P,
+    md_options: &MarkdownOptions,
+    options: &ExportOptions,
+) -> Result<String> {
+    md_options.validate()?;
+
+    let (deck, cards) = read_cards(path.as_ref(), md_options, &CsvOptions::default()).await?;
+    let deck = match deck {
+        Some(deck) => deck,
+        None => file::to_file_name(path)?,
+    };
+
+    ToMarkdownDeck::new(&deck)
+        .in_dir(&options.dir)
+        .with_options(*md_options)
+        .write(cards.into_iter().map(|card| options.apply(card)))
+        .await
+}
+
+fn check_extension(path: &Path, extension: &str, format: &str) -> Result<()> {
+    if path.extension().and_then(|ext| ext.to_str()) != Some(extension) {
+        return Err(Error::invalid(format!(
+            "{} is not a {format} file, expected .{extension} extension",
+            path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads cards like `read_cards` and points their references to local media files to names
+/// of the files in collection, the files are returned along with cards.
+async fn read_cards_with_media(
+    path: &Path,
+    md_options: &MarkdownOptions,
+    csv_options: &CsvOptions,
+) -> Result<(Option<String>, Vec<Card>, LocalMedia)> {
+    let (deck, mut cards) = read_cards(path, md_options, csv_options).await?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut media = LocalMedia::new();
+
+    for card in &mut cards {
+        media::localize(dir, card, &mut media)?;
+    }
+
+    Ok((deck, cards, media))
+}
+
+/// Reads all cards of file along with name of deck, if file format carries it. Markdown file
+/// is read with `md_options`, subdecks of it aren't supported, CSV file with `csv_options`.
+async fn read_cards(
+    path: &Path,
+    md_options: &MarkdownOptions,
+    csv_options: &CsvOptions,
+) -> Result<(Option<String>, Vec<Card>)> {
+    use std::sync::Mutex;
+
+    if !path.exists() {
+        return Err(Error::FileNotFound(path.to_path_buf()));
+    }
+
+    let cards = Mutex::new(Vec::new());
+    let collect = async |card| {
+        cards
+            .lock()
+            .map_err(|_| Error::invalid("failed to lock cards"))?
+            .push(card);
+        Ok(())
+    };
+
+    let deck = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml" | "yml") => {
+            let document = FromYamlDeck::new(path)?;
+            document.for_each(collect).await?;
+            document.deck().map(str::to_string)
+        }
+        Some("toml") => {
+            let document = FromTomlDeck::new(path)?;
+            document.for_each(collect).await?;
+            document.deck().map(str::to_string)
+        }
+        Some("apkg") => {
+            let package = FromApkgDeck::new(path)?;
+            package.for_each(collect).await?;
+            package.deck().map(str::to_string)
+        }
+        Some("json") => {
+            FromJsonDeck::new(path)?.for_each(collect).await?;
+            None
+        }
+        Some("md") => {
+            // cards are read as one list, so subdeck of every card would be lost
+            if md_options.subdecks {
+                return Err(Error::invalid(format!(
+                    "subdecks of Markdown file {} are supported only by import into Anki",
+                    path.display()
+                )));
+            }
+
+            FromMarkdownDeck::new(path)?
+                .with_options(*md_options)
+                .for_each(collect)
+                .await?;
+            None
+        }
+        Some("csv" | "tsv" | "txt") => {
+            FromCsvDeck::new(path, csv_options.clone())?
+                .for_each(collect)
+                .await?;
+            None
+        }
+        _ => {
+            return Err(Error::invalid(format!(
+                "unsupported format of file {}, expected .json, .md, .csv, .tsv, .txt, .yaml, .toml or .apkg",
+                path.display()
+            )));
+        }
+    };
+    let cards = cards
+        .into_inner()
+        .map_err(|_| Error::invalid("failed to unlock cards"))?;
+
+    Ok((deck, cards))
 }
@@ -0,0 +1,315 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anki_multitool_ds::{
+    card::Card,
+    error::{Error, Result},
+};
+
+use crate::Backend;
+
+const SOUND_PREFIX: &str = "[sound:";
+
+/// Ranges of media names referenced by text with `<img src="...">` or `[sound:...]`.
+fn reference_ranges(text: &str) -> Vec<Range<usize>> {
+    // ASCII lowercasing keeps byte offsets, so ranges are valid in original text
+    let lower = text.to_ascii_lowercase();
+    let mut ranges = Vec::new();
+    let mut rest = 0;
+
+    while let Some(start) = lower[rest..].find(['<', '[']).map(|i| i + rest) {
+        rest = start + 1;
+
+        let range = if lower[start..].starts_with(SOUND_PREFIX) {
+            let name = start + SOUND_PREFIX.len();
+            lower[name..].find(']').map(|end| name..name + end)
+        } else if lower[start..].starts_with("<img")
+            && lower[start + 4..].starts_with(char::is_whitespace)
+        {
+            lower[start..]
+                .find('>')
+                .and_then(|end| src_range(&lower[start..start + end]))
+                .map(|src| start + src.start..start + src.end)
+        } else {
+            None
+        };
+
+        if let Some(range) = range.filter(|range| !range.is_empty()) {
+            rest = range.end;
+            ranges.push(range);
+        }
+    }
+
+    ranges
+}
+
+/// Range of value of `src` attribute in tag without closing `>`.
+fn src_range(tag: &str) -> Option<Range<usize>> {
+    let mut rest = 0;
+
+    loop {
+        let start = tag[rest..].find("src")? + rest;
+        rest = start + 3;
+
+        if !tag[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+
+        let Some(value) = tag[rest..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let trimmed = value.trim_start();
+        let value_start = tag.len() - trimmed.len();
+
+        return match trimmed.chars().next()? {
+            quote @ ('"' | '\'') => trimmed[1..]
+                .find(quote)
+                .map(|end| value_start + 1..value_start + 1 + end),
+            _ => {
+                let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+                Some(value_start..value_start + end.min(trimmed.trim_end_matches('/').len()))
+            }
+        };
+    }
+}
+
+/// Checks whether reference points to file rather than to web page or inline data.
+fn is_file(name: &str) -> bool {
+    !name.contains("://") && !name.starts_with("data:")
+}
+
+/// Media files referenced by text, web links are left out.
+pub(crate) fn references(text: &str) -> Vec<&str> {
+    reference_ranges(text)
+        .into_iter()
+        .map(|range| &text[range])
+        .filter(|name| is_file(name))
+        .collect()
+}
+
+/// Media files referenced by fields of card.
+pub(crate) fn card_references(card: &Card) -> impl Iterator<Item = &str> {
+    card.fields.values().flat_map(|value| references(value))
+}
+
+/// Local media files of deck by names they are stored with in collection.
+pub(crate) type LocalMedia = HashMap<String, PathBuf>;
+
+/// Resolves reference to local file relative to `dir`, `None` if there is no such file.
+/// Reference which leads outside of `dir`, e.g. absolute path or path with `..`, is rejected,
+/// so deck can't make import read arbitrary files.
+fn resolve(dir: &Path, reference: &str) -> Result<Option<PathBuf>> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let path = dir.join(reference);
+
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let (dir, path) = (dir.canonicalize()?, path.canonicalize()?);
+
+    if !path.starts_with(&dir) {
+        return Err(Error::invalid(format!(
+            "media file '{reference}' is outside of directory {}",
+            dir.display()
+        )));
+    }
+
+    Ok(Some(path))
+}
+
+/// Points references of card to local files to names of the files, which they are stored
+/// with in collection, and adds the files to `media`. References to files which don't exist
+/// are left as is, as they may be in collection already.
+pub(crate) fn localize(dir: &Path, card: &mut Card, media: &mut LocalMedia) -> Result<()> {
+    let references: Vec<String> = card_references(card).map(str::to_string).collect();
+    let mut names = HashMap::new();
+
+    for reference in references {
+        if names.contains_key(&reference) {
+            continue;
+        }
+
+        let Some(path) = resolve(dir, &reference)? else {
+            continue;
+        };
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        match media.get(name) {
+            Some(known) if *known != path => {
+                return Err(Error::invalid(format!(
+                    "media files {} and {} have the same name",
+                    known.display(),
+                    path.display()
+                )));
+            }
+            Some(_) => {}
+            None => {
+                media.insert(name.to_string(), path.clone());
+            }
+        }
+
+        names.insert(reference, name.to_string());
+    }
+
+    if !names.is_empty() {
+        for value in card.fields.values_mut() {
+            *value = rewrite(value, &names);
+        }
+    }
+
+    Ok(())
+}
+
+/// Media file stored in collection along with previous content of file with the same name,
+/// so storing can be undone.
+pub(crate) struct StoredMedia {
+    name: String,
+    previous: Option<Vec<u8>>,
+}
+
+/// Stores local file in collection under its name, references to it are already pointed
+/// there by `localize`. With `keep_previous` content of file which is replaced is kept.
+pub(crate) async fn store<B: Backend>(
+    backend: &B,
+    name: &str,
+    path: &Path,
+    keep_previous: bool,
+) -> Result<StoredMedia> {
+    let previous = if keep_previous {
+        backend.retrieve_media(name).await?
+    } else {
+        None
+    };
+    let stored = backend.store_media(name, std::fs::read(path)?).await?;
+
+    if stored != name {
+        return Err(Error::Response(format!(
+            "media file '{name}' was stored as '{stored}'"
+        )));
+    }
+
+    Ok(StoredMedia {
+        name: stored,
+        previous,
+    })
+}
+
+/// Undoes storing of files: replaced files get their previous content back, new files are
+/// deleted.
+pub(crate) async fn restore<B: Backend>(backend: &B, stored: Vec<StoredMedia>) -> Result<()> {
+    for media in stored {
+        match media.previous {
+            Some(data) => {
+                backend.store_media(&media.name, data).await?;
+            }
+            None => backend.delete_media(&media.name).await?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces references to media files, which are keys of `names`, with their values.
+pub(crate) fn rewrite(text: &str, names: &HashMap<String, String>) -> String {
+    let mut rewritten = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for range in reference_ranges(text) {
+        if let Some(name) = names.get(&text[range.clone()]) {
+            rewritten.push_str(&text[last..range.start]);
+            rewritten.push_str(name);
+            last = range.end;
+        }
+    }
+
+    rewritten.push_str(&text[last..]);
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_references() {
+        assert_eq!(
+            references(r#"<img src="heart.png"> beats [sound:lub dub.mp3]"#),
+            vec!["heart.png", "lub dub.mp3"]
+        );
+        assert_eq!(
+            references(r#"<IMG alt="x" SRC='img/a.png'><img class=big src=b.png/>"#),
+            vec!["img/a.png", "b.png"]
+        );
+        assert_eq!(
+            references(r#"<img data-src="a.png" src="https://x.org/b.png"> <imgs src="c.png">"#),
+            Vec::<&str>::new()
+        );
+        assert_eq!(references("[sound:] [sound:a.mp3"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_media_localize() {
+        let dir = tempfile::tempdir().expect("failed to create temp directory");
+        let deck_dir = dir.path().join("deck");
+        std::fs::create_dir_all(deck_dir.join("img")).expect("failed to create directory");
+        std::fs::write(deck_dir.join("img").join("heart.png"), b"png")
+            .expect("failed to write image");
+        std::fs::write(dir.path().join("secret"), b"secret").expect("failed to write file");
+
+        let mut media = LocalMedia::new();
+        let mut card = Card::basic(
+            r#"<img src="img/heart.png">"#,
+            "[sound:missing.mp3] [sound:img/../img/heart.png]",
+        );
+
+        localize(&deck_dir, &mut card, &mut media).expect("failed to localize card");
+
+        assert_eq!(
+            card,
+            Card::basic(
+                r#"<img src="heart.png">"#,
+                "[sound:missing.mp3] [sound:heart.png]"
+            )
+        );
+        assert_eq!(media.len(), 1);
+
+        for reference in [
+            "../secret",
+            &dir.path().join("secret").display().to_string(),
+        ] {
+            let mut card = Card::basic(format!(r#"<img src="{reference}">"#), "");
+
+            assert!(
+                localize(&deck_dir, &mut card, &mut media).is_err(),
+                "{reference} is outside of deck directory"
+            );
+        }
+    }
+
+    #[test]
+    fn test_media_rewrite() {
+        let names = HashMap::from([
+            ("img/heart.png".to_string(), "heart.png".to_string()),
+            ("a.mp3".to_string(), "media/a.mp3".to_string()),
+        ]);
+
+        assert_eq!(
+            rewrite(
+                r#"<img src="img/heart.png" alt="heart"> [sound:a.mp3] [sound:b.mp3]"#,
+                &names
+            ),
+            r#"<img src="heart.png" alt="heart"> [sound:media/a.mp3] [sound:b.mp3]"#
+        );
+        assert_eq!(rewrite("no media", &names), "no media");
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Mutex;
+
+use anki_multitool_ds::{
+    card::{Card, Fields},
+    error::{Error, Result},
+    http::request::DuplicateScope,
+};
+
+use crate::Backend;
+
+/// Backend keeping decks in memory, notes are `(id, deck, card)`.
+#[derive(Default)]
+pub(crate) struct MemoryBackend {
+    pub(crate) decks: Mutex<Vec<String>>,
+    pub(crate) notes: Mutex<Vec<(u64, String, Card)>>,
+    pub(crate) media: Mutex<Vec<(String, Vec<u8>)>>,
+}
+
+impl Backend for MemoryBackend {
+    async fn deck_names(&self) -> Result<Vec<String>> {
+        Ok(self.decks.lock().expect("failed to lock decks").clone())
+    }
+
+    async fn create_deck(&self, deck: &str) -> Result<u64> {
+        let mut decks = self.decks.lock().expect("failed to lock decks");
+
+        if decks.iter().any(|d| d == deck) {
+            return Err(Error::DeckExists(deck.to_string()));
+        }
+
+        decks.push(deck.to_string());
+        Ok(decks.len() as u64)
+    }
+
+    async fn delete_decks(&self, decks: Vec<String>) -> Result<()> {
+        self.decks
+            .lock()
+            .expect("failed to lock decks")
+            .retain(|d| !decks.contains(d));
+        Ok(())
+    }
+
+    async fn cards(&self, deck: &str) -> Result<Vec<Card>> {
+        Ok(self
+            .notes
+            .lock()
+            .expect("failed to lock notes")
+            .iter()
+            .filter(|(_, d, _)| d == deck)
+            .map(|(id, _, card)| card.clone().with_id(Some(*id)))
+            .collect())
+    }
+
+    async fn add_notes(
+        &self,
+        deck: &str,
+        cards: Vec<Card>,
+        _duplicate_scope: DuplicateScope,
+    ) -> Result<Vec<Result<u64>>> {
+        let mut notes = self.notes.lock().expect("failed to lock notes");
+
+        Ok(cards
+            .into_iter()
+            .map(|card| {
+                if card.front().is_empty() {
+                    return Err(Error::Api(
+                        "cannot create note because it is empty".to_string(),
+                    ));
+                }
+
+                let id = notes.len() as u64 + 1;
+                notes.push((id, deck.to_string(), card));
+                Ok(id)
+            })
+            .collect())
+    }
+
+    async fn update_notes(&self, updates: Vec<(u64, Fields)>) -> Result<Vec<Result<()>>> {
+        let mut notes = self.notes.lock().expect("failed to lock notes");
+
+        Ok(updates
+            .into_iter()
+            .map(|(id, fields)| {
+                let (_, _, card) = notes
+                    .iter_mut()
+                    .find(|(note_id, _, _)| *note_id == id)
+                    .ok_or_else(|| Error::Api(format!("note was not found: {id}")))?;
+                card.fields.extend(fields);
+                Ok(())
+            })
+            .collect())
+    }
+
+    async fn update_tags(&self, updates: Vec<(u64, Vec<String>)>) -> Result<Vec<Result<()>>> {
+        let mut notes = self.notes.lock().expect("failed to lock notes");
+
+        Ok(updates
+            .into_iter()
+            .map(|(id, tags)| {
+                let (_, _, card) = notes
+                    .iter_mut()
+                    .find(|(note_id, _, _)| *note_id == id)
+                    .ok_or_else(|| Error::Api(format!("note was not found: {id}")))?;
+                card.tags = tags;
+                Ok(())
+            })
+            .collect())
+    }
+
+    async fn delete_notes(&self, ids: Vec<u64>) -> Result<()> {
+        self.notes
+            .lock()
+            .expect("failed to lock notes")
+            .retain(|(id, _, _)| !ids.contains(id));
+        Ok(())
+    }
+
+    async fn store_media(&self, name: &str, data: Vec<u8>) -> Result<String> {
+        let mut media = self.media.lock().expect("failed to lock media");
+
+        media.retain(|(stored, _)| stored != name);
+        media.push((name.to_string(), data));
+        Ok(name.to_string())
+    }
+
+    async fn retrieve_media(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .media
+            .lock()
+            .expect("failed to lock media")
+            .iter()
+            .find(|(stored, _)| stored == name)
+            .map(|(_, data)| data.clone()))
+    }
+
+    async fn delete_media(&self, name: &str) -> Result<()> {
+        self.media
+            .lock()
+            .expect("failed to lock media")
+            .retain(|(stored, _)| stored != name);
+        Ok(())
+    }
+}
@@ -0,0 +1,624 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anki_multitool_ds::{
+    card::{Card, Fields},
+    error::{Error, Result},
+    http::request::DuplicateScope,
+};
+
+use crate::{
+    Backend, DEFAULT_BATCH_SIZE,
+    media::{self, LocalMedia},
+};
+
+/// Settings of synchronization of deck with file.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Deck to sync with, name of file is used if not set.
+    pub deck: Option<String>,
+    /// Delete notes which are absent in file.
+    pub delete: bool,
+    /// Only plan changes, deck is left untouched.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SyncChange {
+    /// Card from file which isn't in deck yet.
+    Add(Card),
+    /// Note which differs from card in file, `fields` contains only changed fields, `tags`
+    /// are new tags of note, if they changed.
+    Update {
+        id: u64,
+        front: String,
+        fields: Fields,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<Vec<String>>,
+    },
+    /// Note which is absent in file.
+    Delete { id: u64, front: String },
+}
+
+impl SyncChange {
+    pub fn front(&self) -> &str {
+        match self {
+            SyncChange::Add(card) => card.front(),
+            SyncChange::Update { front, .. } | SyncChange::Delete { front, .. } => front,
+        }
+    }
+}
+
+/// Change which Anki refused to apply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncFailure {
+    pub change: SyncChange,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    pub deck: String,
+    pub dry_run: bool,
+    /// Planned changes in dry run, otherwise applied ones.
+    pub changes: Vec<SyncChange>,
+    pub unchanged: usize,
+    /// Media files uploaded to collection.
+    pub media: usize,
+    pub failures: Vec<SyncFailure>,
+}
+
+/// Checks whether card and note have the same tags, order of tags doesn't matter.
+fn same_tags(card: &Card, note: &Card) -> bool {
+    let mut card_tags: Vec<&String> = card.tags.iter().collect();
+    let mut note_tags: Vec<&String> = note.tags.iter().collect();
+
+    card_tags.sort();
+    note_tags.sort();
+    card_tags == note_tags
+}
+
+/// Compares cards from file with notes of deck. Cards are matched with notes by note id, or
+/// by note type and front text if card has no id, its note isn't in deck anymore or note
+/// has other note type.
+pub(crate) fn plan(notes: Vec<Card>, cards: Vec<Card>, delete: bool) -> (Vec<SyncChange>, usize) {
+    let by_id: HashMap<u64, usize> = notes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, note)| note.id.map(|id| (id, i)))
+        .collect();
+    let mut by_front: HashMap<(String, String), VecDeque<usize>> = HashMap::new();
+
+    for (i, note) in notes.iter().enumerate() {
+        by_front
+            .entry((note.model.clone(), note.front().to_string()))
+            .or_default()
+            .push_back(i);
+    }
+
+    let mut notes: Vec<Option<Card>> = notes.into_iter().map(Some).collect();
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+
+    for card in cards {
+        let by_card_id = card.id.and_then(|id| by_id.get(&id).copied()).filter(|&i| {
+            notes[i]
+                .as_ref()
+                .is_some_and(|note| note.model == card.model)
+        });
+        let matched = by_card_id.or_else(|| {
+            let candidates = by_front.get_mut(&(card.model.clone(), card.front().to_string()))?;
+
+            while let Some(i) = candidates.pop_front() {
+                if notes[i].is_some() {
+                    return Some(i);
+                }
+            }
+
+            None
+        });
+
+        match matched.and_then(|i| notes[i].take()) {
+            Some(note) => {
+                let tags = (!same_tags(&card, &note)).then(|| card.tags.clone());
+                let fields: Fields = card
+                    .fields
+                    .into_iter()
+                    .filter(|(name, value)| note.fields.get(name) != Some(value))
+                    .collect();
+
+                match note.id {
+                    Some(id) if !fields.is_empty() || tags.is_some() => {
+                        changes.push(SyncChange::Update {
+                            id,
+                            front: note.front().to_string(),
+                            fields,
+                            tags,
+                        })
+                    }
+                    _ => unchanged += 1,
+                }
+            }
+            None => changes.push(SyncChange::Add(card.with_id(None))),
+        }
+    }
+
+    if delete {
+        changes.extend(notes.into_iter().flatten().filter_map(|note| {
+            note.id.map(|id| SyncChange::Delete {
+                id,
+                front: note.front().to_string(),
+            })
+        }));
+    }
+
+    (changes, unchanged)
+}
+
+/// Applies changes in chunks, additions, updates of fields and tags and deletions of chunk are
+/// sent to backend separately, result of every change is checked separately. Local media files
+/// referenced by applied changes are stored in collection, their number is returned along with
+/// results.
+pub(crate) async fn apply<B: Backend>(
+    backend: &B,
+    deck: &str,
+    changes: Vec<SyncChange>,
+    media: &LocalMedia,
+) -> Result<(Vec<SyncChange>, Vec<SyncFailure>, usize)> {
+    let mut applied = Vec::new();
+    let mut failures = Vec::new();
+    let mut stored = HashSet::new();
+    let mut changes = changes.into_iter().peekable();
+
+    while changes.peek().is_some() {
+        let chunk: Vec<SyncChange> = changes.by_ref().take(DEFAULT_BATCH_SIZE).collect();
+        let mut cards = Vec::new();
+        let mut updates = Vec::new();
+        let mut tag_updates = Vec::new();
+        let mut deletions = Vec::new();
+
+        for change in &chunk {
+            match change {
+                SyncChange::Add(card) => cards.push(card.clone()),
+                SyncChange::Update {
+                    id, fields, tags, ..
+                } => {
+                    if !fields.is_empty() {
+                        updates.push((*id, fields.clone()));
+                    }
+
+                    if let Some(tags) = tags {
+                        tag_updates.push((*id, tags.clone()));
+                    }
+                }
+                SyncChange::Delete { id, .. } => deletions.push(*id),
+            }
+        }
+
+        let mut added = if cards.is_empty() {
+            Vec::new()
+        } else {
+            backend
+                .add_notes(deck, cards, DuplicateScope::default())
+                .await?
+        }
+        .into_iter();
+        let mut updated = if updates.is_empty() {
+            Vec::new()
+        } else {
+            backend.update_notes(updates).await?
+        }
+        .into_iter();
+        let mut tags_updated = if tag_updates.is_empty() {
+            Vec::new()
+        } else {
+            backend.update_tags(tag_updates).await?
+        }
+        .into_iter();
+        let deleted = if deletions.is_empty() {
+            Ok(())
+        } else {
+            backend.delete_notes(deletions).await
+        };
+
+        for change in chunk {
+            let result = match &change {
+                SyncChange::Add(_) => added.next().map(|result| result.map(|_| ())),
+                // change succeeds only if both its fields and tags are updated
+                SyncChange::Update { fields, tags, .. } => {
+                    let fields = if fields.is_empty() {
+                        Some(Ok(()))
+                    } else {
+                        updated.next()
+                    };
+                    let tags = if tags.is_none() {
+                        Some(Ok(()))
+                    } else {
+                        tags_updated.next()
+                    };
+
+                    fields.zip(tags).map(|(fields, tags)| fields.and(tags))
+                }
+                SyncChange::Delete { .. } => Some(match &deleted {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(Error::Api(e.to_string())),
+                }),
+            }
+            .ok_or_else(|| Error::Response("no result of change in response".to_string()))?;
+
+            match result {
+                Ok(()) => {
+                    let references: Vec<&str> = match &change {
+                        SyncChange::Add(card) => media::card_references(card).collect(),
+                        SyncChange::Update { fields, .. } => fields
+                            .values()
+                            .flat_map(|value| media::references(value))
+                            .collect(),
+                        SyncChange::Delete { .. } => Vec::new(),
+                    };
+
+                    for name in references {
+                        if let Some(path) = media.get(name)
+                            && stored.insert(name.to_string())
+                        {
+                            media::store(backend, name, path, false).await?;
+                        }
+                    }
+
+                    applied.push(change);
+                }
+                Err(e) => failures.push(SyncFailure {
+                    change,
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    Ok((applied, failures, stored.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let notes = vec![
+            Card::basic("Q1", "A1").with_id(Some(1)),
+            Card::basic("Q2", "A2").with_id(Some(2)),
+            Card::basic("Q3", "A3").with_id(Some(3)),
+            Card::cloze("{{c1::Kyiv}}", "").with_id(Some(4)),
+        ];
+        let cards = vec![
+            // renamed front, matched by id
+            Card::basic("Q1 renamed", "A1").with_id(Some(1)),
+            // no id, matched by front
+            Card::basic("Q2", "A2 changed"),
+            // unknown id, falls back to front
+            Card::cloze("{{c1::Kyiv}}", "").with_id(Some(100)),
+            Card::basic("Q5", "A5").with_id(Some(100)),
+        ];
+
+        let (changes, unchanged) = plan(notes.clone(), cards.clone(), false);
+
+        assert_eq!(unchanged, 1);
+        assert_eq!(
+            changes,
+            vec![
+                SyncChange::Update {
+                    id: 1,
+                    front: "Q1".to_string(),
+                    fields: Fields::from([("Front".to_string(), "Q1 renamed".to_string())]),
+                    tags: None,
+                },
+                SyncChange::Update {
+                    id: 2,
+                    front: "Q2".to_string(),
+                    fields: Fields::from([("Back".to_string(), "A2 changed".to_string())]),
+                    tags: None,
+                },
+                SyncChange::Add(Card::basic("Q5", "A5")),
+            ]
+        );
+
+        let (changes, _) = plan(notes, cards, true);
+
+        assert_eq!(
+            changes.last(),
+            Some(&SyncChange::Delete {
+                id: 3,
+                front: "Q3".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_plan_tags() {
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let notes = vec![
+            Card::basic("Q1", "A1")
+                .with_tags(tags(&["a", "b"]))
+                .with_id(Some(1)),
+            Card::basic("Q2", "A2")
+                .with_tags(tags(&["a"]))
+                .with_id(Some(2)),
+        ];
+        let cards = vec![
+            // the same tags in other order
+            Card::basic("Q1", "A1").with_tags(tags(&["b", "a"])),
+            Card::basic("Q2", "A2").with_tags(tags(&["a", "c"])),
+        ];
+
+        let (changes, unchanged) = plan(notes, cards, false);
+
+        assert_eq!(unchanged, 1);
+        assert_eq!(
+            changes,
+            vec![SyncChange::Update {
+                id: 2,
+                front: "Q2".to_string(),
+                fields: Fields::new(),
+                tags: Some(tags(&["a", "c"])),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_other_model_by_id() {
+        let notes = vec![
+            Card::cloze("{{c1::Kyiv}}", "").with_id(Some(1)),
+            Card::basic("Q", "A").with_id(Some(2)),
+        ];
+        // id of cloze note, but card is basic, so it's matched by front
+        let cards = vec![Card::basic("Q", "A changed").with_id(Some(1))];
+
+        let (changes, unchanged) = plan(notes, cards, false);
+
+        assert_eq!(unchanged, 0);
+        assert_eq!(
+            changes,
+            vec![SyncChange::Update {
+                id: 2,
+                front: "Q".to_string(),
+                fields: Fields::from([("Back".to_string(), "A changed".to_string())]),
+                tags: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_tags() {
+        use crate::memory::MemoryBackend;
+
+        let backend = MemoryBackend::default();
+
+        backend
+            .add_notes(
+                "Deck",
+                vec![Card::basic("Q", "A")],
+                DuplicateScope::default(),
+            )
+            .await
+            .expect("failed to add notes");
+
+        let changes = vec![
+            SyncChange::Update {
+                id: 1,
+                front: "Q".to_string(),
+                fields: Fields::new(),
+                tags: Some(vec!["new".to_string()]),
+            },
+            SyncChange::Update {
+                id: 100,
+                front: "Q100".to_string(),
+                fields: Fields::new(),
+                tags: Some(vec!["new".to_string()]),
+            },
+        ];
+
+        let (applied, failures, _) = apply(&backend, "Deck", changes, &LocalMedia::new())
+            .await
+            .expect("failed to apply changes");
+
+        assert_eq!((applied.len(), failures.len()), (1, 1));
+        assert_eq!(
+            backend.cards("Deck").await.expect("failed to get cards"),
+            vec![
+                Card::basic("Q", "A")
+                    .with_tags(vec!["new".to_string()])
+                    .with_id(Some(1))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_uploads_media() {
+        use crate::memory::MemoryBackend;
+
+        let dir = tempfile::tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("cat.mp3");
+        std::fs::write(&path, b"meow").expect("failed to write audio");
+
+        let backend = MemoryBackend::default();
+        let media = LocalMedia::from([("cat.mp3".to_string(), path)]);
+        let changes = vec![
+            SyncChange::Add(Card::basic("кіт", "cat [sound:cat.mp3]")),
+            SyncChange::Add(Card::basic("", "[sound:cat.mp3]")),
+        ];
+
+        backend
+            .create_deck("Sounds")
+            .await
+            .expect("failed to create deck");
+
+        let (applied, failures, stored) = apply(&backend, "Sounds", changes, &media)
+            .await
+            .expect("failed to apply changes");
+
+        assert_eq!((applied.len(), failures.len(), stored), (1, 1, 1));
+        assert_eq!(
+            backend
+                .retrieve_media("cat.mp3")
+                .await
+                .expect("failed to retrieve media"),
+            Some(b"meow".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_with_markdown_options() {
+        use crate::{
+            CsvOptions, MarkdownOptions, Separator, ToolController, memory::MemoryBackend,
+        };
+
+        let dir = tempfile::tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("Words.md");
+        std::fs::write(&path, "1. Q1 | A1\n2. Q2 | A2 - B2\n").expect("failed to write file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let md_options = MarkdownOptions {
+            separator: Separator::Pipe,
+            ..MarkdownOptions::default()
+        };
+        let report = controller
+            .sync_deck(
+                &path,
+                &md_options,
+                &CsvOptions::default(),
+                &SyncOptions::default(),
+            )
+            .await
+            .expect("failed to sync deck");
+
+        assert_eq!(report.deck, "Words");
+        assert_eq!(report.changes.len(), 2);
+        assert_eq!(
+            controller
+                .backend
+                .cards("Words")
+                .await
+                .expect("failed to get cards"),
+            vec![
+                Card::basic("Q1", "A1").with_id(Some(1)),
+                Card::basic("Q2", "A2 - B2").with_id(Some(2)),
+            ]
+        );
+        assert!(
+            controller
+                .diff_deck("Words", &path, &md_options, &CsvOptions::default())
+                .await
+                .expect("failed to diff deck")
+                .is_empty()
+        );
+        assert!(
+            controller
+                .diff_deck(
+                    "Words",
+                    &path,
+                    &MarkdownOptions::default(),
+                    &CsvOptions::default()
+                )
+                .await
+                .is_err()
+        );
+
+        let md_options = MarkdownOptions {
+            subdecks: true,
+            ..md_options
+        };
+
+        assert!(matches!(
+            controller
+                .sync_deck(&path, &md_options, &CsvOptions::default(), &SyncOptions::default())
+                .await,
+            Err(Error::Invalid(message)) if message.contains("subdecks")
+        ));
+        assert!(
+            controller
+                .diff_deck("Words", &path, &md_options, &CsvOptions::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_with_csv_options() {
+        use crate::{CsvOptions, MarkdownOptions, ToolController, memory::MemoryBackend};
+
+        let dir = tempfile::tempdir().expect("failed to create temp directory");
+        let path = dir.path().join("Words.csv");
+        std::fs::write(&path, "Q1;A1, B1\nQ2;A2\n").expect("failed to write file");
+
+        let controller = ToolController::with_backend(MemoryBackend::default());
+        let csv_options = CsvOptions {
+            delimiter: b';',
+            has_header: false,
+            model: Some("Basic (and reversed card)".to_string()),
+        };
+        let report = controller
+            .sync_deck(
+                &path,
+                &MarkdownOptions::default(),
+                &csv_options,
+                &SyncOptions::default(),
+            )
+            .await
+            .expect("failed to sync deck");
+
+        assert_eq!(report.changes.len(), 2);
+        let cards = controller
+            .backend
+            .cards("Words")
+            .await
+            .expect("failed to get cards");
+
+        assert_eq!(
+            cards
+                .iter()
+                .map(|card| (card.model.as_str(), card.front(), card.back()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Basic (and reversed card)", "Q1", "A1, B1"),
+                ("Basic (and reversed card)", "Q2", "A2"),
+            ]
+        );
+        assert!(
+            controller
+                .diff_deck("Words", &path, &MarkdownOptions::default(), &csv_options)
+                .await
+                .expect("failed to diff deck")
+                .is_empty()
+        );
+        assert!(
+            !controller
+                .diff_deck(
+                    "Words",
+                    &path,
+                    &MarkdownOptions::default(),
+                    &CsvOptions {
+                        has_header: false,
+                        ..CsvOptions::default()
+                    }
+                )
+                .await
+                .map(|diff| diff.is_empty())
+                .unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_plan_duplicated_fronts() {
+        let notes = vec![
+            Card::basic("Q", "A1").with_id(Some(1)),
+            Card::basic("Q", "A2").with_id(Some(2)),
+        ];
+        let cards = vec![Card::basic("Q", "A1"), Card::basic("Q", "A2")];
+
+        let (changes, unchanged) = plan(notes, cards, true);
+
+        assert_eq!(unchanged, 2);
+        assert!(changes.is_empty());
+    }
+}
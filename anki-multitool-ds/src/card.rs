@@ -1,11 +1,341 @@
-use anyhow::{Error, Result, anyhow};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::{fmt, str::FromStr};
 
-#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+use crate::error::{Error, Result};
+
+pub const BASIC_MODEL: &str = "Basic";
+pub const FRONT_FIELD: &str = "Front";
+pub const BACK_FIELD: &str = "Back";
+pub const CLOZE_MODEL: &str = "Cloze";
+pub const TEXT_FIELD: &str = "Text";
+pub const BACK_EXTRA_FIELD: &str = "Back Extra";
+
+/// Ordered map of field name to field value, order follows fields of note type.
+pub type Fields = IndexMap<String, String>;
+
+/// Checks whether text contains at least one cloze deletion like `{{c1::answer}}`.
+pub fn has_cloze_deletions(text: &str) -> bool {
+    text.match_indices("{{c").any(|(i, _)| {
+        let rest = &text[i + 3..];
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+
+        digits > 0 && rest[digits..].starts_with("::")
+    })
+}
+
+/// Separator of field values in one-line card format like "front - back". A backslash before
+/// ASCII punctuation makes the character literal, so `\-` or `\|` never separate values.
+#[derive(Deserialize, Default, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Separator {
+    /// " - "
+    #[default]
+    Dash,
+    /// " :: "
+    DoubleColon,
+    /// "|"
+    Pipe,
+    /// "? ", question mark is kept as the end of the first value. Text without "? " is split
+    /// by " - ", so values which don't end with "?" can be written too.
+    Question,
+}
+
+impl Separator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dash => " - ",
+            Self::DoubleColon => " :: ",
+            Self::Pipe => "|",
+            Self::Question => "? ",
+        }
+    }
+
+    /// Splits string into at most `n` values by unescaped separator, separators inside of
+    /// `{{...}}` (cloze deletions) are ignored. Escapes are removed from values.
+    pub fn split(self, s: &str, n: usize) -> Vec<String> {
+        let pattern = self.as_str();
+        let mut values = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        let mut i = 0;
+
+        while i < s.len() && values.len() + 1 < n {
+            let rest = &s[i..];
+
+            if is_escape(rest) {
+                i += 2;
+            } else if rest.starts_with("{{") {
+                depth += 1;
+                i += 2;
+            } else if rest.starts_with("}}") && depth > 0 {
+                depth -= 1;
+                i += 2;
+            } else if rest.starts_with(pattern) && depth == 0 {
+                let end = if self == Self::Question { i + 1 } else { i };
+
+                values.push(unescape(&s[start..end]));
+                i += pattern.len();
+                start = i;
+            } else if rest.trim_end() == pattern.trim_end() && self != Self::Question && depth == 0
+            {
+                // separator at the end of trimmed text leaves the last value empty
+                values.push(unescape(&s[start..i]));
+                i = s.len();
+                start = i;
+            } else {
+                i += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+            }
+        }
+
+        if self == Self::Question && values.is_empty() && n > 1 {
+            return Self::Dash.split(s, n);
+        }
+
+        values.push(unescape(&s[start..]));
+        values
+    }
+
+    /// Escapes occurrences of separator in value which is followed by separator, including the
+    /// ones completed by it like "a -" followed by " - ".
+    pub fn escape(self, value: &str) -> String {
+        let pattern = self.as_str();
+        let punct = pattern
+            .find(|c: char| c.is_ascii_punctuation())
+            .unwrap_or(0);
+        let followed = format!("{value}{pattern}");
+        let mut escaped = String::with_capacity(value.len());
+        let mut i = 0;
+
+        while i < value.len() {
+            if followed[i..].starts_with(pattern) {
+                escaped.push_str(&pattern[..punct]);
+                escaped.push('\\');
+                i += punct;
+            }
+
+            let c = value[i..].chars().next().unwrap_or_default();
+
+            escaped.push(c);
+            i += c.len_utf8();
+        }
+
+        escaped
+    }
+}
+
+impl FromStr for Separator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dash" | "-" | " - " => Ok(Self::Dash),
+            "double-colon" | "::" | " :: " => Ok(Self::DoubleColon),
+            "pipe" | "|" => Ok(Self::Pipe),
+            "question" | "?" | "? " => Ok(Self::Question),
+            _ => Err(Error::invalid(format!(
+                "unknown separator '{s}', expected 'dash', 'double-colon', 'pipe' or 'question'"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Separator {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// Checks whether string starts with a backslash escape of ASCII punctuation.
+fn is_escape(s: &str) -> bool {
+    s.strip_prefix('\\')
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_punctuation()))
+}
+
+/// Removes backslash escapes of ASCII punctuation, the way values are unescaped along with
+/// splitting.
+pub fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if is_escape(&s[i..]) {
+            unescaped.extend(chars.next().map(|(_, c)| c));
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    unescaped
+}
+
+/// Returns names of fields for note types which are shipped with Anki.
+pub fn known_model_fields(model: &str) -> Option<&'static [&'static str]> {
+    match model {
+        "Basic" | "Basic (and reversed card)" | "Basic (type in the answer)" => {
+            Some(&["Front", "Back"])
+        }
+        "Basic (optional reversed card)" => Some(&["Front", "Back", "Add Reverse"]),
+        "Cloze" => Some(&["Text", "Back Extra"]),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "CardRepr", into = "CardRepr")]
 pub struct Card {
-    pub front: String,
-    pub back: String,
+    /// Id of note in Anki, known for cards exported from a deck.
+    pub id: Option<u64>,
+    pub model: String,
+    pub fields: Fields,
+    pub tags: Vec<String>,
+}
+
+impl Card {
+    pub fn new(model: impl Into<String>, fields: Fields) -> Self {
+        Self {
+            id: None,
+            model: model.into(),
+            fields,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_id(mut self, id: Option<u64>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Appends tags which card doesn't have yet, order of existing tags is kept.
+    pub fn add_tags<'a>(&mut self, tags: impl IntoIterator<Item = &'a String>) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    pub fn basic(front: impl Into<String>, back: impl Into<String>) -> Self {
+        Self::new(
+            BASIC_MODEL,
+            Fields::from([
+                (FRONT_FIELD.to_string(), front.into()),
+                (BACK_FIELD.to_string(), back.into()),
+            ]),
+        )
+    }
+
+    pub fn cloze(text: impl Into<String>, extra: impl Into<String>) -> Self {
+        Self::new(
+            CLOZE_MODEL,
+            Fields::from([
+                (TEXT_FIELD.to_string(), text.into()),
+                (BACK_EXTRA_FIELD.to_string(), extra.into()),
+            ]),
+        )
+    }
+
+    /// Parses a string in the format "value - value - ..." into a Card of given note type,
+    /// values are assigned to fields in order, missing trailing fields are left empty.
+    pub fn parse(s: &str, model: &str, field_names: &[&str]) -> Result<Self> {
+        Self::parse_with(s, model, field_names, Separator::default())
+    }
+
+    /// Same as `parse`, but values are separated by given separator.
+    pub fn parse_with(
+        s: &str,
+        model: &str,
+        field_names: &[&str],
+        separator: Separator,
+    ) -> Result<Self> {
+        if field_names.is_empty() {
+            return Err(Error::invalid(format!("note type '{model}' has no fields")));
+        }
+
+        let values = separator.split(s, field_names.len());
+        let last = values.len() - 1;
+
+        if values.iter().all(|v| v.trim().is_empty()) {
+            return Err(Error::invalid(format!(
+                "invalid card format, all fields are empty: {s}"
+            )));
+        }
+
+        Ok(Self::new(
+            model,
+            field_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    // whitespace is trimmed only next to separators, ends of text are kept
+                    let value = values.get(i).map(String::as_str).unwrap_or("");
+                    let value = if i > 0 { value.trim_start() } else { value };
+                    let value = if i < last { value.trim_end() } else { value };
+
+                    (name.to_string(), value.to_string())
+                })
+                .collect(),
+        ))
+    }
+
+    /// Parses a string in the format "front - back" into a "Basic" Card, values are separated
+    /// by given separator. Front can't be empty, back can be empty only after separator, like in
+    /// "front - ".
+    pub fn parse_basic(s: &str, separator: Separator) -> Result<Self> {
+        let card = Self::parse_with(s, BASIC_MODEL, &[FRONT_FIELD, BACK_FIELD], separator)?;
+
+        if card.front().is_empty() || separator.split(s, 2).len() < 2 {
+            return Err(Error::invalid(format!(
+                "invalid card format, expected 'front{}back', got: {s}",
+                separator.as_str()
+            )));
+        }
+
+        Ok(card)
+    }
+
+    pub fn is_basic(&self) -> bool {
+        self.model == BASIC_MODEL
+            && self.fields.len() == 2
+            && self.fields.contains_key(FRONT_FIELD)
+            && self.fields.contains_key(BACK_FIELD)
+    }
+
+    pub fn is_cloze(&self) -> bool {
+        self.model == CLOZE_MODEL
+            && self.fields.len() == 2
+            && self.fields.contains_key(TEXT_FIELD)
+            && self.fields.contains_key(BACK_EXTRA_FIELD)
+    }
+
+    /// Value of the first field of note, for "Basic" note type it's "Front".
+    pub fn front(&self) -> &str {
+        self.fields
+            .get_index(0)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or("")
+    }
+
+    /// Value of the second field of note, for "Basic" note type it's "Back".
+    pub fn back(&self) -> &str {
+        self.fields
+            .get_index(1)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or("")
+    }
+}
+
+impl Default for Card {
+    fn default() -> Self {
+        Self::basic("", "")
+    }
 }
 
 impl FromStr for Card {
@@ -13,64 +343,187 @@ impl FromStr for Card {
 
     /// Parses a string in the format "front - back" into a Card.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.contains('-') || s.trim().starts_with('-') || s.trim().ends_with('-') {
-            return Err(anyhow!(
-                "invalid card format, expected 'front - back', got: {s}"
-            ));
+        Self::parse_basic(s, Separator::default())
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, value) in self.fields.values().enumerate() {
+            if i > 0 {
+                f.write_str(Separator::default().as_str())?;
+            }
+            write!(f, "{value}")?;
         }
 
-        let parts: Vec<&str> = s.splitn(2, "-").collect();
+        Ok(())
+    }
+}
+
+/// Serialized shape of card, "Basic" notes are written as `{"front": .., "back": ..}`,
+/// "Cloze" notes as `{"cloze": .., "extra": ..}`, other note types as `{"model": .., "fields": {..}}`.
+#[derive(Deserialize, Serialize, Default)]
+struct CardRepr {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    front: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    back: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cloze: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extra: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<Fields>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl TryFrom<CardRepr> for Card {
+    type Error = Error;
 
-        let [front, back] = parts.as_slice() else {
-            return Err(anyhow!(
-                "invalid card format, expected 'front - back', got: {s}"
-            ));
+    fn try_from(mut repr: CardRepr) -> Result<Self> {
+        if let Some(tag) = repr
+            .tags
+            .iter()
+            .find(|t| t.is_empty() || t.contains(char::is_whitespace))
+        {
+            return Err(Error::invalid(format!(
+                "invalid tag '{tag}', tags can't be empty or contain spaces"
+            )));
+        }
+
+        let tags = std::mem::take(&mut repr.tags);
+        let id = repr.id.take();
+
+        let card = match repr {
+            CardRepr {
+                front: Some(front),
+                back: Some(back),
+                cloze: None,
+                extra: None,
+                model,
+                fields: None,
+                ..
+            } => {
+                let model = model.unwrap_or_else(|| BASIC_MODEL.to_string());
+                let mut card = Card::basic(front, back);
+
+                card.model = model;
+                card
+            }
+            CardRepr {
+                front: None,
+                back: None,
+                cloze: Some(cloze),
+                extra,
+                model: None,
+                fields: None,
+                ..
+            } => {
+                if !has_cloze_deletions(&cloze) {
+                    return Err(Error::invalid(format!(
+                        "cloze card has no cloze deletions: {cloze}"
+                    )));
+                }
+
+                Card::cloze(cloze, extra.unwrap_or_default())
+            }
+            CardRepr {
+                front: None,
+                back: None,
+                cloze: None,
+                extra: None,
+                model: Some(model),
+                fields: Some(fields),
+                ..
+            } => {
+                if fields.is_empty() {
+                    return Err(Error::invalid(format!(
+                        "note of type '{model}' has no fields"
+                    )));
+                }
+
+                Card::new(model, fields)
+            }
+            _ => {
+                return Err(Error::invalid(
+                    "invalid card, expected either 'front' and 'back', 'cloze' or 'model' and 'fields'",
+                ));
+            }
         };
 
-        Ok(Self {
-            front: front.trim().to_string(),
-            back: back.trim().to_string(),
-        })
+        Ok(card.with_tags(tags).with_id(id))
     }
 }
 
-impl fmt::Display for Card {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} - {}", self.front, self.back)
+impl From<Card> for CardRepr {
+    fn from(card: Card) -> Self {
+        if card.is_basic() {
+            Self {
+                id: card.id,
+                front: Some(card.front().to_string()),
+                back: Some(card.back().to_string()),
+                tags: card.tags,
+                ..Self::default()
+            }
+        } else if card.is_cloze() {
+            let extra = card.back().to_string();
+
+            Self {
+                id: card.id,
+                cloze: Some(card.front().to_string()),
+                extra: (!extra.is_empty()).then_some(extra),
+                tags: card.tags,
+                ..Self::default()
+            }
+        } else {
+            Self {
+                id: card.id,
+                model: Some(card.model),
+                fields: Some(card.fields),
+                tags: card.tags,
+                ..Self::default()
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod card_tests {
-    use super::Card;
+    use super::{Card, Fields, Separator, has_cloze_deletions};
     use std::str::FromStr;
 
     #[test]
     pub fn test_card_from_str() {
-        let mut card = Card::from_str("Front-Back ").expect("wrong format of str");
+        let mut card = Card::from_str("What is 3-2? - 1").expect("wrong format of str");
 
-        assert_eq!(card.front, "Front");
-        assert_eq!(card.back, "Back");
+        assert_eq!(card.front(), "What is 3-2?");
+        assert_eq!(card.back(), "1");
 
-        card = Card::from_str("Front - Back ").expect("wrong format of str");
+        card = Card::from_str("Front  -  Back ").expect("wrong format of str");
 
-        assert_eq!(card.front, "Front");
-        assert_eq!(card.back, "Back");
+        assert_eq!(card.front(), "Front");
+        assert_eq!(card.back(), "Back ");
 
         card = Card::from_str("Front Text - Back Text").expect("wrong format of str");
 
-        assert_eq!(card.front, "Front Text");
-        assert_eq!(card.back, "Back Text");
+        assert_eq!(card.front(), "Front Text");
+        assert_eq!(card.back(), "Back Text");
 
         card = Card::from_str("Front - Back - Text").expect("wrong format of str");
 
-        assert_eq!(card.front, "Front");
-        assert_eq!(card.back, "Back - Text");
+        assert_eq!(card.front(), "Front");
+        assert_eq!(card.back(), "Back - Text");
 
         card = Card::from_str("Front - - Back Text").expect("wrong format of str");
 
-        assert_eq!(card.front, "Front");
-        assert_eq!(card.back, "- Back Text");
+        assert_eq!(card.front(), "Front");
+        assert_eq!(card.back(), "- Back Text");
+        assert!(card.is_basic());
     }
 
     #[test]
@@ -78,7 +531,286 @@ mod card_tests {
         let mut card = Card::from_str("Front Back");
         assert!(card.is_err());
 
-        card = Card::from_str("Front -");
+        card = Card::from_str(" - Back");
+        assert!(card.is_err());
+
+        card = Card::from_str("Front-Back");
         assert!(card.is_err())
     }
+
+    #[test]
+    pub fn test_card_separators() {
+        let card = Card::parse_basic("Front :: Back - Text", Separator::DoubleColon)
+            .expect("wrong format of str");
+
+        assert_eq!(card.front(), "Front");
+        assert_eq!(card.back(), "Back - Text");
+
+        let card = Card::parse_basic("Is it? Yes? Really", Separator::Question)
+            .expect("wrong format of str");
+
+        assert_eq!(card.front(), "Is it?");
+        assert_eq!(card.back(), "Yes? Really");
+
+        let card =
+            Card::parse_basic(r"a \| b \- c|d \\", Separator::Pipe).expect("wrong format of str");
+
+        assert_eq!(card.front(), "a | b - c");
+        assert_eq!(card.back(), r"d \");
+
+        let card = Card::parse_basic(r"x \- y - z", Separator::Dash).expect("wrong format of str");
+
+        assert_eq!(card.front(), "x - y");
+        assert_eq!(card.back(), "z");
+
+        assert!(Card::parse_basic("Is it?", Separator::Question).is_err());
+        assert!(Card::parse_basic(r"a \| b", Separator::Pipe).is_err());
+        assert_eq!("pipe".parse::<Separator>().ok(), Some(Separator::Pipe));
+        assert_eq!(
+            " :: ".parse::<Separator>().ok(),
+            Some(Separator::DoubleColon)
+        );
+        assert!("comma".parse::<Separator>().is_err());
+    }
+
+    #[test]
+    pub fn test_card_whitespace() {
+        let card =
+            Card::parse_basic("  indented | trailing  ", Separator::Pipe).expect("wrong format");
+
+        assert_eq!(card.front(), "  indented");
+        assert_eq!(card.back(), "trailing  ");
+
+        let card = Card::parse("Front  -   Back\n", "Basic", &["Front", "Back"])
+            .expect("wrong format of str");
+
+        assert_eq!(card.front(), "Front");
+        assert_eq!(card.back(), "Back\n");
+
+        let card =
+            Card::parse_basic("Capital - Kyiv", Separator::Question).expect("wrong format of str");
+
+        assert_eq!(card.front(), "Capital");
+        assert_eq!(card.back(), "Kyiv");
+
+        // whitespace next to separator is a part of it, so it isn't kept in values
+        let card = Card::parse_basic("Q   -   A", Separator::Dash).expect("wrong format of str");
+
+        assert_eq!(card.front(), "Q");
+        assert_eq!(card.back(), "A");
+
+        let card =
+            Card::parse_basic("Q  ::  A", Separator::DoubleColon).expect("wrong format of str");
+
+        assert_eq!(card.front(), "Q");
+        assert_eq!(card.back(), "A");
+    }
+
+    #[test]
+    pub fn test_card_empty_back() {
+        for (s, separator) in [
+            ("Q - ", Separator::Dash),
+            ("Q -", Separator::Dash),
+            ("Q ::", Separator::DoubleColon),
+            ("Q|", Separator::Pipe),
+            ("Q -", Separator::Question),
+        ] {
+            let card = Card::parse_basic(s, separator).expect("wrong format of str");
+
+            assert_eq!(card.front(), "Q", "{s}");
+            assert_eq!(card.back(), "", "{s}");
+        }
+
+        assert!(Card::parse_basic("Q", Separator::Dash).is_err());
+        assert!(Card::parse_basic(r"Q \-", Separator::Dash).is_err());
+        assert!(Card::parse_basic("Q?", Separator::Question).is_err());
+    }
+
+    #[test]
+    pub fn test_separator_escape() {
+        for (separator, value) in [
+            (Separator::Dash, "a - b -"),
+            (Separator::DoubleColon, "std::io :: x :"),
+            (Separator::Pipe, "a|b|"),
+            (Separator::Question, "Is it? Yes"),
+        ] {
+            let escaped = separator.escape(value);
+            let joined = format!("{escaped}{}tail", separator.as_str());
+            let expected = match separator {
+                Separator::Question => format!("{value}?"),
+                _ => value.to_string(),
+            };
+
+            assert_eq!(separator.split(&joined, 2), [expected, "tail".to_string()]);
+        }
+
+        assert_eq!(Separator::Pipe.escape("a|b"), r"a\|b");
+        assert_eq!(Separator::Dash.escape("a - b"), r"a \- b");
+        assert_eq!(Separator::Dash.escape("3-2"), "3-2");
+    }
+
+    #[test]
+    pub fn test_card_parse_with_model() {
+        let card = Card::parse(
+            "Front - Back - y",
+            "Basic (optional reversed card)",
+            &["Front", "Back", "Add Reverse"],
+        )
+        .expect("wrong format of str");
+
+        assert_eq!(card.model, "Basic (optional reversed card)");
+        assert_eq!(
+            card.fields.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("Front".to_string(), "Front".to_string()),
+                ("Back".to_string(), "Back".to_string()),
+                ("Add Reverse".to_string(), "y".to_string()),
+            ]
+        );
+
+        let card =
+            Card::parse("Only text", "Custom", &["Text", "Extra"]).expect("wrong format of str");
+
+        assert_eq!(card.front(), "Only text");
+        assert_eq!(card.back(), "");
+
+        assert!(Card::parse(" - ", "Custom", &["Text", "Extra"]).is_err());
+        assert!(Card::parse("Text", "Custom", &[]).is_err());
+    }
+
+    #[test]
+    pub fn test_card_serde() {
+        let basic = Card::basic("Q", "A");
+        let custom = Card::new(
+            "Basic (and reversed card)",
+            Fields::from([
+                ("Front".to_string(), "Q".to_string()),
+                ("Back".to_string(), "A".to_string()),
+            ]),
+        );
+
+        assert_eq!(
+            serde_json::to_string(&basic).expect("failed to serialize card"),
+            r#"{"front":"Q","back":"A"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&custom).expect("failed to serialize card"),
+            r#"{"model":"Basic (and reversed card)","fields":{"Front":"Q","Back":"A"}}"#
+        );
+
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"front":"Q","back":"A"}"#)
+                .expect("failed to deserialize card"),
+            basic
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(
+                r#"{"model":"Basic (and reversed card)","fields":{"Front":"Q","Back":"A"}}"#
+            )
+            .expect("failed to deserialize card"),
+            custom
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(
+                r#"{"model":"Basic (and reversed card)","front":"Q","back":"A"}"#
+            )
+            .expect("failed to deserialize card"),
+            custom
+        );
+
+        assert!(serde_json::from_str::<Card>(r#"{"front":"Q"}"#).is_err());
+        assert!(serde_json::from_str::<Card>(r#"{"model":"Cloze","fields":{}}"#).is_err());
+        assert!(
+            serde_json::from_str::<Card>(r#"{"front":"Q","back":"A","fields":{"a":"b"}}"#).is_err()
+        );
+    }
+
+    #[test]
+    pub fn test_cloze_card() {
+        assert!(has_cloze_deletions(
+            "{{c1::Kyiv}} is the capital of Ukraine"
+        ));
+        assert!(has_cloze_deletions("a {{c12::b::hint}} c"));
+        assert!(!has_cloze_deletions("{{c::Kyiv}} {{1::a}} {c1::a}"));
+
+        let card = Card::parse(
+            "{{c1::T-cell}} is a type of {{c2::lymphocyte}} - immune-system",
+            "Cloze",
+            &["Text", "Back Extra"],
+        )
+        .expect("wrong format of str");
+
+        assert!(card.is_cloze());
+        assert_eq!(
+            card.front(),
+            "{{c1::T-cell}} is a type of {{c2::lymphocyte}}"
+        );
+        assert_eq!(card.back(), "immune-system");
+
+        let cloze = Card::cloze("{{c1::Kyiv}} is the capital of Ukraine", "");
+
+        assert_eq!(
+            serde_json::to_string(&cloze).expect("failed to serialize card"),
+            r#"{"cloze":"{{c1::Kyiv}} is the capital of Ukraine"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"cloze":"{{c1::Kyiv}} is the capital of Ukraine"}"#)
+                .expect("failed to deserialize card"),
+            cloze
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"cloze":"{{c1::Kyiv}}","extra":"city"}"#)
+                .expect("failed to deserialize card"),
+            Card::cloze("{{c1::Kyiv}}", "city")
+        );
+
+        assert!(serde_json::from_str::<Card>(r#"{"cloze":"no deletions"}"#).is_err());
+        assert!(serde_json::from_str::<Card>(r#"{"cloze":"{{c1::a}}","back":"b"}"#).is_err());
+    }
+
+    #[test]
+    pub fn test_card_tags() {
+        let mut card = Card::basic("Q", "A").with_tags(vec!["geography".to_string()]);
+
+        assert_eq!(
+            serde_json::to_string(&card).expect("failed to serialize card"),
+            r#"{"front":"Q","back":"A","tags":["geography"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"front":"Q","back":"A","tags":["geography"]}"#)
+                .expect("failed to deserialize card"),
+            card
+        );
+
+        card.add_tags(&["imported".to_string(), "geography".to_string()]);
+        assert_eq!(card.tags, vec!["geography", "imported"]);
+
+        assert!(
+            serde_json::from_str::<Card>(r#"{"front":"Q","back":"A","tags":["two words"]}"#)
+                .is_err()
+        );
+        assert!(serde_json::from_str::<Card>(r#"{"front":"Q","back":"A","tags":[""]}"#).is_err());
+    }
+
+    #[test]
+    pub fn test_card_id() {
+        let card = Card::cloze("{{c1::Kyiv}}", "").with_id(Some(1700000000000));
+
+        assert_eq!(
+            serde_json::to_string(&card).expect("failed to serialize card"),
+            r#"{"id":1700000000000,"cloze":"{{c1::Kyiv}}"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"id":1700000000000,"cloze":"{{c1::Kyiv}}"}"#)
+                .expect("failed to deserialize card"),
+            card
+        );
+        assert_eq!(
+            serde_json::from_str::<Card>(r#"{"front":"Q","back":"A"}"#)
+                .expect("failed to deserialize card")
+                .id,
+            None
+        );
+        assert!(serde_json::from_str::<Card>(r#"{"id":"x","front":"Q","back":"A"}"#).is_err());
+    }
 }
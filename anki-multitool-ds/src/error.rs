@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::http::response::DUPLICATE_NOTE_ERROR;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors of library crates, variants let callers react to failure without matching messages.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// AnkiConnect can't be reached, usually Anki isn't running.
+    #[error("failed to connect to AnkiConnect at {url}: {message}")]
+    Connection { url: String, message: String },
+    /// Error returned by AnkiConnect for requested action.
+    #[error("{0}")]
+    Api(String),
+    /// AnkiConnect refused to add note with the same first field as existing one.
+    #[error("{DUPLICATE_NOTE_ERROR}")]
+    DuplicateNote,
+    /// Response of AnkiConnect which doesn't match request.
+    #[error("{0}")]
+    Response(String),
+    #[error("deck '{0}' already exists")]
+    DeckExists(String),
+    #[error("deck '{0}' does not exist")]
+    DeckNotFound(String),
+    #[error("file {} already exists", .0.display())]
+    FileExists(PathBuf),
+    #[error("file {} does not exist", .0.display())]
+    FileNotFound(PathBuf),
+    /// Malformed content of file, `line` and `column` are 1-based position where problem was
+    /// found, if it's known. Columns are counted in characters.
+    #[error("{}: {message}", location(path, *line, *column))]
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// Card, note type or options which can't be handled.
+    #[error("{0}")]
+    Invalid(String),
+    /// Failure of SQLite collection or zip archive of Anki package.
+    #[error("{0}")]
+    Collection(String),
+    /// Atomic import failed and changes made by it were removed.
+    #[error("{0}, import was rolled back")]
+    RolledBack(Box<Error>),
+    #[error("{error}, failed to roll back import: {rollback}")]
+    RollbackFailed {
+        error: Box<Error>,
+        rollback: Box<Error>,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl Error {
+    pub fn parse<P: AsRef<Path>>(path: P, line: Option<usize>, message: impl ToString) -> Self {
+        Self::Parse {
+            path: path.as_ref().to_path_buf(),
+            line,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn parse_at<P: AsRef<Path>>(
+        path: P,
+        line: usize,
+        column: usize,
+        message: impl ToString,
+    ) -> Self {
+        Self::Parse {
+            path: path.as_ref().to_path_buf(),
+            line: Some(line),
+            column: Some(column),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn invalid(message: impl ToString) -> Self {
+        Self::Invalid(message.to_string())
+    }
+}
+
+/// 1-based line and column of byte offset in text, columns are counted in characters.
+pub fn text_position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+fn location(path: &Path, line: Option<usize>, column: Option<usize>) -> String {
+    match (line, column) {
+        (Some(line), Some(column)) => format!("{}:{line}:{column}", path.display()),
+        (Some(line), None) => format!("{}:{line}", path.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            Error::parse("deck.md", Some(12), "unsupported format of markdown").to_string(),
+            "deck.md:12: unsupported format of markdown"
+        );
+        assert_eq!(
+            Error::parse("deck.json", None, "expected array").to_string(),
+            "deck.json: expected array"
+        );
+        assert_eq!(
+            Error::parse_at("deck.json", 2, 7, "expected ','").to_string(),
+            "deck.json:2:7: expected ','"
+        );
+        assert_eq!(
+            Error::DeckExists("Words".to_string()).to_string(),
+            "deck 'Words' already exists"
+        );
+        assert_eq!(Error::DuplicateNote.to_string(), DUPLICATE_NOTE_ERROR);
+    }
+
+    #[test]
+    fn test_text_position() {
+        let text = "deck: Слова\ncards: [\n  - серце: heart";
+
+        assert_eq!(text_position(text, 0), (1, 1));
+        // Cyrillic letters take two bytes, but one column
+        assert_eq!(text_position(text, 10), (1, 9));
+        assert_eq!(text_position(text, 20), (2, 4));
+        assert_eq!(text_position(text, 42), (3, 12));
+        assert_eq!(text_position(text, text.len() + 1), (3, 17));
+    }
+}
@@ -1,10 +1,14 @@
-use anyhow::Result;
-use serde::{Serialize, Serializer};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+use crate::{
+    card::{BASIC_MODEL, Card, Fields},
+    error::{Error, Result},
+};
 
 const API_VERSION: u16 = 6;
 const DEFAULT_DECK_NAME: &str = "Default";
-const DEFAULT_DUPLICATE_SCOPE: &str = "deck";
-const DEFAULT_MODEL_NAME: &str = "Basic";
 
 #[derive(Serialize, Eq, PartialEq)]
 #[cfg_attr(feature = "test", derive(serde::Deserialize, Debug))]
@@ -19,6 +23,22 @@ pub enum ApiMethod {
     AddNote,
     #[serde(rename = "notesInfo")]
     NotesInfo,
+    #[serde(rename = "multi")]
+    Multi,
+    #[serde(rename = "deleteNotes")]
+    DeleteNotes,
+    #[serde(rename = "deleteDecks")]
+    DeleteDecks,
+    #[serde(rename = "updateNoteFields")]
+    UpdateNoteFields,
+    #[serde(rename = "updateNoteTags")]
+    UpdateNoteTags,
+    #[serde(rename = "storeMediaFile")]
+    StoreMediaFile,
+    #[serde(rename = "retrieveMediaFile")]
+    RetrieveMediaFile,
+    #[serde(rename = "deleteMediaFile")]
+    DeleteMediaFile,
 }
 
 #[derive(Serialize)]
@@ -39,6 +59,87 @@ pub struct AddNoteParams {
     pub note: Note,
 }
 
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct MultiParams {
+    pub actions: Vec<ApiRequest>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct DeleteNotesParams {
+    pub notes: Vec<u64>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct DeleteDecksParams {
+    pub decks: Vec<String>,
+    #[serde(rename = "cardsToo")]
+    pub cards_too: bool,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct UpdateNoteFieldsParams {
+    pub note: NoteFields,
+}
+
+/// Id of existing note with its new tags, which replace old ones.
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct UpdateNoteTagsParams {
+    pub note: u64,
+    pub tags: Vec<String>,
+}
+
+/// File stored in media folder of collection, `data` is its content in base64.
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct StoreMediaFileParams {
+    pub filename: String,
+    pub data: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+/// Name of file in media folder of collection, for actions which take only the name.
+pub struct MediaFileParams {
+    pub filename: String,
+}
+
+/// Id of existing note with new values of its fields, fields which are absent are kept as is.
+#[derive(Serialize)]
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Deserialize, Debug, Default, Eq, PartialEq)
+)]
+pub struct NoteFields {
+    pub id: u64,
+    pub fields: Fields,
+}
+
 #[derive(Serialize)]
 #[cfg_attr(feature = "test", derive(serde::Deserialize, Debug, Eq, PartialEq))]
 pub struct Note {
@@ -46,27 +147,38 @@ pub struct Note {
     pub deck: String,
     #[serde(rename = "modelName")]
     pub model: String,
-    pub fields: InFields,
+    pub fields: Fields,
     pub options: Options,
     pub tags: Vec<String>,
 }
 
 impl Note {
     pub fn new(deck: String, front: String, back: String) -> Self {
+        Self::from_card(deck, Card::basic(front, back))
+    }
+
+    pub fn from_card(deck: String, card: Card) -> Self {
         Self {
+            options: Options::in_deck(&deck),
             deck,
-            fields: InFields { front, back },
-            ..Self::default()
+            model: card.model,
+            fields: card.fields,
+            tags: card.tags,
         }
     }
+
+    pub fn with_dup_scope(mut self, dup_scope: DuplicateScope) -> Self {
+        self.options.dup_scope = dup_scope;
+        self
+    }
 }
 
 impl Default for Note {
     fn default() -> Self {
         Self {
             deck: DEFAULT_DECK_NAME.to_string(),
-            model: DEFAULT_MODEL_NAME.to_string(),
-            fields: InFields::default(),
+            model: BASIC_MODEL.to_string(),
+            fields: Fields::new(),
             options: Options::default(),
             tags: Vec::new(),
         }
@@ -75,30 +187,49 @@ impl Default for Note {
 
 #[derive(Serialize, Default)]
 #[cfg_attr(feature = "test", derive(serde::Deserialize, Debug, Eq, PartialEq))]
-pub struct InFields {
-    #[serde(rename = "Front")]
-    pub front: String,
-    #[serde(rename = "Back")]
-    pub back: String,
-}
-
-#[derive(Serialize)]
-#[cfg_attr(feature = "test", derive(serde::Deserialize, Debug, Eq, PartialEq))]
 pub struct Options {
     #[serde(rename = "allowDuplicate")]
     pub allow_dups: bool,
     #[serde(rename = "duplicateScope")]
-    pub dup_scope: String,
+    pub dup_scope: DuplicateScope,
     #[serde(rename = "duplicateScopeOptions")]
     pub options: DuplicateScopeOptions,
 }
 
-impl Default for Options {
-    fn default() -> Self {
+impl Options {
+    /// Duplicates are searched in given deck, if scope is `Deck`.
+    pub fn in_deck(deck: &str) -> Self {
         Self {
-            allow_dups: false,
-            dup_scope: DEFAULT_DUPLICATE_SCOPE.to_string(),
-            options: DuplicateScopeOptions::default(),
+            options: DuplicateScopeOptions {
+                deck_name: deck.to_string(),
+                ..DuplicateScopeOptions::default()
+            },
+            ..Self::default()
+        }
+    }
+}
+
+/// Where Anki looks for a note with the same first field before adding new one.
+#[derive(Serialize, Default, Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "test", derive(serde::Deserialize))]
+pub enum DuplicateScope {
+    #[default]
+    #[serde(rename = "deck")]
+    Deck,
+    #[serde(rename = "collection")]
+    Collection,
+}
+
+impl FromStr for DuplicateScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "deck" => Ok(Self::Deck),
+            "collection" => Ok(Self::Collection),
+            _ => Err(Error::invalid(format!(
+                "unknown duplicate scope '{s}', expected 'deck' or 'collection'"
+            ))),
         }
     }
 }
@@ -158,6 +289,32 @@ pub enum Params {
     CreateDeck(CreateDeckParams),
     AddNote(AddNoteParams),
     NotesInfo(NotesInfoParams),
+    Multi(MultiParams),
+    DeleteNotes(DeleteNotesParams),
+    DeleteDecks(DeleteDecksParams),
+    UpdateNoteFields(UpdateNoteFieldsParams),
+    UpdateNoteTags(UpdateNoteTagsParams),
+    // goes before `MediaFile`, which would match its params too
+    StoreMediaFile(StoreMediaFileParams),
+    MediaFile(MediaFileParams),
+}
+
+/// Key required by AnkiConnect when `apiKey` is set in its config. It's never shown in debug
+/// output, so it can't leak into logs or error messages.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey(***)")
+    }
 }
 
 #[derive(Serialize)]
@@ -167,14 +324,22 @@ pub struct ApiRequest {
     pub version: u16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub params: Option<Params>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<ApiKey>,
 }
 
 impl ApiRequest {
+    /// Sets key of request, actions of `multi` request don't need their own keys.
+    pub fn with_key(self, key: Option<ApiKey>) -> Self {
+        Self { key, ..self }
+    }
+
     pub fn make_deck_names_req() -> Self {
         ApiRequest {
             action: ApiMethod::DeckNames,
             version: API_VERSION,
             params: None,
+            key: None,
         }
     }
 
@@ -183,6 +348,7 @@ impl ApiRequest {
             action: ApiMethod::DeckNamesAndIds,
             version: API_VERSION,
             params: None,
+            key: None,
         }
     }
 
@@ -193,6 +359,7 @@ impl ApiRequest {
             params: Some(Params::CreateDeck(CreateDeckParams {
                 deck: deck.to_string(),
             })),
+            key: None,
         }
     }
 
@@ -201,6 +368,7 @@ impl ApiRequest {
             action: ApiMethod::AddNote,
             version: API_VERSION,
             params: Some(Params::AddNote(AddNoteParams { note })),
+            key: None,
         }
     }
 
@@ -211,6 +379,96 @@ impl ApiRequest {
             params: Some(Params::NotesInfo(NotesInfoParams {
                 query: deck.to_string(),
             })),
+            key: None,
+        }
+    }
+
+    pub fn make_multi_req(actions: Vec<ApiRequest>) -> Self {
+        ApiRequest {
+            action: ApiMethod::Multi,
+            version: API_VERSION,
+            params: Some(Params::Multi(MultiParams { actions })),
+            key: None,
+        }
+    }
+
+    pub fn make_delete_notes_req(notes: Vec<u64>) -> Self {
+        ApiRequest {
+            action: ApiMethod::DeleteNotes,
+            version: API_VERSION,
+            params: Some(Params::DeleteNotes(DeleteNotesParams { notes })),
+            key: None,
+        }
+    }
+
+    /// Anki refuses to delete decks without their cards, so cards are always deleted too.
+    pub fn make_delete_decks_req(decks: Vec<String>) -> Self {
+        ApiRequest {
+            action: ApiMethod::DeleteDecks,
+            version: API_VERSION,
+            params: Some(Params::DeleteDecks(DeleteDecksParams {
+                decks,
+                cards_too: true,
+            })),
+            key: None,
+        }
+    }
+
+    pub fn make_update_note_fields_req(id: u64, fields: Fields) -> Self {
+        ApiRequest {
+            action: ApiMethod::UpdateNoteFields,
+            version: API_VERSION,
+            params: Some(Params::UpdateNoteFields(UpdateNoteFieldsParams {
+                note: NoteFields { id, fields },
+            })),
+            key: None,
+        }
+    }
+
+    pub fn make_update_note_tags_req(id: u64, tags: Vec<String>) -> Self {
+        ApiRequest {
+            action: ApiMethod::UpdateNoteTags,
+            version: API_VERSION,
+            params: Some(Params::UpdateNoteTags(UpdateNoteTagsParams {
+                note: id,
+                tags,
+            })),
+            key: None,
+        }
+    }
+
+    /// Existing file with the same name is replaced.
+    pub fn make_store_media_file_req(filename: &str, data: &[u8]) -> Self {
+        ApiRequest {
+            action: ApiMethod::StoreMediaFile,
+            version: API_VERSION,
+            params: Some(Params::StoreMediaFile(StoreMediaFileParams {
+                filename: filename.to_string(),
+                data: BASE64_STANDARD.encode(data),
+            })),
+            key: None,
+        }
+    }
+
+    pub fn make_retrieve_media_file_req(filename: &str) -> Self {
+        ApiRequest {
+            action: ApiMethod::RetrieveMediaFile,
+            version: API_VERSION,
+            params: Some(Params::MediaFile(MediaFileParams {
+                filename: filename.to_string(),
+            })),
+            key: None,
+        }
+    }
+
+    pub fn make_delete_media_file_req(filename: &str) -> Self {
+        ApiRequest {
+            action: ApiMethod::DeleteMediaFile,
+            version: API_VERSION,
+            params: Some(Params::MediaFile(MediaFileParams {
+                filename: filename.to_string(),
+            })),
+            key: None,
         }
     }
 }
@@ -1,18 +1,81 @@
-use anyhow::{Result, anyhow};
-use serde::Deserialize;
-use std::collections::HashMap;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Deserialize)]
-#[cfg_attr(feature = "test", derive(serde::Serialize, Debug, Eq, PartialEq))]
-#[serde(untagged)]
+use crate::{
+    card::Card,
+    error::{Error, Result},
+    http::request::{ApiMethod, ApiRequest, MultiParams, Params},
+};
+
+/// Error returned by Anki for note which has the same first field as existing one.
+pub const DUPLICATE_NOTE_ERROR: &str = "cannot create note because it is a duplicate";
+
+/// Result of action, its shape is chosen by action of request, since different actions may
+/// return the same JSON, e.g. empty array.
+#[cfg_attr(
+    feature = "test",
+    derive(serde::Serialize, Debug, Eq, PartialEq),
+    serde(untagged)
+)]
 pub enum ApiResponseData {
     Names(Vec<String>),
     NamesAndIds(HashMap<String, u64>),
     Id(u64),
     NotesInfo(Vec<NotesInfoResponseData>),
+    Multi(Vec<ApiResponse>),
+    Text(String),
+    Flag(bool),
 }
 
 impl ApiResponseData {
+    /// Parses result of given request, results of `multi` are parsed by its actions.
+    /// Actions which return `null` on success have no data.
+    fn parse(request: &ApiRequest, value: Value) -> Result<Option<Self>> {
+        let data = match request.action {
+            ApiMethod::DeckNames => Self::Names(from_value(value)?),
+            ApiMethod::DeckNamesAndIds => Self::NamesAndIds(from_value(value)?),
+            ApiMethod::CreateDeck | ApiMethod::AddNote => Self::Id(from_value(value)?),
+            ApiMethod::NotesInfo => Self::NotesInfo(from_value(value)?),
+            ApiMethod::StoreMediaFile => Self::Text(from_value(value)?),
+            // AnkiConnect returns `false` for missing file
+            ApiMethod::RetrieveMediaFile => match value {
+                Value::Bool(flag) => Self::Flag(flag),
+                value => Self::Text(from_value(value)?),
+            },
+            ApiMethod::Multi => {
+                let Some(Params::Multi(MultiParams { actions })) = &request.params else {
+                    return Err(Error::Response("multi request has no actions".to_string()));
+                };
+                let responses: Vec<RawApiResponse> = from_value(value)?;
+
+                if responses.len() != actions.len() {
+                    return Err(Error::Response(format!(
+                        "expected {} results in response, got {}",
+                        actions.len(),
+                        responses.len()
+                    )));
+                }
+
+                Self::Multi(
+                    actions
+                        .iter()
+                        .zip(responses)
+                        .map(|(action, response)| ApiResponse::parse(action, response))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            ApiMethod::DeleteNotes
+            | ApiMethod::DeleteDecks
+            | ApiMethod::UpdateNoteFields
+            | ApiMethod::UpdateNoteTags
+            | ApiMethod::DeleteMediaFile => return Ok(None),
+        };
+
+        Ok(Some(data))
+    }
+
     pub fn into_names_res(self) -> Option<Vec<String>> {
         match self {
             ApiResponseData::Names(names) => Some(names),
@@ -40,6 +103,30 @@ impl ApiResponseData {
             _ => None,
         }
     }
+
+    pub fn into_multi_res(self) -> Option<Vec<ApiResponse>> {
+        match self {
+            ApiResponseData::Multi(responses) => Some(responses),
+            _ => None,
+        }
+    }
+
+    pub fn into_text_res(self) -> Option<String> {
+        match self {
+            ApiResponseData::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Content of media file decoded from base64, inner `None` if there is no such file, since
+    /// Anki returns `false` for it.
+    pub fn into_media_res(self) -> Option<Option<Vec<u8>>> {
+        match self {
+            ApiResponseData::Text(data) => BASE64_STANDARD.decode(data).ok().map(Some),
+            ApiResponseData::Flag(false) => Some(None),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -69,20 +156,34 @@ pub struct NotesInfoResponseData {
     pub cards: Vec<u64>,
 }
 
+impl From<NotesInfoResponseData> for Card {
+    fn from(note: NotesInfoResponseData) -> Self {
+        let mut fields: Vec<(String, Data)> = note.fields.into_iter().collect();
+        fields.sort_by_key(|(_, data)| data.order);
+
+        Card::new(
+            note.model_name,
+            fields
+                .into_iter()
+                .map(|(name, data)| (name, data.value))
+                .collect(),
+        )
+        .with_tags(note.tags)
+        .with_id(Some(note.note_id))
+    }
+}
+
 #[cfg(feature = "test")]
 impl NotesInfoResponseData {
     pub fn new_simple(front: &str, back: &str) -> Self {
+        Self::from_card(Card::basic(front, back))
+    }
+
+    pub fn from_card(card: Card) -> Self {
         Self {
-            fields: OutFields {
-                front: Data {
-                    value: front.to_string(),
-                    ..Data::default()
-                },
-                back: Data {
-                    value: back.to_string(),
-                    ..Data::default()
-                },
-            },
+            model_name: card.model,
+            fields: to_out_fields(card.fields),
+            tags: card.tags,
             ..Self::default()
         }
     }
@@ -96,31 +197,30 @@ impl NotesInfoResponseData {
 #[cfg(feature = "test")]
 impl From<super::request::Note> for NotesInfoResponseData {
     fn from(note: super::request::Note) -> Self {
-        Self::new_simple(note.fields.front.as_str(), note.fields.back.as_str())
+        Self::from_card(Card::new(note.model, note.fields).with_tags(note.tags))
     }
 }
 
-#[derive(Deserialize)]
-#[cfg_attr(
-    feature = "test",
-    derive(
-        serde::Serialize,
-        Clone,
-        Debug,
-        Default,
-        Eq,
-        PartialEq,
-        Ord,
-        PartialOrd
-    )
-)]
-pub struct OutFields {
-    #[serde(rename = "Front")]
-    pub front: Data,
-    #[serde(rename = "Back")]
-    pub back: Data,
+#[cfg(feature = "test")]
+fn to_out_fields(fields: crate::card::Fields) -> OutFields {
+    fields
+        .into_iter()
+        .enumerate()
+        .map(|(order, (name, value))| {
+            (
+                name,
+                Data {
+                    value,
+                    order: order as u16,
+                },
+            )
+        })
+        .collect()
 }
 
+/// Fields of note keyed by name, `order` of each field keeps position in note type.
+pub type OutFields = BTreeMap<String, Data>;
+
 #[derive(Deserialize, Default)]
 #[cfg_attr(
     feature = "test",
@@ -131,7 +231,13 @@ pub struct Data {
     pub order: u16,
 }
 
+/// Response as it's received, before its result is parsed by request.
 #[derive(Deserialize)]
+pub struct RawApiResponse {
+    pub error: Option<String>,
+    pub result: Option<Value>,
+}
+
 #[cfg_attr(
     feature = "test",
     derive(serde::Serialize, Debug, Default, Eq, PartialEq)
@@ -142,13 +248,48 @@ pub struct ApiResponse {
 }
 
 impl ApiResponse {
+    /// Parses result of response by action of request it answers, result of failed action
+    /// isn't parsed.
+    pub fn parse(request: &ApiRequest, raw: RawApiResponse) -> Result<Self> {
+        let result = match (&raw.error, raw.result) {
+            (None, Some(value)) => ApiResponseData::parse(request, value)?,
+            _ => None,
+        };
+
+        Ok(Self {
+            error: raw.error,
+            result,
+        })
+    }
+
     pub fn into_result(self) -> Result<ApiResponseData> {
         match (self.error, self.result) {
-            (Some(error), _) => Err(anyhow!(error)),
+            (Some(error), _) => Err(api_error(error)),
             (None, Some(result)) => Ok(result),
-            _ => Err(anyhow!("empty response")),
+            _ => Err(Error::Response("empty response".to_string())),
         }
     }
+
+    /// For actions which return `null` on success, e.g. `deleteNotes`.
+    pub fn into_empty_result(self) -> Result<()> {
+        match self.error {
+            Some(error) => Err(api_error(error)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> Result<T> {
+    serde_json::from_value(value)
+        .map_err(|e| Error::Response(format!("invalid response of AnkiConnect: {e}")))
+}
+
+fn api_error(error: String) -> Error {
+    if error == DUPLICATE_NOTE_ERROR {
+        Error::DuplicateNote
+    } else {
+        Error::Api(error)
+    }
 }
 
 #[cfg(feature = "test")]
@@ -167,6 +308,13 @@ impl ApiResponse {
         }
     }
 
+    pub fn as_empty_success() -> Self {
+        Self {
+            error: None,
+            result: None,
+        }
+    }
+
     pub fn with_names_ok_res(res: Vec<String>) -> Self {
         Self::as_success(ApiResponseData::Names(res))
     }
@@ -182,4 +330,16 @@ impl ApiResponse {
     pub fn with_notes_info_ok_res(res: Vec<NotesInfoResponseData>) -> Self {
         Self::as_success(ApiResponseData::NotesInfo(res))
     }
+
+    pub fn with_multi_ok_res(res: Vec<ApiResponse>) -> Self {
+        Self::as_success(ApiResponseData::Multi(res))
+    }
+
+    pub fn with_text_ok_res(res: String) -> Self {
+        Self::as_success(ApiResponseData::Text(res))
+    }
+
+    pub fn with_flag_ok_res(res: bool) -> Self {
+        Self::as_success(ApiResponseData::Flag(res))
+    }
 }
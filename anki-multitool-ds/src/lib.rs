@@ -1,2 +1,3 @@
 pub mod card;
+pub mod error;
 pub mod http;
@@ -1,7 +1,13 @@
 use serde_json::Value;
 use std::{fs::read_to_string, sync::LazyLock};
 
-use anki_multitool_core::ToolController;
+use anki_multitool_convert::collection::CollectionWriter;
+use anki_multitool_core::{
+    ApiKey, Backend, CsvOptions, Error, ExportOptions, ImportOptions, ImportReport,
+    MarkdownOptions, MarkdownStyle, NoteFailure, SyncChange, SyncOptions, ToolController,
+    convert_apkg_to_md, convert_file_to_apkg, convert_json_to_apkg, convert_md_to_apkg,
+};
+use anki_multitool_ds::card::Card;
 use anki_multitool_test_util::{env::TestEnv, server::MockAnkiServer, with_mserver};
 use anki_multitool_util::file;
 
@@ -88,12 +94,12 @@ pub async fn test_convert_json() {
 
         let controller = ToolController::new(HOST.to_string(), port);
         let deck = file::to_file_name(file.path()).expect("failed to get file name");
-        let created_deck = controller.convert_json_to_deck(file.path()).await.expect("failed to convert JSON to deck");
+        let created_deck = controller.convert_json_to_deck(file.path(), &ImportOptions::default()).await.expect("failed to convert JSON to deck").deck;
 
         assert_eq!(deck, created_deck);
 
         file.close().expect("failed to close file");
-        let file_path = controller.convert_deck_to_json(&deck).await.expect("failed to convert deck to JSON");
+        let file_path = controller.convert_deck_to_json(&deck, &ExportOptions::default()).await.expect("failed to convert deck to JSON");
 
         assert_eq!(
             serde_json::from_str::<Value>(
@@ -130,12 +136,12 @@ pub async fn test_convert_markdown_ordered_list() {
 
         let controller = ToolController::new(HOST.to_string(), port);
         let deck = file::to_file_name(file.path()).expect("failed to get file name");
-        let created_deck = controller.convert_md_to_deck(file.path()).await.expect("failed to convert Markdown to deck");
+        let created_deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck").deck;
 
         assert_eq!(deck, created_deck);
 
         file.close().expect("failed to close file");
-        let file_path = controller.convert_deck_to_md(&deck).await.expect("failed to convert deck to Markdown");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
 
         assert_eq!(
             &read_to_string(&file_path).expect("failed to read  file"),
@@ -168,12 +174,12 @@ pub async fn test_convert_markdown_unordered_list() {
 
         let controller = ToolController::new(HOST.to_string(), port);
         let deck = file::to_file_name(file.path()).expect("failed to get file name");
-        let created_deck = controller.convert_md_to_deck(file.path()).await.expect("failed to convert Markdown to deck");
+        let created_deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck").deck;
 
         assert_eq!(deck, created_deck);
 
         file.close().expect("failed to close file");
-        let file_path = controller.convert_deck_to_md(&deck).await.expect("failed to convert deck to Markdown");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
 
         assert_eq!(
             &read_to_string(&file_path).expect("failed to read  file"),
@@ -184,3 +190,795 @@ pub async fn test_convert_markdown_unordered_list() {
         );
     }
 }
+
+#[tokio::test]
+pub async fn test_convert_markdown_headings_with_subdecks() {
+    let _ = &*TEST_ENV;
+    let port = 8781;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"## What is ownership?
+
+Set of rules of **memory management**.
+
+# Closures
+
+## What does `move` do?
+
+Moves captured values into closure:
+
+```rust
+let f = move || x;
+```
+"#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let md_options = MarkdownOptions {
+            style: MarkdownStyle::Headings,
+            subdecks: true,
+            ..MarkdownOptions::default()
+        };
+        let controller = ToolController::new(HOST.to_string(), port);
+        let report = controller.convert_md_to_deck(file.path(), &md_options, &ImportOptions::default()).await.expect("failed to convert Markdown to deck");
+
+        assert_eq!(report.added, 2);
+        assert_eq!(
+            controller.deck_list().await.expect("failed to get decks"),
+            vec![report.deck.clone(), format!("{}::Closures", report.deck)]
+        );
+
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_md(&report.deck, &md_options, &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"## What is ownership?
+
+Set of rules of **memory management**.
+
+# Closures
+
+## What does `move` do?
+
+Moves captured values into closure:&#10;<pre><code class="language-rust">let f = move || x;&#10;</code></pre>
+"#
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_markdown_with_media() {
+    let _ = &*TEST_ENV;
+    let port = 8782;
+
+    let dir = tempfile::tempdir().expect("failed to create temp directory");
+    let path = dir.path().join("Anatomy.md");
+    std::fs::create_dir(dir.path().join("img")).expect("failed to create directory");
+    std::fs::write(dir.path().join("img/heart.png"), b"png").expect("failed to write image");
+    std::fs::write(dir.path().join("beat.mp3"), b"mp3").expect("failed to write audio");
+    util::write_to_file(
+        &path,
+        "1. Heart - ![](img/heart.png)\n2. Heartbeat - [sound:beat.mp3]\n3. Lungs - ![](lungs.png)\n",
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let report = controller.convert_md_to_deck(&path, &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck");
+
+        assert_eq!((report.added, report.media), (3, 2));
+        assert_eq!(
+            controller.backend.cards("Anatomy").await.expect("failed to get cards").into_iter().map(|card| card.with_id(None)).collect::<Vec<_>>(),
+            vec![
+                Card::basic("Heart", r#"<img src="heart.png">"#),
+                Card::basic("Heartbeat", "[sound:beat.mp3]"),
+                Card::basic("Lungs", r#"<img src="lungs.png">"#),
+            ]
+        );
+
+        let out = tempfile::tempdir().expect("failed to create temp directory");
+        let file_path = controller.convert_deck_to_md("Anatomy", &MarkdownOptions::default(), &ExportOptions { dir: out.path().to_path_buf(), ..ExportOptions::default() }).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            read_to_string(&file_path).expect("failed to read file"),
+            "1. Heart - ![](media/heart.png)\n2. Heartbeat - \\[sound:media/beat.mp3\\]\n3. Lungs - ![](lungs.png)\n"
+        );
+        assert_eq!(std::fs::read(out.path().join("media/heart.png")).expect("failed to read image"), b"png");
+        assert_eq!(std::fs::read(out.path().join("media/beat.mp3")).expect("failed to read audio"), b"mp3");
+        assert!(!out.path().join("media/lungs.png").exists());
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_json_custom_note_types() {
+    let _ = &*TEST_ENV;
+    let port = 8770;
+
+    let file = util::temp_json_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"[
+            {"front": "Q1", "back": "A1"},
+            {"model": "Basic (and reversed card)", "fields": {"Front": "Q2", "Back": "A2"}},
+            {"model": "Vocabulary", "fields": {"Word": "серце", "Meaning": "heart", "Example": "моє серце"}}
+        ]"#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let deck = controller.convert_json_to_deck(file.path(), &ImportOptions::default()).await.expect("failed to convert JSON to deck").deck;
+
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_json(&deck, &ExportOptions::default()).await.expect("failed to convert deck to JSON");
+
+        assert_eq!(
+            read_to_string(&file_path).expect("failed to read JSON file"),
+            r#"[{"front":"Q1","back":"A1"},{"model":"Basic (and reversed card)","fields":{"Front":"Q2","Back":"A2"}},{"model":"Vocabulary","fields":{"Word":"серце","Meaning":"heart","Example":"моє серце"}}]"#
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_markdown_cloze() {
+    let _ = &*TEST_ENV;
+    let port = 8771;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+- {{c1::Kyiv}} is the capital of Ukraine - city of chestnuts
+- Q1 - A1
+        "#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck").deck;
+
+        let file_path = controller.convert_deck_to_json(&deck, &ExportOptions::default()).await.expect("failed to convert deck to JSON");
+
+        assert_eq!(
+            serde_json::from_str::<Value>(
+                &read_to_string(&file_path).expect("failed to read JSON file")
+            ).expect("failed to parse JSON"),
+            serde_json::json!([
+                {"cloze": "{{c1::Kyiv}} is the capital of Ukraine", "extra": "city of chestnuts"},
+                {"front": "Q1", "back": "A1"}
+            ])
+        );
+
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. {{c1::Kyiv}} is the capital of Ukraine - city of chestnuts
+2. Q1 - A1
+"#
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_tags() {
+    let _ = &*TEST_ENV;
+    let port = 8772;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+- Q1 - A1 {tags: animals, mammals}
+- Q2 - A2
+        "#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let options = ImportOptions {
+            tags: vec!["imported-2026".to_string(), "animals".to_string()],
+            ..ImportOptions::default()
+        };
+        let deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &options).await.expect("failed to convert Markdown to deck").deck;
+
+        let file_path = controller.convert_deck_to_json(&deck, &ExportOptions::default()).await.expect("failed to convert deck to JSON");
+
+        assert_eq!(
+            serde_json::from_str::<Value>(
+                &read_to_string(&file_path).expect("failed to read JSON file")
+            ).expect("failed to parse JSON"),
+            serde_json::json!([
+                {"front": "Q1", "back": "A1", "tags": ["animals", "mammals", "imported-2026"]},
+                {"front": "Q2", "back": "A2", "tags": ["imported-2026", "animals"]}
+            ])
+        );
+
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. Q1 - A1 {tags: animals, mammals, imported-2026}
+2. Q2 - A2 {tags: imported-2026, animals}
+"#
+        );
+
+        let invalid_options = ImportOptions {
+            tags: vec!["two words".to_string()],
+            ..ImportOptions::default()
+        };
+
+        assert!(controller.convert_json_to_deck(&file_path, &invalid_options).await.is_err());
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_in_batches() {
+    let _ = &*TEST_ENV;
+    let port = 8773;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+1. Q1 - A1
+2. Q2 - A2
+3. Q1 - A1
+4. Q3 - A3
+5. Q4 - A4
+        "#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let options = ImportOptions {
+            batch_size: 2,
+            ..ImportOptions::default()
+        };
+        let report = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &options).await.expect("failed to convert Markdown to deck");
+
+        assert_eq!(report.added, 4);
+        assert_eq!(
+            report.failures,
+            vec![NoteFailure {
+                position: 3,
+                front: "Q1".to_string(),
+                error: "cannot create note because it is a duplicate".to_string(),
+            }]
+        );
+
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_md(&report.deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. Q1 - A1
+2. Q2 - A2
+3. Q3 - A3
+4. Q4 - A4
+"#
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_atomic_rollback() {
+    let _ = &*TEST_ENV;
+    let port = 8774;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+1. Q1 - A1
+2. Q2 - A2
+3. Q1 - A1
+4. Q3 - A3
+5. Q4 - A4
+        "#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        util::load_decks(HOST, port, vec!["Existing Deck".to_string()]).await.expect("failed to load decks");
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let options = ImportOptions {
+            batch_size: 2,
+            atomic: true,
+            ..ImportOptions::default()
+        };
+
+        assert_eq!(
+            controller
+                .convert_md_to_deck(file.path(), &MarkdownOptions::default(), &options)
+                .await
+                .expect_err("import should fail")
+                .to_string(),
+            "failed to add card #3 'Q1': cannot create note because it is a duplicate, import was rolled back"
+        );
+        assert_eq!(
+            controller.deck_list().await.expect("failed to get deck names"),
+            vec!["Existing Deck".to_string()]
+        );
+
+        let report = controller
+            .convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions { batch_size: 2, ..ImportOptions::default() })
+            .await
+            .expect("failed to convert Markdown to deck");
+
+        assert_eq!(report.added, 4);
+        assert_eq!(report.failures.len(), 1);
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_append() {
+    let _ = &*TEST_ENV;
+    let port = 8775;
+
+    let first_week = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        first_week.path(),
+        r#"
+1. Q1 - A1
+2. Q2 - A2
+        "#,
+    )
+    .expect("failed to write to file");
+
+    let second_week = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        second_week.path(),
+        r#"
+1. Q2 - A2
+2. Q3 - A3
+3. Q1 - A1
+4. Q4 - A4
+        "#,
+    )
+    .expect("failed to write to file");
+
+    let broken_week = util::temp_json_file().expect("failed to create temp file");
+    util::write_to_file(
+        broken_week.path(),
+        r#"[
+            {"front": "Q5", "back": "A5"},
+            {"front": "", "back": "A6"}
+        ]"#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let options = ImportOptions {
+            deck: Some("Weekly".to_string()),
+            append: true,
+            ..ImportOptions::default()
+        };
+
+        let report = controller.convert_md_to_deck(first_week.path(), &MarkdownOptions::default(), &options).await.expect("failed to convert Markdown to deck");
+        assert_eq!(
+            report,
+            ImportReport { deck: "Weekly".to_string(), new_deck: true, added: 2, note_ids: vec![0, 1], skipped: 0, media: 0, failures: vec![] }
+        );
+
+        let report = controller.convert_md_to_deck(second_week.path(), &MarkdownOptions::default(), &options).await.expect("failed to convert Markdown to deck");
+        assert_eq!(
+            report,
+            ImportReport { deck: "Weekly".to_string(), new_deck: false, added: 2, note_ids: vec![2, 3], skipped: 2, media: 0, failures: vec![] }
+        );
+
+        let atomic_options = ImportOptions { atomic: true, ..options.clone() };
+
+        assert!(controller.convert_json_to_deck(broken_week.path(), &atomic_options).await.is_err());
+
+        let file_path = controller.convert_deck_to_md("Weekly", &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. Q1 - A1
+2. Q2 - A2
+3. Q3 - A3
+4. Q4 - A4
+"#
+        );
+
+        std::fs::remove_file(&file_path).expect("failed to remove file");
+
+        let deck_options = ImportOptions { deck: Some("Weekly".to_string()), ..ImportOptions::default() };
+
+        assert!(controller.convert_md_to_deck(first_week.path(), &MarkdownOptions::default(), &deck_options).await.is_err());
+    }
+}
+
+#[tokio::test]
+pub async fn test_sync() {
+    let _ = &*TEST_ENV;
+    let port = 8776;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+1. Q1 - A1
+2. Q2 - A2
+3. Q3 - A3
+        "#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck").deck;
+        let file_path = controller
+            .convert_deck_to_json(&deck, &ExportOptions { with_ids: true, ..ExportOptions::default() })
+            .await
+            .expect("failed to convert deck to JSON");
+
+        let mut cards = serde_json::from_str::<Vec<Value>>(&read_to_string(&file_path).expect("failed to read JSON file")).expect("failed to parse JSON");
+
+        assert!(cards.iter().all(|card| card["id"].is_u64()));
+
+        cards[0]["front"] = Value::from("Q1 renamed");
+        cards.retain(|card| card["front"] != "Q2");
+        cards.push(serde_json::json!({"front": "Q4", "back": "A4"}));
+        util::write_to_file(&file_path, &serde_json::to_string(&cards).expect("failed to serialize JSON")).expect("failed to write to file");
+
+        let options = SyncOptions { deck: Some(deck.clone()), delete: true, dry_run: true };
+        let report = controller.sync_deck(&file_path, &MarkdownOptions::default(), &CsvOptions::default(), &options).await.expect("failed to sync deck");
+
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(
+            report.changes.iter().map(|change| match change {
+                SyncChange::Add(card) => format!("+ {}", card.front()),
+                SyncChange::Update { front, .. } => format!("~ {front}"),
+                SyncChange::Delete { front, .. } => format!("- {front}"),
+            }).collect::<Vec<_>>(),
+            vec!["~ Q1", "+ Q4", "- Q2"]
+        );
+
+        let report = controller.sync_deck(&file_path, &MarkdownOptions::default(), &CsvOptions::default(), &SyncOptions { dry_run: false, ..options }).await.expect("failed to sync deck");
+
+        assert_eq!(report.changes.len(), 3);
+        assert!(report.failures.is_empty());
+
+        std::fs::remove_file(&file_path).expect("failed to remove file");
+        file.close().expect("failed to close file");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. Q1 renamed - A1
+2. Q3 - A3
+3. Q4 - A4
+"#
+        );
+
+        util::write_to_file(&file_path, "1. Q3 - A3 changed\n2. Q5 - A5\n").expect("failed to write to file");
+
+        let report = controller.sync_deck(&file_path, &MarkdownOptions::default(), &CsvOptions::default(), &SyncOptions { deck: Some(deck.clone()), ..SyncOptions::default() }).await.expect("failed to sync deck");
+
+        assert_eq!(report.unchanged, 0);
+        assert_eq!(report.changes.len(), 2);
+        std::fs::remove_file(&file_path).expect("failed to remove file");
+        let file_path = controller.convert_deck_to_md(&deck, &MarkdownOptions::default(), &ExportOptions::default()).await.expect("failed to convert deck to Markdown");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read  file"),
+            r#"1. Q1 renamed - A1
+2. Q3 - A3 changed
+3. Q4 - A4
+4. Q5 - A5
+"#
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_diff() {
+    let _ = &*TEST_ENV;
+    let port = 8777;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+1. Q1 - A1
+2. Q2 - A2
+3. Q3 - A3
+        "#,
+    )
+    .expect("failed to write to file");
+
+    let changed_file = util::temp_json_file().expect("failed to create temp file");
+    util::write_to_file(
+        changed_file.path(),
+        r#"[
+            {"front": "Q1", "back": "A1"},
+            {"front": "Q3", "back": "A3 changed"},
+            {"front": "Q4", "back": "A4"}
+        ]"#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let deck = controller.convert_md_to_deck(file.path(), &MarkdownOptions::default(), &ImportOptions::default()).await.expect("failed to convert Markdown to deck").deck;
+
+        assert!(controller.diff_deck(&deck, file.path(), &MarkdownOptions::default(), &CsvOptions::default()).await.expect("failed to compare deck with file").is_empty());
+
+        let diff = controller.diff_deck(&deck, changed_file.path(), &MarkdownOptions::default(), &CsvOptions::default()).await.expect("failed to compare deck with file");
+
+        assert_eq!(
+            serde_json::to_value(&diff).expect("failed to serialize diff"),
+            serde_json::json!({
+                "deck": deck,
+                "added": [{"front": "Q4", "back": "A4"}],
+                "removed": [{"id": 1, "front": "Q2", "back": "A2"}],
+                "modified": [
+                    {"id": 2, "front": "Q3", "fields": {"Back": {"old": "A3", "new": "A3 changed"}}}
+                ],
+                "unchanged": 1
+            })
+        );
+
+        assert!(controller.diff_deck("Unknown Deck", file.path(), &MarkdownOptions::default(), &CsvOptions::default()).await.is_err());
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_csv() {
+    let _ = &*TEST_ENV;
+    let port = 8778;
+
+    let file = util::temp_csv_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        "front,back,tags\nQ1,A1,animals\n\"Q2, continued\",\"first line\nsecond line\",\n",
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let deck = controller
+            .convert_csv_to_deck(file.path(), &CsvOptions::default(), &ImportOptions::default())
+            .await
+            .expect("failed to convert CSV to deck")
+            .deck;
+
+        assert_eq!(deck, file::to_file_name(file.path()).expect("failed to get deck name"));
+
+        file.close().expect("failed to close file");
+        let file_path = controller
+            .convert_deck_to_csv(&deck, &CsvOptions::default(), &ExportOptions::default())
+            .await
+            .expect("failed to convert deck to CSV");
+
+        assert_eq!(
+            &read_to_string(&file_path).expect("failed to read file"),
+            "Front,Back,tags\nQ1,A1,animals\n\"Q2, continued\",\"first line\nsecond line\",\n"
+        );
+
+        let tsv_options = CsvOptions { delimiter: b'\t', ..CsvOptions::default() };
+        let file_path = controller
+            .convert_deck_to_csv(&deck, &tsv_options, &ExportOptions::default())
+            .await
+            .expect("failed to convert deck to TSV");
+
+        assert!(file_path.ends_with(".tsv"));
+        assert_eq!(
+            controller.diff_deck(&deck, &file_path, &MarkdownOptions::default(), &tsv_options).await.expect("failed to diff deck").unchanged,
+            2
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_yaml_and_toml() {
+    let _ = &*TEST_ENV;
+    let port = 8779;
+
+    let file = util::temp_yaml_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+deck: Capitals
+tags: [geography]
+cards:
+  - front: What is the capital of Ukraine?
+    back: |
+      Kyiv,
+      city of chestnuts
+  - cloze: "{{c1::Berlin}} is the capital of Germany"
+"#,
+    )
+    .expect("failed to write to file");
+
+    with_mserver! {
+        use_port port;
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let report = controller
+            .convert_yaml_to_deck(file.path(), &ImportOptions::default())
+            .await
+            .expect("failed to convert YAML to deck");
+
+        assert_eq!(report.deck, "Capitals");
+        assert_eq!(report.added, 2);
+
+        let file_path = controller
+            .convert_deck_to_toml("Capitals", &ExportOptions::default())
+            .await
+            .expect("failed to convert deck to TOML");
+
+        assert!(read_to_string(&file_path).expect("failed to read file").starts_with("deck = \"Capitals\""));
+
+        let report = controller
+            .sync_deck(&file_path, &MarkdownOptions::default(), &CsvOptions::default(), &SyncOptions::default())
+            .await
+            .expect("failed to sync deck");
+
+        assert_eq!(report.deck, "Capitals");
+        assert_eq!(report.unchanged, 2);
+        assert!(report.changes.is_empty());
+    }
+}
+
+#[tokio::test]
+pub async fn test_convert_apkg() {
+    let _ = &*TEST_ENV;
+
+    let file = util::temp_md_file().expect("failed to create temp file");
+    util::write_to_file(
+        file.path(),
+        r#"
+1. What is the capital of Ukraine? - Kyiv
+2. What is the capital of Germany? - Berlin
+        "#,
+    )
+    .expect("failed to write to file");
+    let deck = file::to_file_name(file.path()).expect("failed to get file name");
+
+    // conversion doesn't need Anki, so no mock server is started
+    assert!(matches!(
+        convert_json_to_apkg(file.path(), &ExportOptions::default()).await,
+        Err(Error::Invalid(_))
+    ));
+
+    let package_path = convert_md_to_apkg(
+        file.path(),
+        &MarkdownOptions::default(),
+        &ExportOptions::default(),
+    )
+    .await
+    .expect("failed to convert Markdown to apkg");
+
+    assert_eq!(package_path, format!("{deck}.apkg"));
+    assert!(
+        convert_file_to_apkg(
+            file.path(),
+            &MarkdownOptions::default(),
+            &ExportOptions::default()
+        )
+        .await
+        .is_err()
+    );
+
+    let file_path = convert_apkg_to_md(
+        &package_path,
+        &MarkdownOptions::default(),
+        &ExportOptions::default(),
+    )
+    .await
+    .expect("failed to convert apkg to Markdown");
+
+    assert_eq!(file_path, format!("{deck}.md"));
+    assert_eq!(
+        read_to_string(&file_path)
+            .expect("failed to read file")
+            .trim(),
+        read_to_string(file.path())
+            .expect("failed to read file")
+            .trim()
+    );
+}
+
+#[tokio::test]
+pub async fn test_convert_from_collection() {
+    let _ = &*TEST_ENV;
+
+    let dir = tempfile::TempDir::new().expect("failed to create temp dir");
+    let path = dir.path().join("collection.anki2");
+    let mut collection =
+        CollectionWriter::create(&path, "Capitals").expect("failed to create collection");
+    collection
+        .add(Card::basic("What is the capital of Ukraine?", "Kyiv"))
+        .expect("failed to add card");
+    collection.finish().expect("failed to write collection");
+
+    // Anki isn't needed, so no mock server is started
+    let controller = ToolController::with_collection(&path);
+
+    assert_eq!(
+        controller
+            .deck_list()
+            .await
+            .expect("failed to get deck list"),
+        vec!["Capitals".to_string(), "Default".to_string()]
+    );
+
+    let file_path = controller
+        .convert_deck_to_md(
+            "Capitals",
+            &MarkdownOptions::default(),
+            &ExportOptions::default(),
+        )
+        .await
+        .expect("failed to convert deck to Markdown");
+
+    assert_eq!(
+        read_to_string(&file_path)
+            .expect("failed to read file")
+            .trim(),
+        "1. What is the capital of Ukraine? - Kyiv"
+    );
+    assert!(
+        controller
+            .convert_deck_to_json("Cities", &ExportOptions::default())
+            .await
+            .is_err()
+    );
+    assert!(controller.new_deck("Cities").await.is_err());
+}
+
+#[tokio::test]
+pub async fn test_api_key() {
+    let _ = &*TEST_ENV;
+    let port = 8780;
+
+    with_mserver! {
+        use_port port;
+        use_api_key "classroom-key";
+
+        let controller = ToolController::new(HOST.to_string(), port);
+        let error = controller.new_deck("Shared").await.expect_err("request without key should fail");
+        assert!(matches!(error, Error::Api(_)));
+        assert!(!error.to_string().contains("classroom-key"));
+
+        let controller = controller.with_api_key(ApiKey::new("classroom-key"));
+        assert!(controller.new_deck("Shared").await.is_ok());
+        assert_eq!(controller.deck_list().await.expect("failed to get decks"), vec!["Shared"]);
+    }
+}
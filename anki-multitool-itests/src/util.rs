@@ -28,6 +28,14 @@ pub fn temp_md_file() -> Result<NamedTempFile, Error> {
     temp_file(".md")
 }
 
+pub fn temp_csv_file() -> Result<NamedTempFile, Error> {
+    temp_file(".csv")
+}
+
+pub fn temp_yaml_file() -> Result<NamedTempFile, Error> {
+    temp_file(".yaml")
+}
+
 fn temp_file(ext: &str) -> Result<NamedTempFile, Error> {
     use tempfile::Builder;
     Builder::new().prefix("test_deck_").suffix(ext).tempfile()
@@ -1,8 +1,11 @@
-use anki_multitool_ds::http::{
-    request::{ApiRequest, Note},
-    response::ApiResponse,
+use anki_multitool_ds::{
+    card::Fields,
+    error::{Error, Result},
+    http::{
+        request::{ApiKey, ApiRequest, Note},
+        response::{ApiResponse, RawApiResponse},
+    },
 };
-use anyhow::{Result, anyhow};
 use reqwest::{Client, Method};
 
 #[derive(Debug, Clone)]
@@ -10,6 +13,7 @@ pub struct AnkiClient {
     client: Client,
     host: String,
     port: u16,
+    api_key: Option<ApiKey>,
 }
 
 impl AnkiClient {
@@ -18,6 +22,15 @@ impl AnkiClient {
             client: Client::new(),
             host,
             port,
+            api_key: None,
+        }
+    }
+
+    /// Sends key with every request, for AnkiConnect which requires it.
+    pub fn with_api_key(self, api_key: ApiKey) -> Self {
+        Self {
+            api_key: Some(api_key),
+            ..self
         }
     }
 
@@ -34,7 +47,7 @@ impl AnkiClient {
 
     pub async fn create_deck_req(&self, deck_name: &str) -> Result<ApiResponse> {
         if self.is_deck_exists(deck_name).await? {
-            return Err(anyhow!("deck '{deck_name}' already exists"));
+            return Err(Error::DeckExists(deck_name.to_string()));
         }
 
         self.post_request(ApiRequest::make_create_deck_req(deck_name))
@@ -47,7 +60,7 @@ impl AnkiClient {
 
     pub async fn notes_info_req(&self, deck: &str) -> Result<ApiResponse> {
         if !self.is_deck_exists(deck).await? {
-            return Err(anyhow!("deck '{deck}' does not exist"));
+            return Err(Error::DeckNotFound(deck.to_string()));
         }
 
         self.post_request(ApiRequest::make_notes_info_req(deck))
@@ -61,12 +74,53 @@ impl AnkiClient {
 
     pub async fn add_note_req(&self, note: Note) -> Result<ApiResponse> {
         if !self.is_deck_exists(&note.deck).await? {
-            return Err(anyhow!("deck '{}' does not exist", note.deck));
+            return Err(Error::DeckNotFound(note.deck));
         }
 
         self.post_request(ApiRequest::make_add_note_req(note)).await
     }
 
+    /// Executes several actions with single request, response contains result of every action.
+    pub async fn multi_req(&self, actions: Vec<ApiRequest>) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_multi_req(actions)).await
+    }
+
+    pub async fn delete_notes_req(&self, notes: Vec<u64>) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_delete_notes_req(notes))
+            .await
+    }
+
+    pub async fn delete_decks_req(&self, decks: Vec<String>) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_delete_decks_req(decks))
+            .await
+    }
+
+    pub async fn update_note_fields_req(&self, id: u64, fields: Fields) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_update_note_fields_req(id, fields))
+            .await
+    }
+
+    pub async fn update_note_tags_req(&self, id: u64, tags: Vec<String>) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_update_note_tags_req(id, tags))
+            .await
+    }
+
+    /// Stores file in media folder of collection, response contains name of stored file.
+    pub async fn store_media_file_req(&self, filename: &str, data: &[u8]) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_store_media_file_req(filename, data))
+            .await
+    }
+
+    pub async fn retrieve_media_file_req(&self, filename: &str) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_retrieve_media_file_req(filename))
+            .await
+    }
+
+    pub async fn delete_media_file_req(&self, filename: &str) -> Result<ApiResponse> {
+        self.post_request(ApiRequest::make_delete_media_file_req(filename))
+            .await
+    }
+
     pub async fn get_request(&self, request: ApiRequest) -> Result<ApiResponse> {
         self.request(Method::GET, request).await
     }
@@ -76,14 +130,23 @@ impl AnkiClient {
     }
 
     pub async fn request(&self, method: Method, request: ApiRequest) -> Result<ApiResponse> {
-        self.client
-            .request(method, format!("http://{}:{}", self.host, self.port))
+        let url = format!("http://{}:{}", self.host, self.port);
+        let request = request.with_key(self.api_key.clone());
+        let response = self
+            .client
+            .request(method, &url)
             .json(&request)
             .send()
-            .await?
-            .json::<ApiResponse>()
             .await
-            .map_err(|e| e.into())
+            .map_err(|e| Error::Connection {
+                url,
+                message: e.to_string(),
+            })?
+            .json::<RawApiResponse>()
+            .await
+            .map_err(|e| Error::Response(format!("invalid response of AnkiConnect: {e}")))?;
+
+        ApiResponse::parse(&request, response)
     }
 }
 
@@ -109,7 +172,10 @@ mod tests {
             assert!(client.create_deck_req("Deck 2").await.is_ok());
             assert!(client.create_deck_req("Deck 3").await.is_ok());
 
-            assert!(client.create_deck_req("Deck 3").await.is_err());
+            assert!(matches!(
+                client.create_deck_req("Deck 3").await,
+                Err(Error::DeckExists(deck)) if deck == "Deck 3"
+            ));
 
             assert_eq!(
                 client
@@ -143,7 +209,10 @@ mod tests {
                 ])
             );
 
-            assert!(client.notes_info_req("Unknown deck").await.is_err());
+            assert!(matches!(
+                client.notes_info_req("Unknown deck").await,
+                Err(Error::DeckNotFound(_))
+            ));
         }
     }
 
@@ -152,9 +221,6 @@ mod tests {
         use anki_multitool_ds::http::response::NotesInfoResponseData;
 
         let port = 8787;
-        let _server = MockAnkiServer::new(HOST, port)
-            .await
-            .expect("failed to create mock server");
         let client = AnkiClient::new(HOST.to_string(), port);
 
         with_mserver! {
@@ -220,4 +286,249 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    pub async fn test_batch_notes() {
+        let port = 8788;
+        let client = AnkiClient::new(HOST.to_string(), port);
+        let note = |front: &str, back: &str| {
+            Note::new("Test Deck".to_string(), front.to_string(), back.to_string())
+        };
+
+        with_mserver! {
+            use_port port;
+
+            assert!(client.create_deck_req("Test Deck").await.is_ok());
+
+            let responses = client
+                .multi_req(vec![
+                    ApiRequest::make_add_note_req(note("Q1", "A1")),
+                    ApiRequest::make_add_note_req(note("Q1", "A1")),
+                    ApiRequest::make_deck_names_req(),
+                ])
+                .await
+                .unwrap()
+                .into_result()
+                .unwrap()
+                .into_multi_res()
+                .unwrap();
+
+            assert_eq!(responses.len(), 3);
+
+            let mut responses = responses.into_iter().map(ApiResponse::into_result);
+
+            assert_eq!(responses.next().unwrap().unwrap().into_id_res(), Some(0));
+            assert!(matches!(
+                responses.next().unwrap(),
+                Err(Error::DuplicateNote)
+            ));
+            assert_eq!(
+                responses.next().unwrap().unwrap().into_names_res(),
+                Some(vec!["Test Deck".to_string()])
+            );
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_delete() {
+        let port = 8789;
+        let client = AnkiClient::new(HOST.to_string(), port);
+        let note = |deck: &str, front: &str| {
+            Note::new(deck.to_string(), front.to_string(), "A".to_string())
+        };
+
+        with_mserver! {
+            use_port port;
+
+            assert!(client.create_deck_req("Deck 1").await.is_ok());
+            assert!(client.create_deck_req("Deck 2").await.is_ok());
+
+            let ids: Vec<u64> = client
+                .multi_req(vec![
+                    ApiRequest::make_add_note_req(note("Deck 1", "Q1")),
+                    ApiRequest::make_add_note_req(note("Deck 1", "Q2")),
+                    ApiRequest::make_add_note_req(note("Deck 2", "Q3")),
+                ])
+                .await
+                .unwrap()
+                .into_result()
+                .unwrap()
+                .into_multi_res()
+                .unwrap()
+                .into_iter()
+                .map(|response| response.into_result().unwrap().into_id_res().unwrap())
+                .collect();
+
+            assert!(
+                client
+                    .delete_notes_req(vec![ids[0], ids[2]])
+                    .await
+                    .unwrap()
+                    .into_empty_result()
+                    .is_ok()
+            );
+            assert!(client.delete_notes_req(Vec::new()).await.unwrap().into_empty_result().is_ok());
+
+            assert_eq!(
+                client
+                    .notes_info_req("Deck 1")
+                    .await
+                    .unwrap()
+                    .into_result()
+                    .unwrap()
+                    .into_notes_info_res()
+                    .unwrap()
+                    .len(),
+                1
+            );
+
+            assert!(
+                client
+                    .delete_decks_req(vec!["Deck 1".to_string()])
+                    .await
+                    .unwrap()
+                    .into_empty_result()
+                    .is_ok()
+            );
+
+            assert!(!client.is_deck_exists("Deck 1").await.unwrap());
+            assert!(client.is_deck_exists("Deck 2").await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_update_note_fields() {
+        use anki_multitool_ds::card::Card;
+
+        let port = 8790;
+        let client = AnkiClient::new(HOST.to_string(), port);
+
+        with_mserver! {
+            use_port port;
+
+            assert!(client.create_deck_req("Test Deck").await.is_ok());
+
+            let id = client
+                .add_note_req(Note::new("Test Deck".to_string(), "Q1".to_string(), "A1".to_string()))
+                .await
+                .unwrap()
+                .into_result()
+                .unwrap()
+                .into_id_res()
+                .unwrap();
+
+            assert!(
+                client
+                    .update_note_fields_req(id, Fields::from([("Back".to_string(), "A2".to_string())]))
+                    .await
+                    .unwrap()
+                    .into_empty_result()
+                    .is_ok()
+            );
+            assert!(
+                client
+                    .update_note_fields_req(id, Fields::from([("Extra".to_string(), "x".to_string())]))
+                    .await
+                    .unwrap()
+                    .into_empty_result()
+                    .is_err()
+            );
+            assert!(
+                client
+                    .update_note_fields_req(id + 1, Fields::from([("Back".to_string(), "A3".to_string())]))
+                    .await
+                    .unwrap()
+                    .into_empty_result()
+                    .is_err()
+            );
+
+            assert_eq!(
+                client
+                    .notes_info_req("Test Deck")
+                    .await
+                    .unwrap()
+                    .into_result()
+                    .unwrap()
+                    .into_notes_info_res()
+                    .unwrap()
+                    .into_iter()
+                    .map(Card::from)
+                    .collect::<Vec<_>>(),
+                vec![Card::basic("Q1", "A2").with_id(Some(id))]
+            );
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_api_key() {
+        let port = 8791;
+        let client = AnkiClient::new(HOST.to_string(), port);
+
+        with_mserver! {
+            use_port port;
+            use_api_key "secret";
+
+            assert!(matches!(client.deck_names_req().await.unwrap().into_result(), Err(Error::Api(_))));
+
+            let client = client.clone().with_api_key(ApiKey::new("wrong"));
+            assert!(client.deck_names_req().await.unwrap().into_result().is_err());
+
+            let client = client.with_api_key(ApiKey::new("secret"));
+            assert!(client.create_deck_req("Deck 1").await.unwrap().into_result().is_ok());
+            assert!(!format!("{client:?}").contains("secret"));
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_media_files() {
+        let port = 8792;
+        let client = AnkiClient::new(HOST.to_string(), port);
+
+        with_mserver! {
+            use_port port;
+
+            assert_eq!(
+                client
+                    .store_media_file_req("heart.png", &[0x89, b'P', b'N', b'G'])
+                    .await
+                    .unwrap()
+                    .into_result()
+                    .unwrap()
+                    .into_text_res(),
+                Some("heart.png".to_string())
+            );
+
+            assert_eq!(
+                client
+                    .retrieve_media_file_req("heart.png")
+                    .await
+                    .unwrap()
+                    .into_result()
+                    .unwrap()
+                    .into_media_res(),
+                Some(Some(vec![0x89, b'P', b'N', b'G']))
+            );
+            assert_eq!(
+                client
+                    .retrieve_media_file_req("missing.mp3")
+                    .await
+                    .unwrap()
+                    .into_result()
+                    .unwrap()
+                    .into_media_res(),
+                Some(None)
+            );
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_connection_error() {
+        // nothing listens on this port, as if Anki isn't running
+        let client = AnkiClient::new(HOST.to_string(), 1);
+
+        assert!(matches!(
+            client.deck_names_req().await,
+            Err(Error::Connection { url, .. }) if url == "http://localhost:1"
+        ));
+    }
 }
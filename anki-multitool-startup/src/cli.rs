@@ -1,14 +1,66 @@
 use clap::{
-    Parser, Subcommand,
-    builder::{Styles, styling::AnsiColor},
+    Args, Parser, Subcommand, ValueEnum,
+    builder::{RangedU64ValueParser, Styles, styling::AnsiColor},
 };
+use std::path::PathBuf;
+
+use anki_multitool_core::{
+    CsvOptions, DEFAULT_BATCH_SIZE, DEFAULT_HEADING_LEVEL, DuplicateScope, ImportOptions,
+    MarkdownOptions, MarkdownStyle, Separator, parse_delimiter,
+};
+
+use crate::exit::EXIT_CODES_HELP;
 
 #[derive(Parser)]
 #[command(disable_version_flag = true)]
 #[command(disable_help_flag = true)]
 #[command(color = clap::ColorChoice::Auto)]
 #[command(styles = get_styles())]
+#[command(after_help = EXIT_CODES_HELP)]
 pub struct Cli {
+    #[arg(
+        long = "collection",
+        value_name = "PATH",
+        global = true,
+        help = "Read decks from collection.anki2 of Anki profile instead of AnkiConnect, Anki has to be closed"
+    )]
+    pub collection: Option<PathBuf>,
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        global = true,
+        default_value = "text",
+        help = "Format of output, json prints single JSON document with result or error of command"
+    )]
+    pub output: OutputFormat,
+    #[arg(
+        long = "profile",
+        value_name = "NAME",
+        global = true,
+        help = "Profile of config file with settings of Anki instance, overrides ANKI_MULTITOOL_PROFILE"
+    )]
+    pub profile: Option<String>,
+    #[arg(
+        long = "host",
+        value_name = "HOST",
+        global = true,
+        help = "Host of AnkiConnect, overrides ANKI_MULTITOOL_HOST and profile"
+    )]
+    pub host: Option<String>,
+    #[arg(
+        long = "port",
+        value_name = "PORT",
+        global = true,
+        help = "Port of AnkiConnect, overrides ANKI_MULTITOOL_PORT and profile"
+    )]
+    pub port: Option<u16>,
+    #[arg(
+        long = "output-dir",
+        value_name = "DIR",
+        global = true,
+        help = "Directory where exported files are written, overrides profile"
+    )]
+    pub output_dir: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Command,
 }
@@ -28,11 +80,13 @@ pub enum Command {
     },
     #[command(
         name = "json2deck",
-        about = "📄 -> 🃏 Import a deck from a JSON file into Anki, if deck exists, it will return error"
+        about = "📄 -> 🃏 Import a deck from a JSON file into Anki, if deck exists, it will return error unless --append or --into is used"
     )]
     Json2deck {
         #[arg(value_name = "PATH", help = "Path to the JSON file")]
         path: String,
+        #[command(flatten)]
+        import: ImportArgs,
     },
     #[command(
         name = "deck2json",
@@ -41,14 +95,23 @@ pub enum Command {
     Deck2json {
         #[arg(value_name = "DECK_NAME", help = "Name of deck to export")]
         deck: String,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
     },
     #[command(
         name = "md2deck",
-        about = "📄 -> 🃏 Import a deck from a Markdown file into Anki"
+        about = "📄 -> 🃏 Import a deck from a Markdown file into Anki, if deck exists, it will return error unless --append or --into is used"
     )]
     Md2deck {
         #[arg(value_name = "PATH", help = "Path to the Markdown file")]
         path: String,
+        #[command(flatten)]
+        md: MarkdownArgs,
+        #[command(flatten)]
+        import: ImportArgs,
     },
     #[command(
         name = "deck2md",
@@ -57,9 +120,322 @@ pub enum Command {
     Deck2md {
         #[arg(value_name = "DECK_NAME", help = "Name of deck to export")]
         deck: String,
+        #[command(flatten)]
+        md: MarkdownArgs,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
+    },
+    #[command(
+        name = "csv2deck",
+        about = "📄 -> 🃏 Import a deck from a CSV or TSV file into Anki, if deck exists, it will return error unless --append or --into is used"
+    )]
+    Csv2deck {
+        #[arg(value_name = "PATH", help = "Path to the CSV or TSV file")]
+        path: String,
+        #[command(flatten)]
+        csv: CsvArgs,
+        #[arg(
+            long = "no-header",
+            help = "First row is a card, columns are fields of note type followed by tags"
+        )]
+        no_header: bool,
+        #[arg(
+            long = "model",
+            value_name = "NOTE_TYPE",
+            help = "Note type of cards, by default csv_note_type of profile, recognized by header or 'Basic'"
+        )]
+        model: Option<String>,
+        #[command(flatten)]
+        import: ImportArgs,
+    },
+    #[command(
+        name = "deck2csv",
+        about = "🃏 -> 📄 Export a deck from Anki to a CSV or TSV file, if file exists, it will return error"
+    )]
+    Deck2csv {
+        #[arg(value_name = "DECK_NAME", help = "Name of deck to export")]
+        deck: String,
+        #[command(flatten)]
+        csv: CsvArgs,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
+    },
+    #[command(
+        name = "yaml2deck",
+        about = "📄 -> 🃏 Import a deck from a YAML file into Anki, deck name is taken from the file if it's set there, if deck exists, it will return error unless --append or --into is used"
+    )]
+    Yaml2deck {
+        #[arg(value_name = "PATH", help = "Path to the YAML file")]
+        path: String,
+        #[command(flatten)]
+        import: ImportArgs,
+    },
+    #[command(
+        name = "deck2yaml",
+        about = "🃏 -> 📄 Export a deck from Anki to a YAML file, if file exists, it will return error"
+    )]
+    Deck2yaml {
+        #[arg(value_name = "DECK_NAME", help = "Name of deck to export")]
+        deck: String,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
+    },
+    #[command(
+        name = "toml2deck",
+        about = "📄 -> 🃏 Import a deck from a TOML file into Anki, deck name is taken from the file if it's set there, if deck exists, it will return error unless --append or --into is used"
+    )]
+    Toml2deck {
+        #[arg(value_name = "PATH", help = "Path to the TOML file")]
+        path: String,
+        #[command(flatten)]
+        import: ImportArgs,
+    },
+    #[command(
+        name = "deck2toml",
+        about = "🃏 -> 📄 Export a deck from Anki to a TOML file, if file exists, it will return error"
+    )]
+    Deck2toml {
+        #[arg(value_name = "DECK_NAME", help = "Name of deck to export")]
+        deck: String,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
+    },
+    #[command(
+        name = "md2apkg",
+        about = "📄 -> 📦 Build an Anki package from a Markdown file without Anki, if package exists, it will return error"
+    )]
+    Md2apkg {
+        #[arg(value_name = "PATH", help = "Path to the Markdown file")]
+        path: String,
+        #[command(flatten)]
+        md: MarkdownArgs,
+    },
+    #[command(
+        name = "json2apkg",
+        about = "📄 -> 📦 Build an Anki package from a JSON file without Anki, if package exists, it will return error"
+    )]
+    Json2apkg {
+        #[arg(value_name = "PATH", help = "Path to the JSON file")]
+        path: String,
+    },
+    #[command(
+        name = "apkg2md",
+        about = "📦 -> 📄 Convert an Anki package to a Markdown file without Anki, if file exists, it will return error"
+    )]
+    Apkg2md {
+        #[arg(value_name = "PATH", help = "Path to the .apkg file")]
+        path: String,
+        #[command(flatten)]
+        md: MarkdownArgs,
+        #[arg(
+            long = "with-ids",
+            help = "Write ids of notes, so file can be synced back to the deck"
+        )]
+        with_ids: bool,
+    },
+    #[command(
+        name = "sync",
+        about = "🔄 Sync a deck in Anki with a JSON, Markdown, CSV, YAML or TOML file"
+    )]
+    Sync {
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the JSON, Markdown, CSV, YAML or TOML file"
+        )]
+        path: String,
+        #[arg(
+            long = "deck",
+            value_name = "DECK_NAME",
+            help = "Deck to sync with, by default deck set in YAML or TOML file or name of file"
+        )]
+        deck: Option<String>,
+        #[arg(long = "delete", help = "Delete cards which are absent in file")]
+        delete: bool,
+        #[arg(long = "dry-run", help = "Print planned changes without applying them")]
+        dry_run: bool,
+        #[command(flatten)]
+        md: MarkdownArgs,
+        #[command(flatten)]
+        csv: CsvArgs,
+    },
+    #[command(
+        name = "diff",
+        about = "🔍 Show difference between a deck in Anki and a JSON, Markdown, CSV, YAML or TOML file"
+    )]
+    Diff {
+        #[arg(value_name = "DECK_NAME", help = "Name of deck")]
+        deck: String,
+        #[arg(
+            value_name = "PATH",
+            help = "Path to the JSON, Markdown, CSV, YAML or TOML file"
+        )]
+        path: String,
+        #[arg(
+            long = "format",
+            value_name = "FORMAT",
+            default_value = "text",
+            help = "Format of output"
+        )]
+        format: DiffFormat,
+        #[command(flatten)]
+        md: MarkdownArgs,
+        #[command(flatten)]
+        csv: CsvArgs,
     },
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored text for humans
+    Text,
+    /// Machine-readable JSON
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    /// Colored list of changes
+    Text,
+    /// Machine-readable JSON
+    Json,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    #[arg(
+        long = "tag",
+        value_name = "TAG",
+        help = "Tag added to every imported card, can be used multiple times"
+    )]
+    pub tags: Vec<String>,
+    #[arg(
+        long = "batch-size",
+        value_name = "SIZE",
+        default_value_t = DEFAULT_BATCH_SIZE,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+        help = "Number of cards sent to Anki with single request"
+    )]
+    pub batch_size: usize,
+    #[arg(
+        long = "atomic",
+        help = "Stop at the first card which can't be added and roll back the whole import"
+    )]
+    pub atomic: bool,
+    #[arg(
+        long = "append",
+        help = "Add cards to existing deck instead of creating new one, duplicates are skipped"
+    )]
+    pub append: bool,
+    #[arg(
+        long = "into",
+        value_name = "DECK_NAME",
+        help = "Import cards into given deck instead of deck named after file, implies --append"
+    )]
+    pub deck: Option<String>,
+    #[arg(
+        long = "duplicate-scope",
+        value_name = "SCOPE",
+        default_value = "deck",
+        help = "Where to look for duplicates of imported cards: 'deck' or 'collection'"
+    )]
+    pub duplicate_scope: DuplicateScope,
+}
+
+impl ImportArgs {
+    /// Options of import, `default_tags` are used if no tags are given in command line.
+    pub fn into_options(self, default_tags: &[String]) -> ImportOptions {
+        ImportOptions {
+            tags: if self.tags.is_empty() {
+                default_tags.to_vec()
+            } else {
+                self.tags
+            },
+            batch_size: self.batch_size,
+            atomic: self.atomic,
+            append: self.append || self.deck.is_some(),
+            deck: self.deck,
+            duplicate_scope: self.duplicate_scope,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct CsvArgs {
+    #[arg(
+        long = "delimiter",
+        value_name = "CHAR",
+        value_parser = |value: &str| parse_delimiter(value).map_err(|e| e.to_string()),
+        help = "Delimiter of columns, single character or 'tab', by default tab for .tsv and .txt files and comma otherwise"
+    )]
+    pub delimiter: Option<u8>,
+}
+
+impl CsvArgs {
+    /// Options for given file, delimiter falls back to one matching extension of file.
+    pub fn into_options(self, path: &str) -> CsvOptions {
+        let defaults = CsvOptions::for_path(path);
+
+        CsvOptions {
+            delimiter: self.delimiter.unwrap_or(defaults.delimiter),
+            ..defaults
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct MarkdownArgs {
+    #[arg(
+        long = "md-style",
+        value_name = "STYLE",
+        default_value = "list",
+        help = "Layout of Markdown file: 'list' of 'front - back' items or 'headings' with fronts in headings and backs under them"
+    )]
+    pub style: MarkdownStyle,
+    #[arg(
+        long = "heading-level",
+        value_name = "LEVEL",
+        default_value_t = DEFAULT_HEADING_LEVEL,
+        value_parser = RangedU64ValueParser::<u8>::new().range(1..=6),
+        help = "Level of headings which are fronts of cards in headings style"
+    )]
+    pub heading_level: u8,
+    #[arg(
+        long = "subdecks",
+        help = "Top-level '#' headings are subdecks (Deck::Subdeck) in headings style"
+    )]
+    pub subdecks: bool,
+    #[arg(
+        long = "separator",
+        value_name = "SEPARATOR",
+        help = "Separator of fields in list style: 'dash' (' - ', default), 'double-colon' (' :: '), 'pipe' ('|') or 'question' ('? '), escape it with '\\' to use it in text, whitespace next to it isn't kept in fields"
+    )]
+    pub separator: Option<Separator>,
+}
+
+impl MarkdownArgs {
+    /// Options of Markdown file, `default_separator` is used if none is given in command line.
+    pub fn into_options(self, default_separator: Option<Separator>) -> MarkdownOptions {
+        MarkdownOptions {
+            style: self.style,
+            heading_level: self.heading_level,
+            subdecks: self.subdecks,
+            separator: self.separator.or(default_separator).unwrap_or_default(),
+        }
+    }
+}
+
 fn get_styles() -> Styles {
     Styles::styled()
         .header(AnsiColor::Green.on_default())
@@ -102,24 +478,435 @@ mod tests {
 
         cli = parse_args(&["anki-mtool", "json2deck", "path/to/file.json"])
             .expect("failed to parse CLI arguments");
-        assert!(matches!(cli.command, Command::Json2deck { path } if path == "path/to/file.json"));
+        assert!(
+            matches!(cli.command, Command::Json2deck { path, import } if path == "path/to/file.json" && import.tags.is_empty() && import.batch_size == DEFAULT_BATCH_SIZE && !import.atomic && !import.append && import.deck.is_none() && import.duplicate_scope == DuplicateScope::Deck)
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "json2deck",
+            "path/to/file.json",
+            "--tag",
+            "imported-2026",
+            "--tag",
+            "animals",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Json2deck { path, import } if path == "path/to/file.json" && import.tags == ["imported-2026", "animals"])
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "json2deck",
+            "path/to/file.json",
+            "--into",
+            "Weekly",
+            "--duplicate-scope",
+            "collection",
+        ])
+        .expect("failed to parse CLI arguments");
+        let Command::Json2deck { import, .. } = cli.command else {
+            panic!("expected json2deck command");
+        };
+        let options = import.into_options(&["shared".to_string()]);
+        assert!(options.append);
+        assert_eq!(options.deck.as_deref(), Some("Weekly"));
+        assert_eq!(options.duplicate_scope, DuplicateScope::Collection);
+        assert_eq!(options.tags, ["shared"]);
 
         cli = parse_args(&["anki-mtool", "deck2json", "test_deck"])
             .expect("failed to parse CLI arguments");
-        assert!(matches!(cli.command, Command::Deck2json { deck } if deck == "test_deck"));
+        assert!(
+            matches!(cli.command, Command::Deck2json { deck, with_ids } if deck == "test_deck" && !with_ids)
+        );
 
         cli = parse_args(&["anki-mtool", "md2deck", "path/to/file.md"])
             .expect("failed to parse CLI arguments");
-        assert!(matches!(cli.command, Command::Md2deck { path } if path == "path/to/file.md"));
+        assert!(
+            matches!(cli.command, Command::Md2deck { path, md, import } if path == "path/to/file.md" && md.style == MarkdownStyle::List && import.tags.is_empty() && import.batch_size == DEFAULT_BATCH_SIZE && !import.atomic)
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "md2deck",
+            "path/to/file.md",
+            "--tag",
+            "weekly",
+            "--batch-size",
+            "500",
+            "--atomic",
+            "--append",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Md2deck { path, import, .. } if path == "path/to/file.md" && import.tags == ["weekly"] && import.batch_size == 500 && import.atomic && import.append)
+        );
+
+        cli = parse_args(&["anki-mtool", "deck2md", "test_deck", "--with-ids"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Deck2md { deck, with_ids, .. } if deck == "test_deck" && with_ids)
+        );
+    }
+
+    #[test]
+    fn test_markdown_cli() {
+        let mut cli = parse_args(&[
+            "anki-mtool",
+            "md2deck",
+            "lecture.md",
+            "--md-style",
+            "headings",
+            "--heading-level",
+            "3",
+            "--subdecks",
+        ])
+        .expect("failed to parse CLI arguments");
+        let Command::Md2deck { md, .. } = cli.command else {
+            panic!("expected md2deck command");
+        };
+        assert_eq!(
+            md.into_options(None),
+            MarkdownOptions {
+                style: MarkdownStyle::Headings,
+                heading_level: 3,
+                subdecks: true,
+                separator: Separator::Dash,
+            }
+        );
+
+        cli = parse_args(&["anki-mtool", "deck2md", "Lecture", "--md-style", "headings"])
+            .expect("failed to parse CLI arguments");
+        let Command::Deck2md { md, .. } = cli.command else {
+            panic!("expected deck2md command");
+        };
+        assert_eq!(
+            md.into_options(Some(Separator::Pipe)),
+            MarkdownOptions {
+                style: MarkdownStyle::Headings,
+                separator: Separator::Pipe,
+                ..MarkdownOptions::default()
+            }
+        );
+
+        cli = parse_args(&["anki-mtool", "md2deck", "q.md", "--separator", "question"])
+            .expect("failed to parse CLI arguments");
+        let Command::Md2deck { md, .. } = cli.command else {
+            panic!("expected md2deck command");
+        };
+        assert_eq!(
+            md.into_options(Some(Separator::Pipe)).separator,
+            Separator::Question
+        );
+
+        assert!(parse_args(&["anki-mtool", "deck2md", "Lecture", "--md-style", "table"]).is_err());
+        assert!(parse_args(&["anki-mtool", "deck2md", "Lecture", "--separator", ","]).is_err());
+        assert!(
+            parse_args(&[
+                "anki-mtool",
+                "md2deck",
+                "lecture.md",
+                "--heading-level",
+                "7"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_csv_cli() {
+        let mut cli = parse_args(&["anki-mtool", "csv2deck", "words.tsv"])
+            .expect("failed to parse CLI arguments");
+        let Command::Csv2deck {
+            path,
+            csv,
+            no_header,
+            model,
+            import,
+        } = cli.command
+        else {
+            panic!("expected csv2deck command");
+        };
+        assert_eq!(path, "words.tsv");
+        assert!(!no_header && model.is_none() && !import.append);
+        assert_eq!(csv.into_options(&path).delimiter, b'\t');
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "csv2deck",
+            "words.csv",
+            "--delimiter",
+            ";",
+            "--no-header",
+            "--model",
+            "Cloze",
+            "--into",
+            "Weekly",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Csv2deck { csv, no_header, model, import, .. } if csv.delimiter == Some(b';') && no_header && model.as_deref() == Some("Cloze") && import.deck.as_deref() == Some("Weekly"))
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "deck2csv",
+            "Weekly",
+            "--delimiter",
+            "tab",
+            "--with-ids",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Deck2csv { deck, csv, with_ids } if deck == "Weekly" && csv.delimiter == Some(b'\t') && with_ids)
+        );
+
+        assert!(parse_args(&["anki-mtool", "deck2csv", "Weekly", "--delimiter", ";;"]).is_err());
+    }
+
+    #[test]
+    fn test_yaml_toml_cli() {
+        let mut cli = parse_args(&["anki-mtool", "yaml2deck", "words.yaml", "--append"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Yaml2deck { path, import } if path == "words.yaml" && import.append)
+        );
+
+        cli = parse_args(&["anki-mtool", "deck2yaml", "Words", "--with-ids"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Deck2yaml { deck, with_ids } if deck == "Words" && with_ids)
+        );
+
+        cli = parse_args(&["anki-mtool", "toml2deck", "words.toml", "--tag", "weekly"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Toml2deck { path, import } if path == "words.toml" && import.tags == ["weekly"])
+        );
+
+        cli = parse_args(&["anki-mtool", "deck2toml", "Words"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Deck2toml { deck, with_ids } if deck == "Words" && !with_ids)
+        );
+    }
+
+    #[test]
+    fn test_apkg_cli() {
+        let mut cli = parse_args(&["anki-mtool", "md2apkg", "words.md"])
+            .expect("failed to parse CLI arguments");
+        assert!(matches!(cli.command, Command::Md2apkg { path, .. } if path == "words.md"));
+
+        cli = parse_args(&["anki-mtool", "json2apkg", "words.json"])
+            .expect("failed to parse CLI arguments");
+        assert!(matches!(cli.command, Command::Json2apkg { path } if path == "words.json"));
+
+        cli = parse_args(&["anki-mtool", "apkg2md", "words.apkg", "--with-ids"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Apkg2md { path, with_ids, .. } if path == "words.apkg" && with_ids)
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "apkg2md",
+            "words.apkg",
+            "--md-style",
+            "headings",
+        ])
+        .expect("failed to parse CLI arguments");
+        let Command::Apkg2md { md, .. } = cli.command else {
+            panic!("expected apkg2md command");
+        };
+        assert_eq!(md.into_options(None).style, MarkdownStyle::Headings);
+
+        assert!(parse_args(&["anki-mtool", "md2apkg"]).is_err());
+    }
+
+    #[test]
+    fn test_collection_cli() {
+        let mut cli =
+            parse_args(&["anki-mtool", "decklist"]).expect("failed to parse CLI arguments");
+        assert!(cli.collection.is_none());
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "deck2md",
+            "Words",
+            "--collection",
+            "collection.anki2",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert_eq!(cli.collection, Some(PathBuf::from("collection.anki2")));
+        assert!(matches!(cli.command, Command::Deck2md { deck, .. } if deck == "Words"));
+
+        cli = parse_args(&["anki-mtool", "--collection", "collection.anki2", "decklist"])
+            .expect("failed to parse CLI arguments");
+        assert_eq!(cli.collection, Some(PathBuf::from("collection.anki2")));
+    }
+
+    #[test]
+    fn test_output_cli() {
+        let mut cli =
+            parse_args(&["anki-mtool", "decklist"]).expect("failed to parse CLI arguments");
+        assert_eq!(cli.output, OutputFormat::Text);
 
-        cli = parse_args(&["anki-mtool", "deck2md", "test_deck"])
+        cli = parse_args(&["anki-mtool", "--output", "json", "decklist"])
             .expect("failed to parse CLI arguments");
-        assert!(matches!(cli.command, Command::Deck2md { deck } if deck == "test_deck"));
+        assert_eq!(cli.output, OutputFormat::Json);
+
+        cli = parse_args(&["anki-mtool", "json2deck", "deck.json", "--output", "json"])
+            .expect("failed to parse CLI arguments");
+        assert_eq!(cli.output, OutputFormat::Json);
+
+        assert!(parse_args(&["anki-mtool", "--output", "yaml", "decklist"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_cli() {
+        let mut cli =
+            parse_args(&["anki-mtool", "decklist"]).expect("failed to parse CLI arguments");
+        assert!(cli.profile.is_none() && cli.host.is_none() && cli.port.is_none());
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "--profile",
+            "classroom",
+            "deck2md",
+            "Words",
+            "--port",
+            "8766",
+            "--output-dir",
+            "decks",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert_eq!(cli.profile.as_deref(), Some("classroom"));
+        assert_eq!(cli.port, Some(8766));
+        assert_eq!(cli.output_dir, Some(PathBuf::from("decks")));
+
+        assert!(parse_args(&["anki-mtool", "--port", "port", "decklist"]).is_err());
+    }
+
+    #[test]
+    fn test_sync_cli() {
+        let mut cli =
+            parse_args(&["anki-mtool", "sync", "deck.md"]).expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Sync { path, deck, delete, dry_run, .. } if path == "deck.md" && deck.is_none() && !delete && !dry_run)
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "sync",
+            "deck.json",
+            "--deck",
+            "Weekly",
+            "--delete",
+            "--dry-run",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Sync { path, deck, delete, dry_run, .. } if path == "deck.json" && deck.as_deref() == Some("Weekly") && delete && dry_run)
+        );
+
+        cli = parse_args(&["anki-mtool", "sync", "deck.md", "--separator", "pipe"])
+            .expect("failed to parse CLI arguments");
+        let Command::Sync { md, .. } = cli.command else {
+            panic!("expected sync command");
+        };
+        assert_eq!(
+            md.into_options(Some(Separator::Question)).separator,
+            Separator::Pipe
+        );
+
+        cli = parse_args(&["anki-mtool", "sync", "deck.csv", "--delimiter", ";"])
+            .expect("failed to parse CLI arguments");
+        let Command::Sync { csv, .. } = cli.command else {
+            panic!("expected sync command");
+        };
+        assert_eq!(csv.into_options("deck.csv").delimiter, b';');
+
+        cli = parse_args(&["anki-mtool", "diff", "Weekly", "weekly.tsv"])
+            .expect("failed to parse CLI arguments");
+        let Command::Diff { csv, .. } = cli.command else {
+            panic!("expected diff command");
+        };
+        assert_eq!(csv.into_options("weekly.tsv").delimiter, b'\t');
+    }
+
+    #[test]
+    fn test_diff_cli() {
+        let mut cli = parse_args(&["anki-mtool", "diff", "Weekly", "weekly.md"])
+            .expect("failed to parse CLI arguments");
+        assert!(
+            matches!(cli.command, Command::Diff { deck, path, format, .. } if deck == "Weekly" && path == "weekly.md" && format == DiffFormat::Text)
+        );
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "diff",
+            "Weekly",
+            "weekly.json",
+            "--format",
+            "json",
+        ])
+        .expect("failed to parse CLI arguments");
+        assert!(matches!(cli.command, Command::Diff { format, .. } if format == DiffFormat::Json));
+
+        cli = parse_args(&[
+            "anki-mtool",
+            "diff",
+            "Weekly",
+            "weekly.md",
+            "--md-style",
+            "headings",
+            "--heading-level",
+            "2",
+        ])
+        .expect("failed to parse CLI arguments");
+        let Command::Diff { md, .. } = cli.command else {
+            panic!("expected diff command");
+        };
+        assert_eq!(
+            md.into_options(None),
+            MarkdownOptions {
+                style: MarkdownStyle::Headings,
+                heading_level: 2,
+                subdecks: false,
+                separator: Separator::Dash,
+            }
+        );
+
+        assert!(parse_args(&["anki-mtool", "diff", "Weekly"]).is_err());
+        assert!(
+            parse_args(&[
+                "anki-mtool",
+                "diff",
+                "Weekly",
+                "weekly.md",
+                "--format",
+                "yaml"
+            ])
+            .is_err()
+        );
     }
 
     #[test]
     fn test_failed_cli() {
         let cli = parse_args(&["anki-mtool", "unknown_command"]);
         assert!(cli.is_err());
+
+        let cli = parse_args(&["anki-mtool", "json2deck", "file.json", "--batch-size", "0"]);
+        assert!(cli.is_err());
+
+        let cli = parse_args(&[
+            "anki-mtool",
+            "md2deck",
+            "file.md",
+            "--duplicate-scope",
+            "everywhere",
+        ]);
+        assert!(cli.is_err());
     }
 }
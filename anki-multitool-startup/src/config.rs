@@ -1,4 +1,7 @@
-use std::env;
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use anki_multitool_core::{ApiKey, Error, Result, Separator, text_position};
 
 pub const AUTHOR: &str = "Bohdan Sokolovskyi (gaussfff)";
 pub const REPOSITORY: &str = "https://github.com/gaussfff/anki-multitool";
@@ -23,13 +26,192 @@ pub const APP_INFO: [&str; 6] = [
     "  - To be continued... ⏩",
 ];
 
-pub fn get_host() -> String {
-    env::var("ANKI_MULTITOOL_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string())
+/// Settings of single Anki instance, every setting is optional.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// Key of AnkiConnect with `apiKey` set in its config.
+    pub api_key: Option<ApiKey>,
+    /// Note type of cards read from CSV files, note type of other formats is set by file itself.
+    pub csv_note_type: Option<String>,
+    /// Directory where exported files are written.
+    pub output_dir: Option<PathBuf>,
+    /// Separator of fields in Markdown list style.
+    pub separator: Option<Separator>,
+    /// Tags added to every imported card, if no tags are given in command line.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// Profile used when none is chosen with `--profile` or env.
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// `ANKI_MULTITOOL_CONFIG`, or `anki-multitool/config.toml` in config directory of user.
+pub fn config_path() -> Option<PathBuf> {
+    env::var_os("ANKI_MULTITOOL_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+                .map(|dir| dir.join("anki-multitool").join("config.toml"))
+        })
+}
+
+/// Profile chosen by `--profile`, then by `ANKI_MULTITOOL_PROFILE`, then by `default_profile`
+/// of config file. Missing config file is the same as empty one.
+pub fn load_profile(name: Option<String>) -> Result<Profile> {
+    let name = name.or_else(|| env::var("ANKI_MULTITOOL_PROFILE").ok());
+    let path = config_path();
+    let config = match &path {
+        Some(path) if path.exists() => {
+            parse_config(&path.display().to_string(), &fs::read_to_string(path)?)?
+        }
+        _ => ConfigFile::default(),
+    };
+
+    select_profile(config, name)
+}
+
+fn parse_config(path: &str, content: &str) -> Result<ConfigFile> {
+    toml::from_str(content).map_err(|e| match e.span() {
+        Some(span) => {
+            let (line, column) = text_position(content, span.start);
+            Error::parse_at(path, line, column, e.message())
+        }
+        None => Error::parse(path, None, e.message()),
+    })
+}
+
+fn select_profile(mut config: ConfigFile, name: Option<String>) -> Result<Profile> {
+    match name.or(config.default_profile) {
+        Some(name) => config.profiles.remove(&name).ok_or_else(|| {
+            Error::invalid(format!("profile '{name}' is not defined in config file"))
+        }),
+        None => Ok(Profile::default()),
+    }
+}
+
+/// Value of setting by precedence: command line, env, profile, default.
+fn resolve<T>(cli: Option<T>, env: Option<T>, profile: Option<T>, default: T) -> T {
+    cli.or(env).or(profile).unwrap_or(default)
 }
 
-pub fn get_port() -> u16 {
-    env::var("ANKI_MULTITOOL_PORT")
+pub fn get_host(cli: Option<String>, profile: &Profile) -> String {
+    resolve(
+        cli,
+        env::var("ANKI_MULTITOOL_HOST").ok(),
+        profile.host.clone(),
+        DEFAULT_HOST.to_string(),
+    )
+}
+
+pub fn get_port(cli: Option<u16>, profile: &Profile) -> u16 {
+    resolve(
+        cli,
+        env::var("ANKI_MULTITOOL_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok()),
+        profile.port,
+        DEFAULT_PORT,
+    )
+}
+
+/// Key isn't accepted in command line, so it doesn't appear in shell history.
+pub fn get_api_key(profile: &Profile) -> Option<ApiKey> {
+    env::var("ANKI_MULTITOOL_API_KEY")
         .ok()
-        .and_then(|port| port.parse().ok())
-        .unwrap_or(DEFAULT_PORT)
+        .map(ApiKey::new)
+        .or_else(|| profile.api_key.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_profile = "personal"
+
+[profiles.personal]
+port = 8765
+tags = ["personal"]
+
+[profiles.classroom]
+host = "192.168.1.10"
+port = 8766
+api_key = "classroom-key"
+csv_note_type = "Basic (and reversed card)"
+output_dir = "/srv/decks"
+separator = "pipe"
+tags = ["class", "shared"]
+"#;
+
+    #[test]
+    fn test_select_profile() {
+        let profile = |name: Option<&str>| {
+            select_profile(
+                parse_config("config.toml", CONFIG).expect("failed to parse config"),
+                name.map(str::to_string),
+            )
+        };
+
+        assert_eq!(
+            profile(None).expect("failed to select profile"),
+            Profile {
+                port: Some(8765),
+                tags: vec!["personal".to_string()],
+                ..Profile::default()
+            }
+        );
+        assert_eq!(
+            profile(Some("classroom")).expect("failed to select profile"),
+            Profile {
+                host: Some("192.168.1.10".to_string()),
+                port: Some(8766),
+                api_key: Some(ApiKey::new("classroom-key")),
+                csv_note_type: Some("Basic (and reversed card)".to_string()),
+                output_dir: Some(PathBuf::from("/srv/decks")),
+                separator: Some(Separator::Pipe),
+                tags: vec!["class".to_string(), "shared".to_string()],
+            }
+        );
+        assert!(profile(Some("test")).is_err());
+
+        assert_eq!(
+            select_profile(ConfigFile::default(), None).expect("failed to select profile"),
+            Profile::default()
+        );
+        assert!(select_profile(ConfigFile::default(), Some("test".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_failed_config() {
+        assert!(matches!(
+            parse_config("config.toml", "[profiles.test]\nport = \"not a port\"\n"),
+            Err(Error::Parse {
+                line: Some(2),
+                column: Some(8),
+                ..
+            })
+        ));
+        assert!(parse_config("config.toml", "[profiles.test]\napi = 1\n").is_err());
+        assert!(parse_config("config.toml", "[profiles.test]\nseparator = \",\"\n").is_err());
+        assert!(parse_config("config.toml", "[profiles.test]\nnote_type = \"Basic\"\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(Some(1), Some(2), Some(3), 4), 1);
+        assert_eq!(resolve(None, Some(2), Some(3), 4), 2);
+        assert_eq!(resolve(None, None, Some(3), 4), 3);
+        assert_eq!(resolve(None, None, None, 4), 4);
+    }
 }
@@ -0,0 +1,80 @@
+use std::process::ExitCode;
+
+use anki_multitool_core::Error;
+
+pub const SUCCESS: u8 = 0;
+/// Deck differs from file, returned only by `diff`.
+pub const DIFFERENCES: u8 = 1;
+/// Invalid arguments or options, clap uses the same code for usage errors.
+pub const INVALID: u8 = 2;
+pub const CONNECTION: u8 = 3;
+pub const ANKI_CONNECT: u8 = 4;
+pub const FILE: u8 = 5;
+pub const PARSE: u8 = 6;
+pub const CONFLICT: u8 = 7;
+
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  deck differs from file (diff only)
+  2  invalid arguments or options
+  3  failed to connect to AnkiConnect
+  4  AnkiConnect returned an error, or some cards were not added or synced
+  5  file or collection can't be read or written
+  6  file has invalid format
+  7  deck or file already exists, or deck does not exist";
+
+/// Code of error class, errors of rolled back import are classified by their cause.
+pub fn code(error: &Error) -> u8 {
+    match error {
+        Error::Connection { .. } => CONNECTION,
+        Error::Api(_) | Error::DuplicateNote | Error::Response(_) => ANKI_CONNECT,
+        Error::FileNotFound(_) | Error::Io(_) | Error::Collection(_) => FILE,
+        Error::Parse { .. } | Error::Json(_) => PARSE,
+        Error::DeckExists(_) | Error::DeckNotFound(_) | Error::FileExists(_) => CONFLICT,
+        Error::Invalid(_) => INVALID,
+        Error::RolledBack(error) | Error::RollbackFailed { error, .. } => code(error),
+    }
+}
+
+/// Name of error class, used in JSON output.
+pub fn kind(error: &Error) -> &'static str {
+    match code(error) {
+        CONNECTION => "connection",
+        ANKI_CONNECT => "anki_connect",
+        FILE => "file",
+        PARSE => "parse",
+        CONFLICT => "conflict",
+        _ => "invalid",
+    }
+}
+
+pub fn failure(error: &Error) -> ExitCode {
+    ExitCode::from(code(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            code(&Error::Connection {
+                url: "http://localhost:8765".to_string(),
+                message: "connection refused".to_string(),
+            }),
+            CONNECTION
+        );
+        assert_eq!(code(&Error::DuplicateNote), ANKI_CONNECT);
+        assert_eq!(code(&Error::FileNotFound("deck.md".into())), FILE);
+        assert_eq!(code(&Error::parse("deck.md", Some(3), "bad line")), PARSE);
+        assert_eq!(code(&Error::DeckExists("Words".to_string())), CONFLICT);
+        assert_eq!(
+            code(&Error::RolledBack(Box::new(Error::Api(
+                "failed".to_string()
+            )))),
+            ANKI_CONNECT
+        );
+    }
+}
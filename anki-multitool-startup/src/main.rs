@@ -1,36 +1,267 @@
 mod cli;
 mod config;
+mod exit;
 mod printer;
 
 use clap::Parser;
-use config::{get_host, get_port};
+use config::{Profile, get_api_key, get_host, get_port, load_profile};
+use std::{path::PathBuf, process::ExitCode};
 
-use anki_multitool_core::ToolController;
+use anki_multitool_core::{
+    Backend, CsvOptions, ExportOptions, SyncOptions, ToolController, convert_apkg_to_md,
+    convert_json_to_apkg, convert_md_to_apkg,
+};
 
-use cli::{Cli, Command};
+use cli::{Cli, Command, OutputFormat};
 
 #[tokio::main]
-async fn main() {
-    let controller = ToolController::new(get_host(), get_port());
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    match Cli::parse().command {
-        Command::Info => printer::print_info(controller.version()),
-        Command::Version => printer::print_version(controller.version()),
-        Command::Newdeck { ref deck } => {
-            printer::print_new_deck(controller.new_deck(deck).await, deck)
-        }
-        Command::Decklist => printer::print_decklist(controller.deck_list().await),
-        Command::Deck2md { ref deck } => {
-            printer::print_deck2md(controller.convert_deck_to_md(deck).await, deck)
-        }
-        Command::Deck2json { ref deck } => {
-            printer::print_deck2json(controller.convert_deck_to_json(deck).await, deck)
+    // colors are already disabled when stdout isn't a terminal, JSON never has them
+    if cli.output == OutputFormat::Json {
+        colored::control::set_override(false);
+    }
+
+    let Cli {
+        collection,
+        output,
+        profile,
+        host,
+        port,
+        output_dir,
+        command,
+    } = cli;
+    let profile = match load_profile(profile) {
+        Ok(profile) => profile,
+        Err(e) => return printer::print_config_error(e, output),
+    };
+    let output_dir = output_dir
+        .or_else(|| profile.output_dir.clone())
+        .unwrap_or_default();
+
+    match collection {
+        Some(path) => {
+            run(
+                ToolController::with_collection(path),
+                command,
+                output,
+                &profile,
+                output_dir,
+            )
+            .await
         }
-        Command::Json2deck { ref path } => {
-            printer::print_json2deck(controller.convert_json_to_deck(path).await, path)
+        None => {
+            let controller =
+                ToolController::new(get_host(host, &profile), get_port(port, &profile));
+            let controller = match get_api_key(&profile) {
+                Some(api_key) => controller.with_api_key(api_key),
+                None => controller,
+            };
+
+            run(controller, command, output, &profile, output_dir).await
         }
-        Command::Md2deck { ref path } => {
-            printer::print_md2deck(controller.convert_md_to_deck(path).await, path)
+    }
+}
+
+async fn run<B: Backend>(
+    controller: ToolController<B>,
+    command: Command,
+    output: OutputFormat,
+    profile: &Profile,
+    output_dir: PathBuf,
+) -> ExitCode {
+    let export = |with_ids| ExportOptions {
+        with_ids,
+        dir: output_dir.clone(),
+    };
+
+    match command {
+        Command::Info => printer::print_info(controller.version(), output),
+        Command::Version => printer::print_version(controller.version(), output),
+        Command::Newdeck { ref deck } => {
+            printer::print_new_deck(controller.new_deck(deck).await, deck, output)
         }
+        Command::Decklist => printer::print_decklist(controller.deck_list().await, output),
+        Command::Deck2md {
+            ref deck,
+            md,
+            with_ids,
+        } => printer::print_export(
+            controller
+                .convert_deck_to_md(deck, &md.into_options(profile.separator), &export(with_ids))
+                .await,
+            deck,
+            "markdown",
+            output,
+        ),
+        Command::Deck2json { ref deck, with_ids } => printer::print_export(
+            controller
+                .convert_deck_to_json(deck, &export(with_ids))
+                .await,
+            deck,
+            "json",
+            output,
+        ),
+        Command::Json2deck { ref path, import } => printer::print_import(
+            controller
+                .convert_json_to_deck(path, &import.into_options(&profile.tags))
+                .await,
+            path,
+            "json",
+            output,
+        ),
+        Command::Md2deck {
+            ref path,
+            md,
+            import,
+        } => printer::print_import(
+            controller
+                .convert_md_to_deck(
+                    path,
+                    &md.into_options(profile.separator),
+                    &import.into_options(&profile.tags),
+                )
+                .await,
+            path,
+            "markdown",
+            output,
+        ),
+        Command::Csv2deck {
+            ref path,
+            csv,
+            no_header,
+            model,
+            import,
+        } => printer::print_import(
+            controller
+                .convert_csv_to_deck(
+                    path,
+                    &CsvOptions {
+                        has_header: !no_header,
+                        model: model.or_else(|| profile.csv_note_type.clone()),
+                        ..csv.into_options(path)
+                    },
+                    &import.into_options(&profile.tags),
+                )
+                .await,
+            path,
+            "csv",
+            output,
+        ),
+        Command::Deck2csv {
+            ref deck,
+            csv,
+            with_ids,
+        } => printer::print_export(
+            controller
+                .convert_deck_to_csv(deck, &csv.into_options(deck), &export(with_ids))
+                .await,
+            deck,
+            "csv",
+            output,
+        ),
+        Command::Yaml2deck { ref path, import } => printer::print_import(
+            controller
+                .convert_yaml_to_deck(path, &import.into_options(&profile.tags))
+                .await,
+            path,
+            "yaml",
+            output,
+        ),
+        Command::Deck2yaml { ref deck, with_ids } => printer::print_export(
+            controller
+                .convert_deck_to_yaml(deck, &export(with_ids))
+                .await,
+            deck,
+            "yaml",
+            output,
+        ),
+        Command::Toml2deck { ref path, import } => printer::print_import(
+            controller
+                .convert_toml_to_deck(path, &import.into_options(&profile.tags))
+                .await,
+            path,
+            "toml",
+            output,
+        ),
+        Command::Deck2toml { ref deck, with_ids } => printer::print_export(
+            controller
+                .convert_deck_to_toml(deck, &export(with_ids))
+                .await,
+            deck,
+            "toml",
+            output,
+        ),
+        Command::Md2apkg { ref path, md } => printer::print_convert(
+            convert_md_to_apkg(path, &md.into_options(profile.separator), &export(false)).await,
+            path,
+            "apkg",
+            output,
+        ),
+        Command::Json2apkg { ref path } => printer::print_convert(
+            convert_json_to_apkg(path, &export(false)).await,
+            path,
+            "apkg",
+            output,
+        ),
+        Command::Apkg2md {
+            ref path,
+            md,
+            with_ids,
+        } => printer::print_convert(
+            convert_apkg_to_md(path, &md.into_options(profile.separator), &export(with_ids)).await,
+            path,
+            "markdown",
+            output,
+        ),
+        Command::Sync {
+            ref path,
+            deck,
+            delete,
+            dry_run,
+            md,
+            csv,
+        } => printer::print_sync(
+            controller
+                .sync_deck(
+                    path,
+                    &md.into_options(profile.separator),
+                    &CsvOptions {
+                        model: profile.csv_note_type.clone(),
+                        ..csv.into_options(path)
+                    },
+                    &SyncOptions {
+                        deck,
+                        delete,
+                        dry_run,
+                    },
+                )
+                .await,
+            path,
+            output,
+        ),
+        Command::Diff {
+            ref deck,
+            ref path,
+            format,
+            md,
+            csv,
+        } => printer::print_diff(
+            controller
+                .diff_deck(
+                    deck,
+                    path,
+                    &md.into_options(profile.separator),
+                    &CsvOptions {
+                        model: profile.csv_note_type.clone(),
+                        ..csv.into_options(path)
+                    },
+                )
+                .await,
+            path,
+            format,
+            output,
+        ),
     }
 }
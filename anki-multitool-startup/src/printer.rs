@@ -1,9 +1,23 @@
-use anyhow::Result;
 use colored::Colorize;
+use serde_json::{Value, json};
+use std::process::ExitCode;
 
-use crate::config::{APP_INFO, ASCII_ART, AUTHOR, REPOSITORY};
+use anki_multitool_core::{DeckDiff, Error, ImportReport, Result, SyncChange, SyncReport};
+
+use crate::{
+    cli::{DiffFormat, OutputFormat},
+    config::{APP_INFO, ASCII_ART, AUTHOR, REPOSITORY},
+    exit,
+};
+
+pub fn print_info(version: &str, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(
+            Ok(json!({ "version": version, "author": AUTHOR, "repository": REPOSITORY })),
+            exit::SUCCESS,
+        );
+    }
 
-pub fn print_info(version: &str) {
     for line in ASCII_ART {
         println!("{}", line.bold().blue());
     }
@@ -17,13 +31,119 @@ pub fn print_info(version: &str) {
     for line in APP_INFO {
         println!("{}", line.green());
     }
+
+    ExitCode::SUCCESS
 }
 
-pub fn print_version(version: &str) {
+pub fn print_version(version: &str, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(Ok(json!({ "version": version })), exit::SUCCESS);
+    }
+
     println!("{}{}", "v.".green(), version.bold().blue());
+    ExitCode::SUCCESS
+}
+
+/// Prints single JSON document with result of command or with error, so scripts read
+/// outcome from stdout in both cases. `code` is exit code of successful result.
+fn print_json(result: Result<Value>, code: u8) -> ExitCode {
+    let (document, code) = match result {
+        Ok(result) => (
+            json!({ "ok": true, "exit_code": code, "result": result }),
+            code,
+        ),
+        Err(e) => {
+            let code = exit::code(&e);
+            (
+                json!({ "ok": false, "exit_code": code, "error": error_json(&e) }),
+                code,
+            )
+        }
+    };
+
+    println!("{document}");
+    ExitCode::from(code)
+}
+
+fn error_json(e: &Error) -> Value {
+    let mut error = json!({ "kind": exit::kind(e), "message": e.to_string() });
+
+    match e {
+        Error::Connection { url, .. } => error["url"] = json!(url),
+        Error::Parse {
+            path, line, column, ..
+        } => {
+            error["path"] = json!(path);
+            error["line"] = json!(line);
+            error["column"] = json!(column);
+        }
+        Error::FileExists(path) | Error::FileNotFound(path) => error["path"] = json!(path),
+        Error::DeckExists(deck) | Error::DeckNotFound(deck) => error["deck"] = json!(deck),
+        _ => {}
+    }
+
+    error
+}
+
+/// Serializes report and adds path of file to it.
+fn report_json(report: impl serde::Serialize, path: &str) -> Result<Value> {
+    let mut report = serde_json::to_value(report)?;
+    report["path"] = json!(path);
+    Ok(report)
+}
+
+/// Prints line to stderr. `colored` decides whether to use colors by stdout, so they are
+/// switched to follow stderr while the line is formatted.
+fn eprint_line(line: impl FnOnce() -> String) {
+    use std::io::IsTerminal;
+
+    colored::control::set_override(colors_enabled(
+        |name| std::env::var(name).ok(),
+        std::io::stderr().is_terminal(),
+    ));
+    let line = line();
+    colored::control::unset_override();
+
+    eprintln!("{line}");
+}
+
+/// Same rules as in `colored`: `CLICOLOR_FORCE` and `NO_COLOR` take precedence, otherwise
+/// colors are used on terminal unless `CLICOLOR` is `0`.
+fn colors_enabled(var: impl Fn(&str) -> Option<String>, terminal: bool) -> bool {
+    if var("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+        return true;
+    }
+
+    if var("NO_COLOR").is_some() {
+        return false;
+    }
+
+    terminal && var("CLICOLOR").is_none_or(|v| v != "0")
+}
+
+/// Prints error to stderr and returns exit code of its class.
+fn print_error(context: &str, e: &Error) -> ExitCode {
+    eprint_line(|| format!("{}{}", context.red(), e.to_string().bold().red()));
+    exit::failure(e)
+}
+
+/// Prints error of reading config file, command isn't run in this case.
+pub fn print_config_error(e: Error, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(Err(e), exit::SUCCESS);
+    }
+
+    print_error("error reading config: ", &e)
 }
 
-pub fn print_new_deck(res: Result<u64>, deck_name: &str) {
+pub fn print_new_deck(res: Result<u64>, deck_name: &str, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(
+            res.map(|id| json!({ "deck": deck_name, "id": id })),
+            exit::SUCCESS,
+        );
+    }
+
     match res {
         Ok(deck_id) => {
             println!(
@@ -34,19 +154,17 @@ pub fn print_new_deck(res: Result<u64>, deck_name: &str) {
                 deck_id.to_string().bold().blue(),
                 "'".green()
             );
+            ExitCode::SUCCESS
         }
-        Err(e) => {
-            println!(
-                "{}{}{}",
-                "error creating deck".red(),
-                ": ".red(),
-                e.to_string().bold().red()
-            );
-        }
+        Err(e) => print_error("error creating deck: ", &e),
     }
 }
 
-pub fn print_decklist(decks: Result<Vec<String>>) {
+pub fn print_decklist(decks: Result<Vec<String>>, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(decks.map(|decks| json!({ "decks": decks })), exit::SUCCESS);
+    }
+
     match decks {
         Ok(decks) => {
             if decks.is_empty() {
@@ -57,18 +175,26 @@ pub fn print_decklist(decks: Result<Vec<String>>) {
                     println!("{} {}", "-".green(), deck.bold().blue());
                 }
             }
+            ExitCode::SUCCESS
         }
-        Err(e) => {
-            println!(
-                "{}{}",
-                "error fetching deck list: ".red(),
-                e.to_string().bold().red()
-            );
-        }
+        Err(e) => print_error("error fetching deck list: ", &e),
     }
 }
 
-pub fn print_deck2md(file: Result<String>, deck: &str) {
+/// Prints result of export of deck, `format` is name of file format used in error message.
+pub fn print_export(
+    file: Result<String>,
+    deck: &str,
+    format: &str,
+    output: OutputFormat,
+) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(
+            file.map(|file| json!({ "deck": deck, "format": format, "path": file })),
+            exit::SUCCESS,
+        );
+    }
+
     match file {
         Ok(file) => {
             println!(
@@ -79,79 +205,373 @@ pub fn print_deck2md(file: Result<String>, deck: &str) {
                 file.bold().blue(),
                 "' file".green()
             );
+            ExitCode::SUCCESS
         }
-        Err(e) => {
-            println!(
-                "{}{}",
-                "error converting deck to markdown: ".red(),
-                e.to_string().bold().red()
-            );
-        }
+        Err(e) => print_error(&format!("error converting deck to {format}: "), &e),
     }
 }
 
-pub fn print_deck2json(file: Result<String>, deck: &str) {
+/// Prints result of offline conversion of file, `format` is name of target format.
+pub fn print_convert(
+    file: Result<String>,
+    path: &str,
+    format: &str,
+    output: OutputFormat,
+) -> ExitCode {
+    if output == OutputFormat::Json {
+        return print_json(
+            file.map(|file| json!({ "source": path, "format": format, "path": file })),
+            exit::SUCCESS,
+        );
+    }
+
     match file {
         Ok(file) => {
             println!(
                 "{}{}{}{}{}",
-                "deck '".green(),
-                deck.bold().blue(),
-                "' was written to '".green(),
+                "file '".green(),
+                path.bold().blue(),
+                "' was converted to '".green(),
                 file.bold().blue(),
                 "' file".green()
             );
+            ExitCode::SUCCESS
         }
-        Err(e) => {
+        Err(e) => print_error(&format!("error converting file to {format}: "), &e),
+    }
+}
+
+/// Prints result of import of deck, `format` is name of file format used in error message.
+/// Import with failed cards isn't successful, even if other cards were added.
+pub fn print_import(
+    report: Result<ImportReport>,
+    path: &str,
+    format: &str,
+    output: OutputFormat,
+) -> ExitCode {
+    if output == OutputFormat::Json {
+        let code = match &report {
+            Ok(report) if !report.failures.is_empty() => exit::ANKI_CONNECT,
+            _ => exit::SUCCESS,
+        };
+        return print_json(report.and_then(|report| report_json(report, path)), code);
+    }
+
+    match report {
+        Ok(report) => {
             println!(
-                "{}{}",
-                "error converting deck to json: ".red(),
-                e.to_string().bold().red()
+                "{}{}{}{}{}",
+                "deck '".green(),
+                report.deck.bold().blue(),
+                if report.new_deck {
+                    "' was created from '"
+                } else {
+                    "' was updated from '"
+                }
+                .green(),
+                path.bold().blue(),
+                "' file".green()
             );
+            print_import_report(&report);
+
+            if report.failures.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(exit::ANKI_CONNECT)
+            }
         }
+        Err(e) => print_error(&format!("error converting {format} to deck: "), &e),
     }
 }
 
-pub fn print_md2deck(deck: Result<String>, path: &str) {
-    match deck {
-        Ok(deck) => {
+fn print_import_report(report: &ImportReport) {
+    println!(
+        "{}{}",
+        "cards added: ".green(),
+        report.added.to_string().bold().blue()
+    );
+
+    if report.skipped > 0 {
+        println!(
+            "{}{}",
+            "cards skipped as duplicates: ".yellow(),
+            report.skipped.to_string().bold().yellow()
+        );
+    }
+
+    if report.media > 0 {
+        println!(
+            "{}{}",
+            "media files uploaded: ".green(),
+            report.media.to_string().bold().blue()
+        );
+    }
+
+    if report.failures.is_empty() {
+        return;
+    }
+
+    eprint_line(|| {
+        format!(
+            "{}{}",
+            "cards failed: ".red(),
+            report.failures.len().to_string().bold().red()
+        )
+    });
+
+    for failure in &report.failures {
+        eprint_line(|| {
+            format!(
+                "{} {}{}{}{}",
+                "-".red(),
+                format!("#{} '", failure.position).red(),
+                failure.front.bold().red(),
+                "': ".red(),
+                failure.error.red()
+            )
+        });
+    }
+}
+
+/// Sync with failed changes isn't successful, even if other changes were applied.
+pub fn print_sync(report: Result<SyncReport>, path: &str, output: OutputFormat) -> ExitCode {
+    if output == OutputFormat::Json {
+        let code = match &report {
+            Ok(report) if !report.failures.is_empty() => exit::ANKI_CONNECT,
+            _ => exit::SUCCESS,
+        };
+        return print_json(report.and_then(|report| report_json(report, path)), code);
+    }
+
+    match report {
+        Ok(report) => {
             println!(
                 "{}{}{}{}{}",
-                "deck '".green(),
-                deck.bold().blue(),
-                "' was created from '".green(),
+                if report.dry_run {
+                    "planned changes of deck '"
+                } else {
+                    "deck '"
+                }
+                .green(),
+                report.deck.bold().blue(),
+                if report.dry_run {
+                    "' to sync with '"
+                } else {
+                    "' was synced with '"
+                }
+                .green(),
                 path.bold().blue(),
                 "' file".green()
             );
-        }
-        Err(e) => {
+
+            for change in &report.changes {
+                print_sync_change(change);
+            }
+
+            let count = |f: fn(&SyncChange) -> bool| {
+                report.changes.iter().filter(|c| f(c)).count().to_string()
+            };
+
             println!(
-                "{}{}",
-                "error converting markdown to deck: ".red(),
-                e.to_string().bold().red()
+                "{}{}{}{}{}{}{}{}",
+                "added: ".green(),
+                count(|c| matches!(c, SyncChange::Add(_))).bold().blue(),
+                ", updated: ".green(),
+                count(|c| matches!(c, SyncChange::Update { .. }))
+                    .bold()
+                    .blue(),
+                ", deleted: ".green(),
+                count(|c| matches!(c, SyncChange::Delete { .. }))
+                    .bold()
+                    .blue(),
+                ", unchanged: ".green(),
+                report.unchanged.to_string().bold().blue()
             );
+
+            if report.media > 0 {
+                println!(
+                    "{}{}",
+                    "media files uploaded: ".green(),
+                    report.media.to_string().bold().blue()
+                );
+            }
+
+            if report.failures.is_empty() {
+                return ExitCode::SUCCESS;
+            }
+
+            eprint_line(|| {
+                format!(
+                    "{}{}",
+                    "changes failed: ".red(),
+                    report.failures.len().to_string().bold().red()
+                )
+            });
+
+            for failure in &report.failures {
+                eprint_line(|| {
+                    format!(
+                        "{} '{}'{}{}",
+                        "-".red(),
+                        failure.change.front().bold().red(),
+                        ": ".red(),
+                        failure.error.red()
+                    )
+                });
+            }
+
+            ExitCode::from(exit::ANKI_CONNECT)
         }
+        Err(e) => print_error("error syncing deck: ", &e),
+    }
+}
+
+fn print_sync_change(change: &SyncChange) {
+    match change {
+        SyncChange::Add(card) => println!("{} {}", "+".green(), card.front().green()),
+        SyncChange::Update {
+            front,
+            fields,
+            tags,
+            ..
+        } => println!(
+            "{} {} {}",
+            "~".yellow(),
+            front.yellow(),
+            format!(
+                "({})",
+                fields
+                    .keys()
+                    .map(String::as_str)
+                    .chain(tags.as_ref().map(|_| "tags"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .dimmed()
+        ),
+        SyncChange::Delete { front, .. } => println!("{} {}", "-".red(), front.red()),
     }
 }
 
-pub fn print_json2deck(deck: Result<String>, path: &str) {
-    match deck {
-        Ok(deck) => {
+/// Like `diff` utility, returns code 1 if deck differs from file.
+pub fn print_diff(
+    diff: Result<DeckDiff>,
+    path: &str,
+    format: DiffFormat,
+    output: OutputFormat,
+) -> ExitCode {
+    if output == OutputFormat::Json {
+        let code = match &diff {
+            Ok(diff) if !diff.is_empty() => exit::DIFFERENCES,
+            _ => exit::SUCCESS,
+        };
+        return print_json(diff.and_then(|diff| report_json(diff, path)), code);
+    }
+
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(e) => return print_error("error comparing deck with file: ", &e),
+    };
+    let code = if diff.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(exit::DIFFERENCES)
+    };
+
+    if format == DiffFormat::Json {
+        match serde_json::to_string_pretty(&diff) {
+            Ok(json) => println!("{json}"),
+            Err(e) => return print_error("error serializing diff: ", &e.into()),
+        }
+        return code;
+    }
+
+    println!(
+        "{}{}{}{}{}",
+        "deck '".green(),
+        diff.deck.bold().blue(),
+        if diff.is_empty() {
+            "' matches '"
+        } else {
+            "' differs from '"
+        }
+        .green(),
+        path.bold().blue(),
+        "' file".green()
+    );
+
+    for card in &diff.added {
+        println!("{} {}", "+".green(), card.front().green());
+    }
+
+    for card in &diff.removed {
+        println!("{} {}", "-".red(), card.front().red());
+    }
+
+    for card in &diff.modified {
+        println!("{} {}", "~".yellow(), card.front.yellow());
+
+        for (name, change) in &card.fields {
             println!(
-                "{}{}{}{}{}",
-                "deck '".green(),
-                deck.bold().blue(),
-                "' was created from '".green(),
-                path.bold().blue(),
-                "' file".green()
+                "    {}{} {} {} {}",
+                name.bold(),
+                ":".bold(),
+                change.old.red(),
+                "->".dimmed(),
+                change.new.green()
             );
         }
-        Err(e) => {
+
+        if let Some(change) = &card.tags {
             println!(
-                "{}{}",
-                "error converting json to deck: ".red(),
-                e.to_string().bold().red()
+                "    {}{} {} {} {}",
+                "tags".bold(),
+                ":".bold(),
+                change.old.join(" ").red(),
+                "->".dimmed(),
+                change.new.join(" ").green()
             );
         }
     }
+
+    println!(
+        "{}{}{}{}{}{}{}{}",
+        "added: ".green(),
+        diff.added.len().to_string().bold().blue(),
+        ", removed: ".green(),
+        diff.removed.len().to_string().bold().blue(),
+        ", modified: ".green(),
+        diff.modified.len().to_string().bold().blue(),
+        ", unchanged: ".green(),
+        diff.unchanged.to_string().bold().blue()
+    );
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_enabled() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert!(colors_enabled(env(&[]), true));
+        assert!(!colors_enabled(env(&[]), false));
+        assert!(!colors_enabled(env(&[("NO_COLOR", "1")]), true));
+        assert!(!colors_enabled(env(&[("CLICOLOR", "0")]), true));
+        assert!(colors_enabled(env(&[("CLICOLOR_FORCE", "1")]), false));
+        assert!(colors_enabled(
+            env(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]),
+            false
+        ));
+        assert!(!colors_enabled(env(&[("CLICOLOR_FORCE", "0")]), false));
+    }
 }
@@ -9,23 +9,37 @@ use wiremock::{
     matchers::{body_json_schema, method, path},
 };
 
-use anki_multitool_ds::http::{
-    request::{
-        AddNoteParams, ApiMethod, ApiRequest, CreateDeckParams, Note, NotesInfoParams, Params,
+use anki_multitool_ds::{
+    card::Fields,
+    http::{
+        request::{
+            AddNoteParams, ApiKey, ApiMethod, ApiRequest, CreateDeckParams, DeleteDecksParams,
+            DeleteNotesParams, DuplicateScope, MediaFileParams, MultiParams, Note, NoteFields,
+            NotesInfoParams, Params, StoreMediaFileParams, UpdateNoteFieldsParams,
+            UpdateNoteTagsParams,
+        },
+        response::{ApiResponse, DUPLICATE_NOTE_ERROR, NotesInfoResponseData},
     },
-    response::{ApiResponse, NotesInfoResponseData},
 };
 
 type Decks = Arc<Mutex<HashMap<String, (u64, HashMap<u64, NotesInfoResponseData>)>>>;
+/// Files of media folder keyed by name, content is kept in base64 as it's sent.
+type Media = Arc<Mutex<HashMap<String, String>>>;
 
 #[macro_export]
 macro_rules! with_mserver {
+    (use_port $port:expr; use_api_key $key:expr; $($body:stmt;)*) => {
+        {
+            let __mock_server = MockAnkiServer::with_api_key("localhost", $port, $key).await.expect("failed to create mock server");
+            $($body)*
+        }
+    };
     (use_port $port:expr; $($body:stmt;)*) => {
         {
             let __mock_server = MockAnkiServer::new("localhost", $port).await.expect("failed to create mock server");
             $($body)*
         }
-    }
+    };
 }
 
 pub struct MockAnkiServer {
@@ -35,6 +49,15 @@ pub struct MockAnkiServer {
 
 impl MockAnkiServer {
     pub async fn new(host: &str, port: u16) -> Result<Self> {
+        Self::start(host, port, None).await
+    }
+
+    /// Server which rejects requests without given key, like AnkiConnect with `apiKey` set.
+    pub async fn with_api_key(host: &str, port: u16, key: &str) -> Result<Self> {
+        Self::start(host, port, Some(ApiKey::new(key))).await
+    }
+
+    async fn start(host: &str, port: u16, api_key: Option<ApiKey>) -> Result<Self> {
         let mock_server = MockServer::builder()
             .listener(TcpListener::bind(format!("{host}:{port}"))?)
             .start()
@@ -46,7 +69,7 @@ impl MockAnkiServer {
                 Mock::given(method("POST"))
                     .and(path("/"))
                     .and(body_json_schema::<ApiRequest>)
-                    .respond_with(Responder::new(state.clone())),
+                    .respond_with(Responder::new(state.clone(), api_key)),
             )
             .await;
 
@@ -60,6 +83,7 @@ impl MockAnkiServer {
 #[derive(Clone)]
 struct State {
     decks: Decks,
+    media: Media,
     deck_id_counter: Arc<AtomicUsize>,
     note_id_counter: Arc<AtomicUsize>,
 }
@@ -68,6 +92,7 @@ impl State {
     fn new() -> Self {
         State {
             decks: Arc::new(Mutex::new(HashMap::new())),
+            media: Arc::new(Mutex::new(HashMap::new())),
             deck_id_counter: Arc::new(AtomicUsize::new(0)),
             note_id_counter: Arc::new(AtomicUsize::new(0)),
         }
@@ -111,24 +136,114 @@ impl State {
         }
     }
 
-    fn add_note(&self, note: Note) -> Option<u64> {
+    fn add_note(&self, note: Note) -> Result<u64, String> {
         let deck_name = note.deck.clone();
+        let dup_deck_name = note.options.options.deck_name.clone();
+        let check_all_decks = note.options.dup_scope == DuplicateScope::Collection;
+        let allow_dups = note.options.allow_dups;
         let note = NotesInfoResponseData::from(note);
+        let first_field = |n: &NotesInfoResponseData| {
+            n.fields
+                .values()
+                .min_by_key(|data| data.order)
+                .map(|data| data.value.clone())
+        };
 
-        if self.decks.lock().unwrap().contains_key(deck_name.as_str()) {
-            let note_id = self.next_note_id();
-            self.decks
-                .lock()
-                .unwrap()
-                .get_mut(deck_name.as_str())
-                .unwrap()
-                .1
-                .insert(note_id, note.with_id(note_id));
+        let mut decks = self.decks.lock().unwrap();
+
+        if !decks.contains_key(deck_name.as_str()) {
+            return Err(format!("deck was not found: {deck_name}"));
+        }
+
+        if first_field(&note).is_none_or(|value| value.is_empty()) {
+            return Err("cannot create note because it is empty".to_string());
+        }
+
+        let is_duplicate = decks
+            .iter()
+            .filter(|(name, _)| check_all_decks || **name == dup_deck_name)
+            .flat_map(|(_, (_, notes))| notes.values())
+            .any(|n| n.model_name == note.model_name && first_field(n) == first_field(&note));
+
+        if is_duplicate && !allow_dups {
+            return Err(DUPLICATE_NOTE_ERROR.to_string());
+        }
+
+        let note_id = self.next_note_id();
+        decks
+            .get_mut(deck_name.as_str())
+            .unwrap()
+            .1
+            .insert(note_id, note.with_id(note_id));
+
+        Ok(note_id)
+    }
+
+    fn update_note_fields(&self, note_id: u64, fields: Fields) -> Result<(), String> {
+        let mut decks = self.decks.lock().unwrap();
+        let note = decks
+            .values_mut()
+            .find_map(|(_, notes)| notes.get_mut(&note_id))
+            .ok_or_else(|| format!("note was not found: {note_id}"))?;
+
+        if let Some(name) = fields.keys().find(|name| !note.fields.contains_key(*name)) {
+            return Err(format!("field was not found: {name}"));
+        }
+
+        for (name, value) in fields {
+            if let Some(data) = note.fields.get_mut(&name) {
+                data.value = value;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_note_tags(&self, note_id: u64, tags: Vec<String>) -> Result<(), String> {
+        let mut decks = self.decks.lock().unwrap();
+        let note = decks
+            .values_mut()
+            .find_map(|(_, notes)| notes.get_mut(&note_id))
+            .ok_or_else(|| format!("note was not found: {note_id}"))?;
+
+        note.tags = tags;
+        Ok(())
+    }
+
+    fn delete_notes(&self, note_ids: &[u64]) {
+        for (_, notes) in self.decks.lock().unwrap().values_mut() {
+            notes.retain(|id, _| !note_ids.contains(id));
+        }
+    }
+
+    fn delete_decks(&self, deck_names: &[String], cards_too: bool) -> Result<(), String> {
+        if !cards_too {
+            return Err("since Anki 2.1.28 it's not possible to delete decks without deleting cards as well".to_string());
+        }
 
-            return Some(note_id);
+        self.decks
+            .lock()
+            .unwrap()
+            .retain(|name, _| !deck_names.contains(name));
+
+        Ok(())
+    }
+
+    fn store_media_file(&self, filename: String, data: String) -> Result<String, String> {
+        if filename.is_empty() || filename.contains(['/', '\\']) {
+            return Err(format!("invalid media file name: {filename}"));
         }
 
-        None
+        self.media.lock().unwrap().insert(filename.clone(), data);
+        Ok(filename)
+    }
+
+    fn retrieve_media_file(&self, filename: &str) -> Option<String> {
+        self.media.lock().unwrap().get(filename).cloned()
+    }
+
+    fn delete_media_file(&self, filename: &str) {
+        self.media.lock().unwrap().remove(filename);
     }
 
     fn notes_info(&self, deck_name: &str) -> Option<Vec<NotesInfoResponseData>> {
@@ -142,11 +257,121 @@ impl State {
 
 struct Responder {
     state: State,
+    api_key: Option<ApiKey>,
 }
 
 impl Responder {
-    pub fn new(state: State) -> Self {
-        Responder { state }
+    pub fn new(state: State, api_key: Option<ApiKey>) -> Self {
+        Responder { state, api_key }
+    }
+}
+
+impl Responder {
+    /// Returns `None` if request is invalid.
+    fn process(&self, request: ApiRequest) -> Option<ApiResponse> {
+        match request.action {
+            ApiMethod::DeckNames => Some(ApiResponse::with_names_ok_res(self.state.deck_names())),
+            ApiMethod::DeckNamesAndIds => Some(ApiResponse::with_names_and_ids_ok_res(
+                self.state.deck_names_and_ids(),
+            )),
+            ApiMethod::CreateDeck => match request.params {
+                Some(Params::CreateDeck(CreateDeckParams { deck })) if !deck.is_empty() => {
+                    Some(ApiResponse::with_id_ok_res(self.state.create_deck(deck)))
+                }
+                _ => None,
+            },
+            ApiMethod::AddNote => match request.params {
+                Some(Params::AddNote(AddNoteParams { note })) => {
+                    Some(match self.state.add_note(note) {
+                        Ok(id) => ApiResponse::with_id_ok_res(id),
+                        Err(e) => ApiResponse::as_error(e),
+                    })
+                }
+                _ => None,
+            },
+            ApiMethod::NotesInfo => match request.params {
+                Some(Params::NotesInfo(NotesInfoParams { query: deck })) => self
+                    .state
+                    .notes_info(deck.as_str())
+                    .map(ApiResponse::with_notes_info_ok_res),
+                _ => None,
+            },
+            ApiMethod::DeleteNotes => match request.params {
+                Some(Params::DeleteNotes(DeleteNotesParams { notes })) => {
+                    self.state.delete_notes(&notes);
+                    Some(ApiResponse::as_empty_success())
+                }
+                _ => None,
+            },
+            ApiMethod::DeleteDecks => match request.params {
+                Some(Params::DeleteDecks(DeleteDecksParams { decks, cards_too })) => {
+                    Some(match self.state.delete_decks(&decks, cards_too) {
+                        Ok(()) => ApiResponse::as_empty_success(),
+                        Err(e) => ApiResponse::as_error(e),
+                    })
+                }
+                _ => None,
+            },
+            ApiMethod::UpdateNoteFields => match request.params {
+                Some(Params::UpdateNoteFields(UpdateNoteFieldsParams {
+                    note: NoteFields { id, fields },
+                })) => Some(match self.state.update_note_fields(id, fields) {
+                    Ok(()) => ApiResponse::as_empty_success(),
+                    Err(e) => ApiResponse::as_error(e),
+                }),
+                _ => None,
+            },
+            ApiMethod::UpdateNoteTags => match request.params {
+                Some(Params::UpdateNoteTags(UpdateNoteTagsParams { note, tags })) => {
+                    Some(match self.state.update_note_tags(note, tags) {
+                        Ok(()) => ApiResponse::as_empty_success(),
+                        Err(e) => ApiResponse::as_error(e),
+                    })
+                }
+                _ => None,
+            },
+            ApiMethod::StoreMediaFile => match request.params {
+                Some(Params::StoreMediaFile(StoreMediaFileParams { filename, data })) => {
+                    Some(match self.state.store_media_file(filename, data) {
+                        Ok(filename) => ApiResponse::with_text_ok_res(filename),
+                        Err(e) => ApiResponse::as_error(e),
+                    })
+                }
+                _ => None,
+            },
+            ApiMethod::RetrieveMediaFile => match request.params {
+                Some(Params::MediaFile(MediaFileParams { filename })) => {
+                    // the same as AnkiConnect, `false` is returned for missing file
+                    Some(match self.state.retrieve_media_file(&filename) {
+                        Some(data) => ApiResponse::with_text_ok_res(data),
+                        None => ApiResponse::with_flag_ok_res(false),
+                    })
+                }
+                _ => None,
+            },
+            ApiMethod::DeleteMediaFile => match request.params {
+                Some(Params::MediaFile(MediaFileParams { filename })) => {
+                    self.state.delete_media_file(&filename);
+                    Some(ApiResponse::as_empty_success())
+                }
+                _ => None,
+            },
+            ApiMethod::Multi => match request.params {
+                Some(Params::Multi(MultiParams { actions })) => {
+                    Some(ApiResponse::with_multi_ok_res(
+                        actions
+                            .into_iter()
+                            .map(|action| {
+                                self.process(action).unwrap_or_else(|| {
+                                    ApiResponse::as_error("invalid request".to_string())
+                                })
+                            })
+                            .collect(),
+                    ))
+                }
+                _ => None,
+            },
+        }
     }
 }
 
@@ -154,59 +379,26 @@ impl Respond for Responder {
     fn respond(&self, request: &Request) -> ResponseTemplate {
         let body = request.body.as_ref();
         let request_str = String::from_utf8_lossy(body);
-        let ok_response = ResponseTemplate::new(200);
         let bad_response = ResponseTemplate::new(400)
             .set_body_json(ApiResponse::as_error("invalid request".to_string()));
 
-        match serde_json::from_str::<ApiRequest>(&request_str) {
-            Ok(request) => match request.action {
-                ApiMethod::DeckNames => ok_response
-                    .set_body_json(ApiResponse::with_names_ok_res(self.state.deck_names())),
-                ApiMethod::DeckNamesAndIds => ok_response.set_body_json(
-                    ApiResponse::with_names_and_ids_ok_res(self.state.deck_names_and_ids()),
-                ),
-                ApiMethod::CreateDeck => {
-                    if let Some(Params::CreateDeck(CreateDeckParams { deck })) = request.params {
-                        if deck.is_empty() {
-                            return bad_response;
-                        }
-
-                        ok_response.set_body_json(ApiResponse::with_id_ok_res(
-                            self.state.create_deck(deck),
-                        ))
-                    } else {
-                        bad_response
-                    }
+        match serde_json::from_str::<ApiRequest>(&request_str)
+            .ok()
+            .and_then(|request| {
+                if self.api_key.is_some() && request.key != self.api_key {
+                    // the same response as AnkiConnect gives
+                    return Some(ApiResponse::as_error(
+                        "valid api key must be provided".to_string(),
+                    ));
                 }
-                ApiMethod::AddNote => {
-                    if let Some(Params::AddNote(AddNoteParams { note })) = request.params {
-                        let res = self.state.add_note(note);
-
-                        if res.is_none() {
-                            return bad_response;
-                        }
-
-                        ok_response.set_body_json(ApiResponse::with_id_ok_res(res.unwrap()))
-                    } else {
-                        bad_response
-                    }
-                }
-                ApiMethod::NotesInfo => {
-                    if let Some(Params::NotesInfo(NotesInfoParams { query: deck })) = request.params
-                    {
-                        let res = self.state.notes_info(deck.as_str());
-
-                        if res.is_none() {
-                            return bad_response;
-                        }
-
-                        ok_response.set_body_json(ApiResponse::with_notes_info_ok_res(res.unwrap()))
-                    } else {
-                        bad_response
-                    }
-                }
-            },
-            Err(_) => bad_response,
+
+                self.process(request)
+            }) {
+            Some(response) if response.error.is_some() => {
+                ResponseTemplate::new(400).set_body_json(response)
+            }
+            Some(response) => ResponseTemplate::new(200).set_body_json(response),
+            None => bad_response,
         }
     }
 }
@@ -1,11 +1,16 @@
-use anyhow::Result;
-use std::{fmt, fs::File, marker::PhantomData};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{fs::File, marker::PhantomData};
 
+use anki_multitool_ds::error::Result;
+
+/// Stages prepared records in a temporary file and replays them only when every record
+/// was prepared successfully. Each record is stored as one line of JSON, so arbitrary
+/// content (newlines, separators, unicode) survives the round trip unchanged.
 pub struct FileCommitBuffer<PA, C, D, PD>
 where
     PA: AsyncFn(D) -> Result<PD>,
-    C: AsyncFn(String) -> Result<()>,
-    PD: fmt::Display,
+    C: AsyncFn(PD) -> Result<()>,
+    PD: Serialize + DeserializeOwned,
 {
     tmpfile: File,
     prepare_action: PA,
@@ -13,8 +18,12 @@ where
     _type: PhantomData<D>,
 }
 
-impl<D, PA: AsyncFn(D) -> Result<PD>, C: AsyncFn(String) -> Result<()>, PD: fmt::Display>
-    FileCommitBuffer<PA, C, D, PD>
+impl<
+    D,
+    PA: AsyncFn(D) -> Result<PD>,
+    C: AsyncFn(PD) -> Result<()>,
+    PD: Serialize + DeserializeOwned,
+> FileCommitBuffer<PA, C, D, PD>
 {
     pub fn new(prepare_action: PA, commit: C) -> Result<Self> {
         use tempfile::tempfile;
@@ -28,10 +37,17 @@ impl<D, PA: AsyncFn(D) -> Result<PD>, C: AsyncFn(String) -> Result<()>, PD: fmt:
     }
 
     pub async fn exec_and_commit(&mut self, consuming_data: impl Iterator<Item = D>) -> Result<()> {
-        use std::io::Write;
+        use std::io::{BufWriter, Write};
+
+        {
+            let mut writer = BufWriter::new(&self.tmpfile);
+
+            for data in consuming_data {
+                serde_json::to_writer(&mut writer, &(self.prepare_action)(data).await?)?;
+                writeln!(writer)?;
+            }
 
-        for data in consuming_data {
-            writeln!(self.tmpfile, "{}", (self.prepare_action)(data).await?)?;
+            writer.flush()?;
         }
 
         self.commit().await
@@ -44,7 +60,7 @@ impl<D, PA: AsyncFn(D) -> Result<PD>, C: AsyncFn(String) -> Result<()>, PD: fmt:
         self.tmpfile.rewind()?;
 
         for line in BufReader::new(&self.tmpfile).lines() {
-            (self.commit)(line?).await?;
+            (self.commit)(serde_json::from_str(line?.as_str())?).await?;
         }
 
         Ok(())
@@ -60,6 +76,8 @@ mod tests {
     };
     use tempfile::NamedTempFile;
 
+    use anki_multitool_ds::error::Error;
+
     #[tokio::test]
     pub async fn test_file_commit_buffer() {
         use std::io::{BufRead, BufReader, Seek};
@@ -110,17 +128,17 @@ mod tests {
 
         assert!(
             FileCommitBuffer::new(
-                async |data| {
+                async |data: &str| {
                     let counter = Arc::clone(&counter);
 
                     if counter.load(Ordering::Relaxed) > 2 {
-                        return Err(anyhow::anyhow!("simulated failure on commit"));
+                        return Err(Error::invalid("simulated failure on commit"));
                     }
 
                     counter.fetch_add(1, Ordering::Relaxed);
-                    Ok(data)
+                    Ok(data.to_string())
                 },
-                async |data| {
+                async |data: String| {
                     writeln!(
                         Arc::clone(&file).lock().expect("failed to get file"),
                         "{data}"
@@ -141,4 +159,60 @@ mod tests {
             0
         );
     }
+
+    #[tokio::test]
+    pub async fn test_file_commit_buffer_structured_records() {
+        use serde::Deserialize;
+
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+        struct Record {
+            front: String,
+            back: String,
+        }
+
+        let records = vec![
+            Record {
+                front: "T-cell".to_string(),
+                back: "a type of lymphocyte - part of immune system".to_string(),
+            },
+            Record {
+                front: "2-3".to_string(),
+                back: "-1".to_string(),
+            },
+            Record {
+                front: "multi\nline".to_string(),
+                back: "first line\nsecond line\r\n\nthird line".to_string(),
+            },
+            Record {
+                front: "<b>bold</b> & <i>italic</i>".to_string(),
+                back: "<div class=\"a\">x</div><br>{\"key\": [1, 2]}".to_string(),
+            },
+            Record {
+                front: "Як справи? 你好 🦀".to_string(),
+                back: "Добре - 很好 ✅".to_string(),
+            },
+            Record {
+                front: String::new(),
+                back: "  leading and trailing spaces  ".to_string(),
+            },
+        ];
+        let committed = Arc::new(Mutex::new(Vec::new()));
+
+        FileCommitBuffer::new(
+            async |record: Record| Ok(record),
+            async |record| {
+                Arc::clone(&committed)
+                    .lock()
+                    .expect("failed to get records")
+                    .push(record);
+                Ok(())
+            },
+        )
+        .expect("failed to create FileCommitBuffer")
+        .exec_and_commit(records.clone().into_iter())
+        .await
+        .expect("failed to commit data");
+
+        assert_eq!(*committed.lock().expect("failed to get records"), records);
+    }
 }
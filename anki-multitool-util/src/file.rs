@@ -1,12 +1,13 @@
-use anyhow::{Result, anyhow};
 use std::path::Path;
 
+use anki_multitool_ds::error::{Error, Result};
+
 pub fn to_file_name<P: AsRef<Path>>(path: P) -> Result<String> {
     Ok(path
         .as_ref()
         .file_stem()
         .and_then(|f| f.to_str())
-        .ok_or_else(|| anyhow!("invalid file name: {}", path.as_ref().display()))?
+        .ok_or_else(|| Error::invalid(format!("invalid file name: {}", path.as_ref().display())))?
         .to_string())
 }
 
@@ -13,10 +13,34 @@ complete -c anki-mtool -f -n "__fish_use_subcommand" -a "json2deck" -d "Import a
 complete -c anki-mtool -f -n "__fish_use_subcommand" -a "deck2json" -d "Export a deck from Anki to a JSON file"
 complete -c anki-mtool -f -n "__fish_use_subcommand" -a "md2deck" -d "Import a deck from a Markdown file into Anki"
 complete -c anki-mtool -f -n "__fish_use_subcommand" -a "deck2md" -d "Export a deck from Anki to a Markdown file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "csv2deck" -d "Import a deck from a CSV or TSV file into Anki"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "deck2csv" -d "Export a deck from Anki to a CSV or TSV file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "yaml2deck" -d "Import a deck from a YAML file into Anki"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "deck2yaml" -d "Export a deck from Anki to a YAML file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "toml2deck" -d "Import a deck from a TOML file into Anki"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "deck2toml" -d "Export a deck from Anki to a TOML file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "md2apkg" -d "Build an Anki package from a Markdown file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "json2apkg" -d "Build an Anki package from a JSON file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "apkg2md" -d "Convert an Anki package to a Markdown file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "sync" -d "Sync a deck in Anki with a file"
+complete -c anki-mtool -f -n "__fish_use_subcommand" -a "diff" -d "Show difference between a deck and a file"
 
 # File completions for commands that take file paths
-complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck" -a "(__fish_complete_suffix .json)" -d "JSON file"
-complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck" -a "(__fish_complete_suffix .md)" -d "Markdown file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck json2apkg" -a "(__fish_complete_suffix .json)" -d "JSON file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck md2apkg" -a "(__fish_complete_suffix .md)" -d "Markdown file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from csv2deck" -a "(__fish_complete_suffix .csv; __fish_complete_suffix .tsv; __fish_complete_suffix .txt)" -d "CSV file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from yaml2deck" -a "(__fish_complete_suffix .yaml; __fish_complete_suffix .yml)" -d "YAML file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from toml2deck" -a "(__fish_complete_suffix .toml)" -d "TOML file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from apkg2md" -a "(__fish_complete_suffix .apkg)" -d "Anki package"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from sync diff" -a "(__fish_complete_suffix .json; __fish_complete_suffix .md; __fish_complete_suffix .csv; __fish_complete_suffix .tsv; __fish_complete_suffix .yaml; __fish_complete_suffix .toml; __fish_complete_suffix .apkg)" -d "Deck file"
+
+# Options of import commands
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l tag -r -d "Tag added to every imported card"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l batch-size -r -d "Number of notes sent to Anki with single request"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l atomic -d "Roll back the whole import on failure"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l append -d "Add cards to existing deck, skipping duplicates"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l into -r -a "(__anki_mtool_complete_decks)" -d "Deck to import cards into"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from json2deck md2deck csv2deck yaml2deck toml2deck" -l duplicate-scope -r -a "deck collection" -d "Where to look for duplicates"
 
 # Dynamic deck name completion function (requires Anki to be running)
 function __anki_mtool_complete_decks
@@ -25,4 +49,32 @@ function __anki_mtool_complete_decks
 end
 
 # Deck name completions for commands that need deck names
-complete -c anki-mtool -f -n "__fish_seen_subcommand_from newdeck deck2json deck2md" -a "(__anki_mtool_complete_decks)" -d "Anki deck"
\ No newline at end of file
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from newdeck deck2json deck2md deck2csv deck2yaml deck2toml diff" -a "(__anki_mtool_complete_decks)" -d "Anki deck"
+
+# Options of export commands
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from deck2json deck2md deck2csv deck2yaml deck2toml apkg2md" -l with-ids -d "Write ids of notes"
+
+# Options of CSV commands
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from csv2deck deck2csv sync diff" -l delimiter -r -a "tab , ;" -d "Delimiter of columns"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck deck2md md2apkg apkg2md sync diff" -l md-style -r -a "list headings" -d "Layout of Markdown file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck deck2md md2apkg apkg2md sync diff" -l heading-level -r -a "1 2 3 4 5 6" -d "Level of headings which are fronts of cards"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck deck2md apkg2md" -l subdecks -d "Top-level headings are subdecks"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from md2deck deck2md md2apkg apkg2md sync diff" -l separator -r -a "dash double-colon pipe question" -d "Separator of fields in list style"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from csv2deck" -l no-header -d "First row is a card"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from csv2deck" -l model -r -d "Note type of cards"
+
+# Options of sync command
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from sync" -l deck -r -a "(__anki_mtool_complete_decks)" -d "Deck to sync with"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from sync" -l delete -d "Delete cards which are absent in file"
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from sync" -l dry-run -d "Print planned changes without applying them"
+
+# Options of diff command
+complete -c anki-mtool -f -n "__fish_seen_subcommand_from diff" -l format -r -a "text json" -d "Format of output"
+
+# Global options
+complete -c anki-mtool -n "__fish_seen_subcommand_from decklist deck2json deck2md deck2csv deck2yaml deck2toml sync diff" -l collection -r -a "(__fish_complete_suffix .anki2)" -d "Read decks from local collection"
+complete -c anki-mtool -f -l output -r -a "text json" -d "Format of output"
+complete -c anki-mtool -f -l profile -r -d "Profile of config file"
+complete -c anki-mtool -f -l host -r -d "Host of AnkiConnect"
+complete -c anki-mtool -f -l port -r -d "Port of AnkiConnect"
+complete -c anki-mtool -l output-dir -r -a "(__fish_complete_directories)" -d "Directory of exported files"